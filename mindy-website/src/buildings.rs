@@ -1,7 +1,7 @@
 use std::borrow::Cow;
 
 use mindy::{
-    types::{LAccess, content::Item},
+    types::{ContentID, LAccess, Object, content::Item},
     vm::{
         Building, Content, CustomBuildingData, InstructionResult, LObject, LValue, LogicVM,
         instructions,
@@ -52,6 +52,10 @@ impl CustomBuildingData for WebMessageData {
             _ => return None,
         })
     }
+
+    fn config(&self, _: &Building) -> Object {
+        self.value.to_string_lossy().into()
+    }
 }
 
 pub struct WebSorterData {
@@ -104,6 +108,13 @@ impl CustomBuildingData for WebSorterData {
             _ => return None,
         })
     }
+
+    fn config(&self, _: &Building) -> Object {
+        match self.item {
+            Some(item) => ContentID::from(Content::Item(item)).into(),
+            None => Object::Null,
+        }
+    }
 }
 
 pub struct WebSwitchData {
@@ -148,4 +159,8 @@ impl CustomBuildingData for WebSwitchData {
             _ => return None,
         })
     }
+
+    fn config(&self, _: &Building) -> Object {
+        self.enabled.into()
+    }
 }
@@ -8,7 +8,7 @@ use embedded_graphics_web_simulator::{
 };
 use js_sys::JsString;
 use mindy::{
-    parser::LogicParser,
+    parser::{self, LogicParser},
     types::{LAccess, Object, ProcessorConfig, ProcessorLinkConfig, content},
     vm::{
         Building, BuildingData, Content, EmbeddedDisplayData, InstructionResult, LValue, LVar,
@@ -205,8 +205,9 @@ impl WebLogicVM {
         position: u32,
         code: &str,
         links: Box<[u32]>,
+        hot_patch: bool,
     ) -> Result<js_sys::Map, String> {
-        let ast = self.logic_parser.parse(code).map_err(|e| e.to_string())?;
+        let ast = parser::parse_with(&self.logic_parser, code).map_err(|e| e.to_string())?;
 
         let position = unpack_point(position);
         let building = self
@@ -221,23 +222,21 @@ impl WebLogicVM {
             ));
         };
 
-        processor
-            .update_config(
-                ast,
-                Some(
-                    &links
-                        .iter()
-                        .map(|p| {
-                            let p = unpack_point(*p);
-                            ProcessorLinkConfig::unnamed(p.x - position.x, p.y - position.y)
-                        })
-                        .collect::<Vec<_>>(),
-                ),
-                &self.vm,
-                building,
-                &self.globals,
-            )
-            .map_err(|e| e.to_string())?;
+        let links = links
+            .iter()
+            .map(|p| {
+                let p = unpack_point(*p);
+                ProcessorLinkConfig::unnamed(p.x - position.x, p.y - position.y)
+            })
+            .collect::<Vec<_>>();
+
+        let result = if hot_patch {
+            processor.hot_patch(ast, Some(&links), &self.vm, building, &self.globals)
+        } else {
+            processor.update_config(ast, Some(&links), &self.vm, building, &self.globals)
+        };
+        result.map_err(|e| e.to_string())?;
+        processor.set_source(Some(code.to_string()));
 
         let names = js_sys::Map::new();
         for link in processor.state.links() {
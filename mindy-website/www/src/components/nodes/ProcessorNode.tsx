@@ -1,11 +1,11 @@
-import { ActionIcon, Group, Textarea } from "@mantine/core";
+import { ActionIcon, Checkbox, Group, Textarea } from "@mantine/core";
 import {
     useNodeConnections,
     useReactFlow,
     type Node,
     type NodeProps,
 } from "@xyflow/react";
-import { useCallback, useEffect, useState } from "react";
+import { useCallback, useEffect, useRef, useState } from "react";
 import { FaXmark, FaCheck } from "react-icons/fa6";
 
 import { ProcessorKind } from "mindy-website";
@@ -34,6 +34,9 @@ export default function ProcessorNode(props: NodeProps<ProcessorNodeType>) {
     const [editCode, setEditCode] = useState(defaultCode);
     const [error, setError] = useState<string>();
 
+    // read from a ref so that toggling this doesn't reload the code
+    const keepState = useRef(false);
+
     const connections = useNodeConnections({ handleType: "source" });
     const reactFlow = useReactFlow<LogicVMNode>();
 
@@ -61,6 +64,7 @@ export default function ProcessorNode(props: NodeProps<ProcessorNodeType>) {
             position,
             code,
             links: new Uint32Array(links),
+            hotPatch: keepState.current,
         });
     }, [vm, position, code, connections, reactFlow]);
 
@@ -106,6 +110,16 @@ export default function ProcessorNode(props: NodeProps<ProcessorNodeType>) {
                 }}
             />
             <Group justify="flex-end" pt={2} gap={4}>
+                <Checkbox
+                    className="nodrag nopan"
+                    label="Keep state"
+                    title="Keep variables and the current position in the code when applying changes"
+                    size="xs"
+                    mr="auto"
+                    onChange={(e) => {
+                        keepState.current = e.currentTarget.checked;
+                    }}
+                />
                 <ActionIcon
                     className={`${classes.button} nodrag nopan`}
                     variant="filled"
@@ -50,6 +50,7 @@ interface SetProcessorCodeRequest {
     position: number;
     code: string;
     links: Uint32Array;
+    hotPatch: boolean;
 }
 
 interface SetSorterConfigRequest {
@@ -121,12 +121,17 @@ void init().then(() => {
             }
 
             case "setProcessorCode": {
-                const { position, code, links } = request;
+                const { position, code, links, hotPatch } = request;
 
                 let error = undefined;
                 let linkNames = undefined;
                 try {
-                    linkNames = vm.set_processor_config(position, code, links);
+                    linkNames = vm.set_processor_config(
+                        position,
+                        code,
+                        links,
+                        hotPatch,
+                    );
                 } catch (e: unknown) {
                     error = String(e);
                 }
@@ -0,0 +1,855 @@
+use std::{collections::HashMap, error::Error, mem};
+
+use lsp_server::{Connection, ErrorCode, Message, Notification, Request, Response};
+use lsp_types::{
+    CompletionItem, CompletionItemKind, CompletionOptions, CompletionParams, CompletionResponse,
+    CompletionTextEdit, Diagnostic, DiagnosticSeverity, DidChangeTextDocumentParams,
+    DidCloseTextDocumentParams, DidOpenTextDocumentParams, GotoDefinitionParams,
+    GotoDefinitionResponse, Hover, HoverContents, HoverParams, HoverProviderCapability,
+    InitializeParams, Location, MarkupContent, MarkupKind, OneOf, Position,
+    PublishDiagnosticsParams, Range, RenameParams, ServerCapabilities, TextDocumentPositionParams,
+    TextDocumentSyncCapability, TextDocumentSyncKind, TextEdit, Uri, WorkspaceEdit,
+    notification::{
+        DidChangeTextDocument, DidCloseTextDocument, DidOpenTextDocument, Notification as _,
+        PublishDiagnostics,
+    },
+    request::{Completion, GotoDefinition, HoverRequest, Rename, Request as _},
+};
+use mindy::{
+    lint::{LintLevel, Linter},
+    parser::{
+        self, LineIndex, SyntaxError,
+        ast::{
+            self, ConditionOp, DrawOp, FetchType, Instruction, LogicOp, MessageType, RadarSort,
+            RadarTarget, TileLayer,
+        },
+    },
+    types::{ContentID, LAccess, ProcessorConfig, content::ContentVersion},
+    vm::{
+        Building, Content, InstructionResult, LObject, LValue, LVar, LogicVM, LogicVMBuilder,
+        VMLoadError, VMLoadResult, buildings::WORLD_PROCESSOR, variables::Constants,
+    },
+};
+use strum::{VariantArray, VariantNames};
+use widestring::U16String;
+
+type Result<T> = std::result::Result<T, Box<dyn Error + Sync + Send>>;
+
+/// Constants that are only defined for a specific processor or world, so they aren't in the global constants.
+const PROCESSOR_CONSTANTS: &[(&str, &str)] = &[
+    ("@this", "The processor executing this code."),
+    (
+        "@thisx",
+        "The x coordinate of the processor executing this code.",
+    ),
+    (
+        "@thisy",
+        "The y coordinate of the processor executing this code.",
+    ),
+    (
+        "@links",
+        "The number of buildings linked to this processor.",
+    ),
+    ("@mapw", "The width of the map, in tiles."),
+    ("@maph", "The height of the map, in tiles."),
+    (
+        "@wait",
+        "Makes `message` wait until the message can be shown, instead of returning a success value.",
+    ),
+];
+
+/// The [`LAccess`] values accepted by the `control` instruction.
+const CONTROL_TYPES: &[LAccess] = &[
+    LAccess::Enabled,
+    LAccess::Shoot,
+    LAccess::Shootp,
+    LAccess::Config,
+    LAccess::Color,
+];
+
+fn main() -> Result<()> {
+    let (connection, io_threads) = Connection::stdio();
+
+    let params = connection.initialize(serde_json::to_value(capabilities())?)?;
+    let params: InitializeParams = serde_json::from_value(params)?;
+
+    let options = params.initialization_options.as_ref();
+
+    let content = options
+        .and_then(|options| options.get("content"))
+        .and_then(|content| content.as_str())
+        .and_then(|content| content.parse().ok())
+        .unwrap_or_default();
+
+    // maps the names of custom instructions registered by the host to their arity
+    let custom_instructions = options
+        .and_then(|options| options.get("customInstructions"))
+        .and_then(|custom| serde_json::from_value(custom.clone()).ok())
+        .unwrap_or_default();
+
+    Server::new(connection, content, custom_instructions).run()?;
+    io_threads.join()?;
+
+    Ok(())
+}
+
+fn capabilities() -> ServerCapabilities {
+    ServerCapabilities {
+        text_document_sync: Some(TextDocumentSyncCapability::Kind(TextDocumentSyncKind::FULL)),
+        hover_provider: Some(HoverProviderCapability::Simple(true)),
+        definition_provider: Some(OneOf::Left(true)),
+        rename_provider: Some(OneOf::Left(true)),
+        completion_provider: Some(CompletionOptions {
+            trigger_characters: Some(vec!["@".to_string()]),
+            ..Default::default()
+        }),
+        ..Default::default()
+    }
+}
+
+struct Server {
+    connection: Connection,
+    documents: HashMap<Uri, String>,
+    content: ContentVersion,
+    globals: Constants,
+    linter: Linter,
+    custom_instructions: HashMap<String, usize>,
+}
+
+impl Server {
+    fn new(
+        connection: Connection,
+        content: ContentVersion,
+        custom_instructions: HashMap<String, usize>,
+    ) -> Self {
+        // we load code into a world processor, so privileged instructions are allowed
+        let mut linter = Linter::for_content(content);
+        linter.privileged = true;
+        linter.custom_instructions.extend(
+            custom_instructions
+                .iter()
+                .map(|(name, &arity)| (name.clone(), arity)),
+        );
+
+        Self {
+            connection,
+            documents: HashMap::new(),
+            content,
+            globals: LVar::create_global_constants_for(content),
+            linter,
+            custom_instructions,
+        }
+    }
+
+    fn run(mut self) -> Result<()> {
+        let receiver = self.connection.receiver.clone();
+        for message in &receiver {
+            match message {
+                Message::Request(request) => {
+                    if self.connection.handle_shutdown(&request)? {
+                        return Ok(());
+                    }
+                    self.handle_request(request)?;
+                }
+                Message::Notification(notification) => self.handle_notification(notification)?,
+                Message::Response(_) => {}
+            }
+        }
+        Ok(())
+    }
+
+    fn handle_request(&self, request: Request) -> Result<()> {
+        let response = match request.method.as_str() {
+            GotoDefinition::METHOD => self.respond::<GotoDefinition>(request, Self::definition),
+            HoverRequest::METHOD => self.respond::<HoverRequest>(request, Self::hover),
+            Completion::METHOD => self.respond::<Completion>(request, Self::completion),
+            Rename::METHOD => self.respond::<Rename>(request, Self::rename),
+            method => Response::new_err(
+                request.id,
+                ErrorCode::MethodNotFound as i32,
+                format!("unsupported request: {method}"),
+            ),
+        };
+        self.connection.sender.send(response.into())?;
+        Ok(())
+    }
+
+    fn respond<R: lsp_types::request::Request>(
+        &self,
+        request: Request,
+        handler: impl FnOnce(&Self, R::Params) -> std::result::Result<R::Result, String>,
+    ) -> Response {
+        let id = request.id.clone();
+        match request.extract(R::METHOD) {
+            Ok((id, params)) => match handler(self, params) {
+                Ok(result) => Response::new_ok(id, result),
+                Err(message) => Response::new_err(id, ErrorCode::InvalidParams as i32, message),
+            },
+            Err(e) => Response::new_err(id, ErrorCode::InvalidParams as i32, e.to_string()),
+        }
+    }
+
+    fn handle_notification(&mut self, notification: Notification) -> Result<()> {
+        match notification.method.as_str() {
+            DidOpenTextDocument::METHOD => {
+                let DidOpenTextDocumentParams { text_document } =
+                    notification.extract(DidOpenTextDocument::METHOD)?;
+                self.documents
+                    .insert(text_document.uri.clone(), text_document.text);
+                self.publish_diagnostics(text_document.uri)
+            }
+            DidChangeTextDocument::METHOD => {
+                let DidChangeTextDocumentParams {
+                    text_document,
+                    content_changes,
+                } = notification.extract(DidChangeTextDocument::METHOD)?;
+                // we only support full document sync, so the last change contains the whole document
+                if let Some(change) = content_changes.into_iter().last() {
+                    self.documents
+                        .insert(text_document.uri.clone(), change.text);
+                }
+                self.publish_diagnostics(text_document.uri)
+            }
+            DidCloseTextDocument::METHOD => {
+                let DidCloseTextDocumentParams { text_document } =
+                    notification.extract(DidCloseTextDocument::METHOD)?;
+                self.documents.remove(&text_document.uri);
+                self.publish_diagnostics(text_document.uri)
+            }
+            _ => Ok(()),
+        }
+    }
+
+    fn publish_diagnostics(&self, uri: Uri) -> Result<()> {
+        let diagnostics = self
+            .documents
+            .get(&uri)
+            .map(|code| self.diagnostics(code))
+            .unwrap_or_default();
+
+        let params = PublishDiagnosticsParams {
+            uri,
+            diagnostics,
+            version: None,
+        };
+        self.connection
+            .sender
+            .send(Notification::new(PublishDiagnostics::METHOD.to_string(), params).into())?;
+        Ok(())
+    }
+
+    fn diagnostics(&self, code: &str) -> Vec<Diagnostic> {
+        let lines = LineIndex::new(code);
+
+        let statements = match parser::parse(code) {
+            Ok(statements) => statements,
+            Err(e) => {
+                return vec![diagnostic(
+                    &lines,
+                    e.span,
+                    DiagnosticSeverity::ERROR,
+                    syntax_error_message(&e),
+                )];
+            }
+        };
+
+        // errors are reported by the VM below, so only keep the warnings to avoid duplicates
+        let mut diagnostics = self
+            .linter
+            .lint(&statements)
+            .into_iter()
+            .filter(|lint| lint.level == LintLevel::Warning)
+            .map(|lint| {
+                diagnostic(
+                    &lines,
+                    lint.span,
+                    DiagnosticSeverity::WARNING,
+                    lint.kind.to_string(),
+                )
+            })
+            .collect::<Vec<_>>();
+
+        if let Err(e) = self.load(code) {
+            let message = match &e {
+                VMLoadError::BadInstruction { error, .. } => error.to_string(),
+                VMLoadError::BadProcessorCode(e) => syntax_error_message(e),
+                e => e.to_string(),
+            };
+            diagnostics.push(diagnostic(
+                &lines,
+                e.span().unwrap_or_default(),
+                DiagnosticSeverity::ERROR,
+                message,
+            ));
+        }
+
+        diagnostics
+    }
+
+    /// Loads the code into a world processor, to find errors that are only detected during late init.
+    fn load(&self, code: &str) -> VMLoadResult<LogicVM> {
+        let mut builder = LogicVMBuilder::new();
+        builder.set_content_version(self.content);
+        // the code is never run, so the handlers don't need to do anything
+        for (name, &arity) in &self.custom_instructions {
+            builder.add_custom_instruction(name, arity, |_, _, _| InstructionResult::Ok);
+        }
+        builder.add_building(Building::from_processor_config(
+            WORLD_PROCESSOR,
+            (0, 0).into(),
+            &ProcessorConfig {
+                code: code.to_string(),
+                links: vec![],
+            },
+            &builder,
+        )?);
+        builder.build()
+    }
+
+    fn definition(
+        &self,
+        params: GotoDefinitionParams,
+    ) -> std::result::Result<Option<GotoDefinitionResponse>, String> {
+        let TextDocumentPositionParams {
+            text_document,
+            position,
+        } = params.text_document_position_params;
+        let Some(code) = self.documents.get(&text_document.uri) else {
+            return Ok(None);
+        };
+
+        let statements = statements(code);
+        Ok(label_at(&statements, position)
+            .and_then(|label| {
+                // if a label is defined more than once, the VM uses the last definition
+                label_ranges(&statements, label).definitions.pop()
+            })
+            .map(|range| {
+                GotoDefinitionResponse::Scalar(Location {
+                    uri: text_document.uri,
+                    range,
+                })
+            }))
+    }
+
+    fn rename(&self, params: RenameParams) -> std::result::Result<Option<WorkspaceEdit>, String> {
+        let RenameParams {
+            text_document_position:
+                TextDocumentPositionParams {
+                    text_document,
+                    position,
+                },
+            new_name,
+            ..
+        } = params;
+        let Some(code) = self.documents.get(&text_document.uri) else {
+            return Ok(None);
+        };
+
+        let statements = statements(code);
+        let Some(label) = label_at(&statements, position) else {
+            return Ok(None);
+        };
+
+        let ranges = label_ranges(&statements, label);
+        if ranges.definitions.is_empty() {
+            return Ok(None);
+        }
+
+        if new_name.is_empty()
+            || new_name.contains(|c: char| c.is_whitespace() || matches!(c, '#' | ';' | '"' | ':'))
+        {
+            return Err(format!("invalid label name: {new_name:?}"));
+        }
+
+        let edits = ranges
+            .definitions
+            .into_iter()
+            .chain(ranges.references)
+            .map(|range| TextEdit::new(range, new_name.clone()))
+            .collect();
+
+        Ok(Some(WorkspaceEdit {
+            changes: Some(HashMap::from([(text_document.uri, edits)])),
+            ..Default::default()
+        }))
+    }
+
+    fn hover(&self, params: HoverParams) -> std::result::Result<Option<Hover>, String> {
+        let TextDocumentPositionParams {
+            text_document,
+            position,
+        } = params.text_document_position_params;
+        let Some(code) = self.documents.get(&text_document.uri) else {
+            return Ok(None);
+        };
+
+        let statements = statements(code);
+        let Some((statement, index)) = token_at(&statements, position) else {
+            return Ok(None);
+        };
+        let token = statement[index];
+
+        let value = if token.text.starts_with('@') {
+            self.describe_constant(token.text)
+        } else if index == 3 && statement[0].text == "lookup" {
+            self.describe_lookup(statement[1].text, token.text)
+        } else {
+            None
+        };
+
+        Ok(value.map(|value| Hover {
+            contents: HoverContents::Markup(MarkupContent {
+                kind: MarkupKind::Markdown,
+                value,
+            }),
+            range: Some(token.range()),
+        }))
+    }
+
+    fn describe_constant(&self, name: &str) -> Option<String> {
+        let description = match self.globals.get(&U16String::from_str(name)) {
+            Some(LVar::Constant(value)) => describe_value(value)?,
+            Some(LVar::Counter) => {
+                "The index of the next instruction to execute. Writing to it jumps to that instruction.".to_string()
+            }
+            Some(LVar::Ipt) => "The number of instructions executed per tick.".to_string(),
+            Some(LVar::Time) => "The time since the world started, in milliseconds.".to_string(),
+            Some(LVar::Tick) => "The number of ticks since the world started.".to_string(),
+            Some(LVar::Second) => "The time since the world started, in seconds.".to_string(),
+            Some(LVar::Minute) => "The time since the world started, in minutes.".to_string(),
+            Some(_) => return None,
+            None => PROCESSOR_CONSTANTS
+                .iter()
+                .find(|(constant, _)| *constant == name)
+                .map(|(_, description)| description.to_string())?,
+        };
+        Some(format!("**{name}**\n\n{description}"))
+    }
+
+    fn describe_lookup(&self, content_type: &str, id: &str) -> Option<String> {
+        let id = id.parse::<f64>().ok().filter(|id| id.fract() == 0.)? as i32;
+        let content = match content_type {
+            "block" => Content::Block(self.content.blocks().from_logic_id(id)?),
+            "item" => Content::Item(self.content.items().from_logic_id(id)?),
+            "liquid" => Content::Liquid(self.content.liquids().from_logic_id(id)?),
+            "unit" => Content::Unit(self.content.units().from_logic_id(id)?),
+            _ => return None,
+        };
+        Some(format!(
+            "**@{}**\n\n{}",
+            content.name().to_string_lossy(),
+            describe_content(content)
+        ))
+    }
+
+    fn completion(
+        &self,
+        params: CompletionParams,
+    ) -> std::result::Result<Option<CompletionResponse>, String> {
+        let TextDocumentPositionParams {
+            text_document,
+            position,
+        } = params.text_document_position;
+        let Some(code) = self.documents.get(&text_document.uri) else {
+            return Ok(None);
+        };
+
+        let line = code.lines().nth(position.line as usize).unwrap_or_default();
+        let mut column = 0;
+        let prefix_len = line
+            .char_indices()
+            .take_while(|(_, c)| {
+                column += c.len_utf16() as u32;
+                column <= position.character
+            })
+            .last()
+            .map_or(0, |(i, c)| i + c.len_utf8());
+        let prefix = &line[..prefix_len];
+
+        let (mut line_statements, comment) = tokenize_line(position.line, prefix);
+        if comment {
+            return Ok(None);
+        }
+
+        let mut statement = line_statements.pop().unwrap_or_default();
+        let partial = match statement.last() {
+            Some(token) if token.end == position.character => statement.pop().unwrap(),
+            _ => Token {
+                text: "",
+                line: position.line,
+                start: position.character,
+                end: position.character,
+            },
+        };
+        if partial.text.starts_with('"') {
+            return Ok(None);
+        }
+
+        let names: Vec<String> = match (statement.first().map(|token| token.text), statement.len())
+        {
+            (None, 0) => Instruction::VARIANTS
+                .iter()
+                .filter(|&&name| name != "unknown")
+                .map(|name| name.to_string())
+                .collect(),
+            (Some("jump"), 1) => {
+                let mut labels = statements(code)
+                    .iter()
+                    .flatten()
+                    .filter_map(|token| token.label())
+                    .map(|label| label.to_string())
+                    .collect::<Vec<_>>();
+                labels.sort();
+                labels.dedup();
+                labels
+            }
+            (Some("jump" | "select"), 2) => to_strings(ConditionOp::VARIANTS),
+            (Some("draw"), 1) => to_strings(DrawOp::VARIANTS),
+            (Some("op"), 1) => to_strings(LogicOp::VARIANTS),
+            (Some("getblock"), 1) => to_strings(TileLayer::VARIANTS),
+            (Some("radar"), 1..=3) => to_strings(RadarTarget::VARIANTS),
+            (Some("radar"), 4) => to_strings(RadarSort::VARIANTS),
+            (Some("message"), 1) => to_strings(MessageType::VARIANTS),
+            (Some("fetch"), 1) => to_strings(FetchType::VARIANTS),
+            (Some("control"), 1) => CONTROL_TYPES.iter().map(|v| v.to_string()).collect(),
+            (Some("sensor"), 3) | (Some("setprop"), 1) => {
+                LAccess::VARIANTS.iter().map(|v| format!("@{v}")).collect()
+            }
+            _ if partial.text.starts_with('@') => self
+                .globals
+                .keys()
+                .map(|name| name.to_string_lossy())
+                .filter(|name| name.starts_with('@'))
+                .chain(PROCESSOR_CONSTANTS.iter().map(|(name, _)| name.to_string()))
+                .collect(),
+            _ => return Ok(None),
+        };
+
+        let kind = match statement.len() {
+            0 => CompletionItemKind::KEYWORD,
+            _ if partial.text.starts_with('@') => CompletionItemKind::CONSTANT,
+            1 if statement[0].text == "jump" => CompletionItemKind::REFERENCE,
+            _ => CompletionItemKind::ENUM_MEMBER,
+        };
+
+        Ok(Some(CompletionResponse::Array(
+            names
+                .into_iter()
+                .map(|name| CompletionItem {
+                    // replace the partial token explicitly, since clients don't consider @ to be part of a word
+                    text_edit: Some(CompletionTextEdit::Edit(TextEdit::new(
+                        partial.range(),
+                        name.clone(),
+                    ))),
+                    label: name,
+                    kind: Some(kind),
+                    ..Default::default()
+                })
+                .collect(),
+        )))
+    }
+}
+
+fn to_strings(names: &[&str]) -> Vec<String> {
+    names.iter().map(|name| name.to_string()).collect()
+}
+
+fn describe_value(value: &LValue) -> Option<String> {
+    match value.obj() {
+        None => Some(format!("Number: `{}`", value.num())),
+        Some(LObject::Null) => Some("`null`".to_string()),
+        Some(LObject::String(string)) => Some(format!("String: `{:?}`", string.to_string_lossy())),
+        Some(LObject::Content(content)) => Some(describe_content(*content)),
+        Some(LObject::Team(team)) => Some(format!("Team `{}`\n\nID: `{}`", team.name(), team.0)),
+        Some(LObject::Sensor(sensor)) => Some(format!("Sensor `{sensor}`")),
+        _ => None,
+    }
+}
+
+fn describe_content(content: Content) -> String {
+    let ContentID { type_, id } = content.into();
+    let type_: &str = type_.into();
+    format!(
+        "Content type: `{type_}`\n\nID: `{id}`\n\nLogic ID: `{}`",
+        content.logic_id()
+    )
+}
+
+fn syntax_error_message(error: &SyntaxError) -> String {
+    // the span is shown by the client, so don't include it in the message
+    let message = error.to_string();
+    message
+        .strip_prefix(&format!("{}: ", error.span))
+        .unwrap_or(&message)
+        .to_string()
+}
+
+fn diagnostic(
+    lines: &LineIndex,
+    span: ast::Span,
+    severity: DiagnosticSeverity,
+    message: String,
+) -> Diagnostic {
+    Diagnostic {
+        range: Range::new(to_position(lines, span.start), to_position(lines, span.end)),
+        severity: Some(severity),
+        source: Some("mlog".to_string()),
+        message,
+        ..Default::default()
+    }
+}
+
+/// Converts a parser location (1-indexed, counted in characters) to an LSP position (0-indexed, counted in UTF-16 code units).
+fn to_position(lines: &LineIndex, location: ast::Location) -> Position {
+    let Some(text) = lines.line(location.line) else {
+        return Position::default();
+    };
+    let character = text
+        .chars()
+        .take(location.column.saturating_sub(1))
+        .map(char::len_utf16)
+        .sum::<usize>();
+    Position::new(location.line as u32 - 1, character as u32)
+}
+
+/// A word in a line of mlog code. Columns are counted in UTF-16 code units, like LSP positions.
+///
+/// Tokenizing the code ourselves instead of using the parser lets us provide completions and navigation for code that doesn't parse yet.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Token<'a> {
+    text: &'a str,
+    line: u32,
+    start: u32,
+    end: u32,
+}
+
+impl<'a> Token<'a> {
+    fn range(&self) -> Range {
+        Range::new(
+            Position::new(self.line, self.start),
+            Position::new(self.line, self.end),
+        )
+    }
+
+    fn contains(&self, position: Position) -> bool {
+        position.line == self.line && (self.start..=self.end).contains(&position.character)
+    }
+
+    /// Returns the name of the label defined by this token, if any.
+    fn label(&self) -> Option<&'a str> {
+        if self.text.starts_with('"') {
+            return None;
+        }
+        self.text.strip_suffix(':').filter(|name| !name.is_empty())
+    }
+}
+
+/// Splits a line of code into statements. The last statement is the one that was in progress at the end of the line, which may be empty.
+///
+/// Also returns whether the line ends with a comment.
+fn tokenize_line(line: u32, text: &str) -> (Vec<Vec<Token<'_>>>, bool) {
+    let mut statements = Vec::new();
+    let mut statement = Vec::new();
+    let mut column = 0;
+
+    let mut chars = text.char_indices().peekable();
+    while let Some((start, c)) = chars.next() {
+        let start_column = column;
+        column += c.len_utf16() as u32;
+        match c {
+            '#' => {
+                statements.push(statement);
+                return (statements, true);
+            }
+            ';' => statements.push(mem::take(&mut statement)),
+            c if c.is_whitespace() => {}
+            _ => {
+                let mut end = start + c.len_utf8();
+                while let Some(&(i, next)) = chars.peek() {
+                    if c != '"' && (next.is_whitespace() || matches!(next, ';' | '#')) {
+                        break;
+                    }
+                    chars.next();
+                    column += next.len_utf16() as u32;
+                    end = i + next.len_utf8();
+                    if c == '"' && next == '"' {
+                        break;
+                    }
+                }
+
+                let token = Token {
+                    text: &text[start..end],
+                    line,
+                    start: start_column,
+                    end: column,
+                };
+                statement.push(token);
+
+                // labels are statements on their own
+                if token.label().is_some() {
+                    statements.push(mem::take(&mut statement));
+                }
+            }
+        }
+    }
+
+    statements.push(statement);
+    (statements, false)
+}
+
+/// Splits code into non-empty statements.
+fn statements(code: &str) -> Vec<Vec<Token<'_>>> {
+    code.lines()
+        .enumerate()
+        .flat_map(|(line, text)| tokenize_line(line as u32, text).0)
+        .filter(|statement| !statement.is_empty())
+        .collect()
+}
+
+/// Returns the statement containing the token at a position, and the index of that token in the statement.
+fn token_at<'a, 'b>(
+    statements: &'b [Vec<Token<'a>>],
+    position: Position,
+) -> Option<(&'b [Token<'a>], usize)> {
+    statements.iter().find_map(|statement| {
+        statement
+            .iter()
+            .position(|token| token.contains(position))
+            .map(|index| (statement.as_slice(), index))
+    })
+}
+
+/// Returns the name of the label defined or referenced at a position, if any.
+fn label_at<'a>(statements: &[Vec<Token<'a>>], position: Position) -> Option<&'a str> {
+    let (statement, index) = token_at(statements, position)?;
+    let token = statement[index];
+    match token.label() {
+        Some(label) => Some(label),
+        None if index == 1 && statement[0].text == "jump" => Some(token.text),
+        None => None,
+    }
+}
+
+#[derive(Debug, Default, PartialEq)]
+struct LabelRanges {
+    /// The ranges of the label's name in its definitions, excluding the colon.
+    definitions: Vec<Range>,
+    /// The ranges of the jump targets referring to the label.
+    references: Vec<Range>,
+}
+
+fn label_ranges(statements: &[Vec<Token<'_>>], label: &str) -> LabelRanges {
+    let mut ranges = LabelRanges::default();
+    for statement in statements {
+        if statement[0].label() == Some(label) {
+            let mut range = statement[0].range();
+            range.end.character -= 1;
+            ranges.definitions.push(range);
+        } else if statement[0].text == "jump"
+            && let Some(target) = statement.get(1)
+            && target.text == label
+        {
+            ranges.references.push(target.range());
+        }
+    }
+    ranges
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    fn range(line: u32, start: u32, end: u32) -> Range {
+        Range::new(Position::new(line, start), Position::new(line, end))
+    }
+
+    #[test]
+    fn test_tokenize_line() {
+        let (statements, comment) = tokenize_line(2, r#"loop: print "a b;c" ; end # comment"#);
+        assert!(comment);
+        assert_eq!(
+            statements
+                .iter()
+                .map(|statement| statement.iter().map(|token| token.text).collect::<Vec<_>>())
+                .collect::<Vec<_>>(),
+            vec![vec!["loop:"], vec!["print", r#""a b;c""#], vec!["end"],]
+        );
+        assert_eq!(statements[1][1].range(), range(2, 12, 19));
+
+        let (statements, comment) = tokenize_line(0, "print π; set x ");
+        assert!(!comment);
+        assert_eq!(statements[0][1].range(), range(0, 6, 7));
+        assert_eq!(statements[1].len(), 2);
+    }
+
+    #[test]
+    fn test_labels() {
+        let code = "start:\njump end always\n  jump start equal x 1\nend: print 1\njump end";
+        let statements = statements(code);
+
+        assert_eq!(label_at(&statements, Position::new(1, 6)), Some("end"));
+        assert_eq!(label_at(&statements, Position::new(0, 2)), Some("start"));
+        assert_eq!(label_at(&statements, Position::new(1, 0)), None);
+        assert_eq!(label_at(&statements, Position::new(3, 8)), None);
+
+        assert_eq!(
+            label_ranges(&statements, "end"),
+            LabelRanges {
+                definitions: vec![range(3, 0, 3)],
+                references: vec![range(1, 5, 8), range(4, 5, 8)],
+            }
+        );
+        assert_eq!(
+            label_ranges(&statements, "start"),
+            LabelRanges {
+                definitions: vec![range(0, 0, 5)],
+                references: vec![range(2, 7, 12)],
+            }
+        );
+    }
+
+    #[test]
+    fn test_custom_instructions() {
+        let code = "double x 2\nprint x\nmemcpy";
+        let (connection, _client) = Connection::memory();
+
+        let server = Server::new(connection, ContentVersion::default(), HashMap::new());
+        assert_eq!(
+            server
+                .diagnostics(code)
+                .into_iter()
+                .map(|diagnostic| diagnostic.range.start.line)
+                .collect::<Vec<_>>(),
+            vec![0]
+        );
+
+        let (connection, _client) = Connection::memory();
+        let server = Server::new(
+            connection,
+            ContentVersion::default(),
+            HashMap::from([("double".to_string(), 2), ("memcpy".to_string(), 0)]),
+        );
+        assert_eq!(server.diagnostics(code), vec![]);
+    }
+
+    #[test]
+    fn test_to_position() {
+        let code = "print \"π\"\nset 𝄞 1";
+        let lines = LineIndex::new(code);
+        assert_eq!(
+            to_position(&lines, ast::Location::new(2, 6)),
+            Position::new(1, 6)
+        );
+        assert_eq!(
+            to_position(&lines, ast::Location::new(1, 10)),
+            Position::new(0, 9)
+        );
+        assert_eq!(
+            to_position(&lines, ast::Location::default()),
+            Position::default()
+        );
+    }
+}
@@ -1,11 +1,22 @@
-use std::{error::Error, time::Instant};
+use std::{
+    error::Error,
+    fs::{self, File},
+    io::{BufWriter, Cursor},
+    path::{Path, PathBuf},
+    time::Instant,
+};
 
-use clap::Parser;
+use binrw::{BinRead, BinWrite};
+use clap::{Args, Parser, Subcommand};
 use clap_stdin::FileOrStdin;
 use mindy::{
-    types::{Object, ProcessorConfig, ProcessorLinkConfig},
+    lint::{LintLevel, Linter},
+    optimize::Optimizer,
+    parser::{self, LineIndex, ast::Statement},
+    render::Layout,
+    types::{Object, ProcessorConfig, ProcessorLinkConfig, Schematic, content::ContentVersion},
     vm::{
-        Building, BuildingData, LogicVMBuilder,
+        Building, BuildingData, LogicVM, LogicVMBuilder, ProcessorLimits, VMLoadResult,
         buildings::{
             HYPER_PROCESSOR, LOGIC_PROCESSOR, MEMORY_BANK, MEMORY_CELL, MESSAGE, MICRO_PROCESSOR,
             WORLD_PROCESSOR,
@@ -16,11 +27,109 @@ use strum::EnumString;
 use widestring::U16String;
 
 #[derive(Parser)]
-#[command(version)]
+#[command(
+    version,
+    args_conflicts_with_subcommands = true,
+    subcommand_negates_reqs = true
+)]
 struct Cli {
-    /// Mlog code to load and run
+    #[command(subcommand)]
+    command: Option<Command>,
+
+    #[command(flatten)]
+    run: RunArgs,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Convert a schematic between the .msch and JSON formats
+    Convert {
+        /// Schematic to read (.msch or .json)
+        input: PathBuf,
+
+        /// Path to write the converted schematic to (.msch or .json)
+        output: PathBuf,
+    },
+    /// Render a schematic to an image showing its blocks and processor links
+    Render {
+        /// Schematic to read (.msch or .json)
+        input: PathBuf,
+
+        /// Path to write the image to (.svg or .png)
+        output: PathBuf,
+
+        /// Size of each tile in pixels, for PNG images
+        #[arg(long, default_value_t = 8)]
+        tile_size: u32,
+    },
+    /// Extract the code of each processor in a schematic to a directory of .mlog files, with a manifest of the other tiles and links
+    Extract {
+        /// Schematic to read (.msch or .json)
+        input: PathBuf,
+
+        /// Directory to write the manifest and code to
+        output: PathBuf,
+    },
+    /// Rebuild a schematic from a directory written by the extract command
+    Rebuild {
+        /// Directory containing the manifest and code
+        input: PathBuf,
+
+        /// Path to write the schematic to (.msch or .json)
+        output: PathBuf,
+    },
+    /// Check mlog code for likely mistakes without running it
+    Lint(LintArgs),
+    /// Optimise mlog code without changing what it does
+    Optimize(OptimizeArgs),
+}
+
+#[derive(Args)]
+struct LintArgs {
+    /// Mlog code to check
     code: FileOrStdin,
 
+    /// Processor type to check for (micro, logic, hyper, world)
+    #[arg(long, short, default_value_t = ProcessorType::World)]
+    processor: ProcessorType,
+
+    /// Name of a building linked to the processor (may be repeated). If not set, link names are not checked
+    #[arg(long = "link", short)]
+    links: Option<Vec<String>>,
+
+    /// Mindustry content version to use (v7, be)
+    #[arg(long, default_value_t = ContentVersion::default())]
+    content: ContentVersion,
+
+    /// Report unknown instructions as warnings instead of errors
+    #[arg(long)]
+    lenient: bool,
+}
+
+#[derive(Args)]
+struct OptimizeArgs {
+    /// Mlog code to optimise
+    code: FileOrStdin,
+
+    /// Path to write the optimised code to. If not set, it is printed to stdout
+    #[arg(long, short)]
+    output: Option<PathBuf>,
+
+    /// Mindustry content version to use (v7, be)
+    #[arg(long, default_value_t = ContentVersion::default())]
+    content: ContentVersion,
+
+    /// Remove instructions even if the code reads or writes @counter
+    #[arg(long)]
+    allow_reindexing: bool,
+}
+
+#[derive(Args)]
+struct RunArgs {
+    /// Mlog code to load and run
+    #[arg(required = true)]
+    code: Option<FileOrStdin>,
+
     /// Processor type to use (micro, logic, hyper, world)
     #[arg(long, short, default_value_t = ProcessorType::World)]
     processor: ProcessorType,
@@ -32,6 +141,18 @@ struct Cli {
     /// Maximum number of ticks to run the simulation for
     #[arg(long)]
     max_ticks: Option<u32>,
+
+    /// Mindustry content version to use (v7, be)
+    #[arg(long, default_value_t = ContentVersion::default())]
+    content: ContentVersion,
+
+    /// Run unknown instructions as no-ops instead of failing to load
+    #[arg(long)]
+    lenient: bool,
+
+    /// Fail to load code that exceeds Mindustry's limits (eg. more than 1000 instructions)
+    #[arg(long)]
+    strict: bool,
 }
 
 fn time_delta_parser(s: &str) -> Result<f64, String> {
@@ -63,33 +184,198 @@ impl ProcessorType {
     }
 }
 
+enum SchematicFormat {
+    Msch,
+    Json,
+}
+
+impl SchematicFormat {
+    fn from_path(path: &Path) -> Result<Self, String> {
+        match path.extension().and_then(|ext| ext.to_str()) {
+            Some("msch") => Ok(Self::Msch),
+            Some("json") => Ok(Self::Json),
+            _ => Err(format!(
+                "unsupported schematic format (expected .msch or .json): {}",
+                path.display()
+            )),
+        }
+    }
+}
+
 fn main() -> Result<(), Box<dyn Error>> {
     let cli = Cli::parse();
 
+    match cli.command {
+        Some(Command::Convert { input, output }) => convert(&input, &output),
+        Some(Command::Render {
+            input,
+            output,
+            tile_size,
+        }) => render(&input, &output, tile_size),
+        Some(Command::Extract { input, output }) => {
+            Ok(read_schematic(&input)?.extract_processors(output)?)
+        }
+        Some(Command::Rebuild { input, output }) => {
+            write_schematic(&Schematic::from_extracted_processors(input)?, &output)
+        }
+        Some(Command::Lint(args)) => lint(args),
+        Some(Command::Optimize(args)) => optimize(args),
+        None => run(cli.run),
+    }
+}
+
+fn read_schematic(path: &Path) -> Result<Schematic, Box<dyn Error>> {
+    Ok(match SchematicFormat::from_path(path)? {
+        SchematicFormat::Msch => Schematic::read(&mut Cursor::new(fs::read(path)?))?,
+        SchematicFormat::Json => Schematic::from_json(&fs::read_to_string(path)?)?,
+    })
+}
+
+fn write_schematic(schem: &Schematic, path: &Path) -> Result<(), Box<dyn Error>> {
+    match SchematicFormat::from_path(path)? {
+        SchematicFormat::Msch => {
+            let mut cur = Cursor::new(Vec::new());
+            schem.write(&mut cur)?;
+            fs::write(path, cur.into_inner())?;
+        }
+        SchematicFormat::Json => fs::write(path, schem.to_json()? + "\n")?,
+    }
+
+    Ok(())
+}
+
+fn convert(input: &Path, output: &Path) -> Result<(), Box<dyn Error>> {
+    write_schematic(&read_schematic(input)?, output)
+}
+
+fn render(input: &Path, output: &Path, tile_size: u32) -> Result<(), Box<dyn Error>> {
+    let layout = Layout::from_schematic(&read_schematic(input)?);
+
+    match output.extension().and_then(|ext| ext.to_str()) {
+        Some("svg") => fs::write(output, layout.to_svg()?)?,
+        Some("png") => layout.write_png(BufWriter::new(File::create(output)?), tile_size)?,
+        _ => {
+            return Err(format!(
+                "unsupported image format (expected .svg or .png): {}",
+                output.display()
+            )
+            .into());
+        }
+    }
+
+    Ok(())
+}
+
+fn lint(args: LintArgs) -> Result<(), Box<dyn Error>> {
+    let code = args.code.contents()?;
+    let statements = parser::parse(&code).inspect_err(|e| eprintln!("{e}\n{}", e.snippet))?;
+
+    let mut linter = Linter::for_content(args.content);
+    linter.privileged = matches!(args.processor, ProcessorType::World);
+    linter.links = args.links;
+    linter.lenient = args.lenient;
+
+    let lints = linter.lint(&statements);
+    let lines = LineIndex::new(&code);
+    for lint in &lints {
+        println!("{lint}\n{}\n", lines.snippet(lint.span));
+    }
+
+    let errors = lints
+        .iter()
+        .filter(|lint| lint.level == LintLevel::Error)
+        .count();
+    println!(
+        "Found {errors} error(s) and {} warning(s).",
+        lints.len() - errors
+    );
+
+    if errors > 0 {
+        Err(format!("found {errors} error(s)").into())
+    } else {
+        Ok(())
+    }
+}
+
+fn optimize(args: OptimizeArgs) -> Result<(), Box<dyn Error>> {
+    let code = args.code.contents()?;
+    let statements = parser::parse(&code).inspect_err(|e| eprintln!("{e}\n{}", e.snippet))?;
+
+    let mut optimizer = Optimizer::for_content(args.content);
+    optimizer.preserve_indices = !args.allow_reindexing;
+
+    let optimized = optimizer.optimize(&statements);
+    let output = parser::print_ast(&optimized);
+    match &args.output {
+        Some(path) => fs::write(path, output)?,
+        None => print!("{output}"),
+    }
+
+    let count = |statements: &[Statement]| {
+        statements
+            .iter()
+            .filter(|statement| matches!(statement, Statement::Instruction(..)))
+            .count()
+    };
+    eprintln!(
+        "Reduced {} instruction(s) to {}.",
+        count(&statements),
+        count(&optimized)
+    );
+
+    Ok(())
+}
+
+fn build_vm(cli: &RunArgs, code: String) -> VMLoadResult<LogicVM> {
     let mut builder = LogicVMBuilder::new();
+    builder.set_content_version(cli.content);
+    builder.set_lenient(cli.lenient);
     builder.add_buildings([
-        Building::from_processor_config(
+        Building::from_processor_config_with_limits(
             cli.processor.name(),
             (0, 0).into(),
             &ProcessorConfig {
-                code: cli.code.contents()?,
+                code,
                 links: vec![
                     ProcessorLinkConfig::unnamed(3, 0),
                     ProcessorLinkConfig::unnamed(4, 0),
                     ProcessorLinkConfig::unnamed(5, 0),
                 ],
             },
+            cli.strict.then(ProcessorLimits::default),
             &builder,
         )?,
         Building::from_config(MESSAGE, (3, 0).into(), &Object::Null, &builder)?,
         Building::from_config(MEMORY_CELL, (4, 0).into(), &Object::Null, &builder)?,
         Building::from_config(MEMORY_BANK, (5, 0).into(), &Object::Null, &builder)?,
     ]);
-    let mut vm = builder.build()?;
+    builder.build()
+}
+
+fn run(mut cli: RunArgs) -> Result<(), Box<dyn Error>> {
+    let code = cli
+        .code
+        .take()
+        .expect("code should be required")
+        .contents()?;
+    let mut vm = build_vm(&cli, code.clone()).inspect_err(|e| {
+        // point at the offending line of code, if possible
+        if let Some(span) = e.span() {
+            eprintln!("{e}\n{}", LineIndex::new(&code).snippet(span));
+        }
+    })?;
 
     let processor = vm.building((0, 0).into()).unwrap().clone();
     assert_eq!(processor.block.name.as_str(), cli.processor.name());
 
+    let lines = LineIndex::new(&code);
+    for warning in processor.data.borrow().unwrap_processor().warnings() {
+        eprintln!("warning: {warning}");
+        if let Some(span) = warning.span() {
+            eprintln!("{}", lines.snippet(span));
+        }
+    }
+
     let message = vm.building((3, 0).into()).unwrap().clone();
     assert_eq!(message.block.name.as_str(), MESSAGE);
 
@@ -0,0 +1,100 @@
+//! Control flow analysis for mlog programs, shared by the [linter](crate::lint) and the [optimiser](crate::optimize).
+
+use alloc::{vec, vec::Vec};
+
+#[allow(unused_imports)]
+use num_traits::float::FloatCore;
+
+use crate::{
+    parser::ast::{Instruction, Value},
+    utils::RapidHashMap,
+    vm::InstructionError,
+};
+
+/// Resolves a jump target to an instruction index, using the same logic as `InstructionBuilder`.
+///
+/// `labels` maps each label to the index of the instruction after it, which is equal to `len` if the label is at the end of the program.
+pub(crate) fn jump_target(
+    target: &Value,
+    labels: &RapidHashMap<&str, usize>,
+    len: usize,
+) -> Result<usize, InstructionError> {
+    match target {
+        Value::Variable(name) => labels
+            .get(name.as_str())
+            .copied()
+            .ok_or_else(|| InstructionError::LabelNotFound(name.clone())),
+        Value::Number(address) => {
+            let counter = *address as usize;
+            if counter < len {
+                Ok(counter)
+            } else {
+                Err(InstructionError::JumpOutOfRange(address.trunc()))
+            }
+        }
+        _ => unreachable!(),
+    }
+}
+
+/// Returns the instructions that may execute after instruction `i` of a program with `len` instructions.
+///
+/// For jumps, `target` is the resolved target (or `None` if it couldn't be resolved), and `taken` is whether the jump is known to always or never be taken.
+pub(crate) fn successors(
+    i: usize,
+    len: usize,
+    instruction: &Instruction,
+    extra: &[Value],
+    target: Option<usize>,
+    taken: Option<bool>,
+) -> Vec<usize> {
+    // the arguments of unknown instructions are in `extra`, and we don't know which are written
+    let writes_counter = match instruction {
+        Instruction::Unknown(_) => extra.iter().collect(),
+        instruction => instruction.outputs(),
+    }
+    .into_iter()
+    .any(|value| matches!(value, Value::Variable(name) if name == "@counter"));
+
+    if writes_counter {
+        // writing to @counter could jump to any instruction
+        return (0..len).collect();
+    }
+
+    let wrap = |target| wrap(target, len);
+    match (instruction, target) {
+        (Instruction::Stop, _) => vec![],
+        (Instruction::End, _) => vec![0],
+        (Instruction::Jump { .. }, Some(target)) => match taken {
+            Some(true) => vec![wrap(target)],
+            Some(false) => vec![wrap(i + 1)],
+            None => vec![wrap(i + 1), wrap(target)],
+        },
+        _ => vec![wrap(i + 1)],
+    }
+}
+
+/// Returns the instruction that executes when the counter is set to `target`.
+///
+/// If execution runs off the end (or reaches a label at the end), it wraps back to the start.
+pub(crate) fn wrap(target: usize, len: usize) -> usize {
+    if target < len { target } else { 0 }
+}
+
+/// Returns which instructions can be executed, starting from the first instruction.
+pub(crate) fn reachable(successors: &[Vec<usize>]) -> Vec<bool> {
+    let mut reachable = vec![false; successors.len()];
+    let mut stack = Vec::new();
+    if !successors.is_empty() {
+        reachable[0] = true;
+        stack.push(0);
+    }
+    while let Some(i) = stack.pop() {
+        for &next in &successors[i] {
+            if !reachable[next] {
+                reachable[next] = true;
+                stack.push(next);
+            }
+        }
+    }
+    reachable
+}
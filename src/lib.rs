@@ -4,7 +4,12 @@ extern crate alloc;
 #[cfg(feature = "std")]
 extern crate std;
 
+mod cfg;
+pub mod lint;
+pub mod optimize;
 pub mod parser;
+#[cfg(feature = "render")]
+pub mod render;
 pub mod types;
 mod utils;
 pub mod vm;
@@ -0,0 +1,757 @@
+//! Static analysis for mlog programs.
+
+use alloc::{borrow::ToOwned, collections::BTreeMap, format, string::String, vec, vec::Vec};
+use core::fmt::Display;
+
+use thiserror::Error;
+
+use crate::{
+    cfg,
+    parser::ast::{ConditionOp, Instruction, Span, Statement, Value},
+    types::content::ContentVersion,
+    utils::{RapidHashMap, RapidHashSet},
+    vm::{InstructionError, LVar, link_name_prefix},
+};
+
+/// Constants that are added per processor or per world, so they aren't in [`LVar::create_global_constants`].
+const EXTRA_CONSTANTS: &[&str] = &[
+    "@this", "@thisx", "@thisy", "@links", "@mapw", "@maph", "@wait",
+];
+
+/// Checks mlog programs for likely mistakes.
+#[derive(Debug, Clone)]
+pub struct Linter {
+    /// If false, privileged instructions are reported, since unprivileged processors ignore them.
+    pub privileged: bool,
+    /// The names of the processor's links. If `None`, link names are not checked.
+    pub links: Option<Vec<String>>,
+    /// If true, unknown instructions are reported as warnings instead of errors, since [lenient](crate::vm::LogicVMBuilder::set_lenient) VMs load them as no-ops.
+    pub lenient: bool,
+    /// The names and arities of custom instructions registered with [`LogicVMBuilder::add_custom_instruction`](crate::vm::LogicVMBuilder::add_custom_instruction), which aren't reported as unknown. See [`LogicVMBuilder::custom_instructions`](crate::vm::LogicVMBuilder::custom_instructions).
+    pub custom_instructions: RapidHashMap<String, usize>,
+    names: Names,
+}
+
+impl Linter {
+    pub fn new() -> Self {
+        Self::for_content(ContentVersion::default())
+    }
+
+    /// Creates a linter that checks constants and link names against a specific version of Mindustry's content.
+    pub fn for_content(content: ContentVersion) -> Self {
+        Self {
+            privileged: false,
+            links: None,
+            lenient: false,
+            custom_instructions: RapidHashMap::default(),
+            names: Names::for_content(content),
+        }
+    }
+
+    /// Returns all problems found in a program, in source order.
+    pub fn lint(&self, code: &[Statement]) -> Vec<Lint> {
+        let mut labels = RapidHashMap::default();
+        let mut instructions = Vec::new();
+        for statement in code {
+            match statement {
+                Statement::Label(label, _) => {
+                    labels.insert(label.as_str(), instructions.len());
+                }
+                Statement::Instruction(instruction, extra, span) => {
+                    instructions.push((instruction, extra, *span));
+                }
+            }
+        }
+        let len = instructions.len();
+
+        // lints for each instruction, in the order they were found
+        let mut lints = vec![Vec::new(); len];
+
+        // variables are referred to by their index in `variables`
+        let mut variables = Vec::new();
+        let mut variable_indices = RapidHashMap::default();
+        let mut reads = vec![Vec::new(); len];
+        let mut writes = vec![Vec::new(); len];
+        let mut used = RapidHashSet::default();
+
+        let mut successors = Vec::with_capacity(len);
+
+        for (i, &(instruction, extra, _)) in instructions.iter().enumerate() {
+            let lints = &mut lints[i];
+
+            // the arguments of unknown instructions are in `extra`, but we don't know which are read or written
+            // so assume they're written, and count them as used so they don't get reported as unused
+            let (inputs, outputs, opaque) = match instruction {
+                Instruction::Unknown(name) => {
+                    // same checks as InstructionBuilder
+                    match self.custom_instructions.get(name) {
+                        Some(&arity) if extra.len() >= arity => {}
+                        Some(&arity) => lints.push(
+                            InstructionError::NotEnoughArguments {
+                                name: name.clone(),
+                                want: arity,
+                                got: extra.len(),
+                            }
+                            .into(),
+                        ),
+                        None => {
+                            lints.push(InstructionError::UnknownInstruction(name.clone()).into())
+                        }
+                    }
+                    (vec![], vec![], extra.iter().collect())
+                }
+                _ => (instruction.inputs(), instruction.outputs(), vec![]),
+            };
+
+            if instruction.is_privileged() && !self.privileged {
+                lints.push(LintKind::PrivilegedInstruction(instruction.into()));
+            }
+
+            let mut variable = |name: &str| match self.check_name(name) {
+                Ok(true) => Some(*variable_indices.entry(name.to_owned()).or_insert_with(|| {
+                    variables.push(name.to_owned());
+                    variables.len() - 1
+                })),
+                Ok(false) => None,
+                Err(lint) => {
+                    lints.push(lint);
+                    None
+                }
+            };
+
+            for value in inputs {
+                if let Value::Variable(name) = value
+                    && let Some(index) = variable(name)
+                {
+                    reads[i].push(index);
+                    used.insert(index);
+                }
+            }
+            for value in outputs.into_iter().chain(opaque.iter().copied()) {
+                if let Value::Variable(name) = value
+                    && let Some(index) = variable(name)
+                {
+                    writes[i].push(index);
+                }
+            }
+            for value in opaque {
+                if let Value::Variable(name) = value
+                    && let Some(&index) = variable_indices.get(name.as_str())
+                {
+                    used.insert(index);
+                }
+            }
+
+            let (target, taken) = match instruction {
+                Instruction::Jump { target, op, .. } => (
+                    cfg::jump_target(target, &labels, len)
+                        .map_err(|error| lints.push(error.into()))
+                        .ok(),
+                    (*op == ConditionOp::Always).then_some(true),
+                ),
+                _ => (None, None),
+            };
+            successors.push(cfg::successors(i, len, instruction, extra, target, taken));
+        }
+
+        // find the instructions that can actually be executed
+
+        let reachable = cfg::reachable(&successors);
+
+        // only report the first instruction of each unreachable block
+        for i in 0..len {
+            if !reachable[i] && (i == 0 || reachable[i - 1]) {
+                lints[i].push(LintKind::UnreachableInstruction);
+            }
+        }
+
+        // find the variables that are definitely written before each instruction executes
+        // this is a standard forward dataflow analysis over the reachable instructions
+
+        let words = variables.len().div_ceil(64);
+        let mut predecessors = vec![Vec::new(); len];
+        for (i, next) in successors.iter().enumerate() {
+            if reachable[i] {
+                for &next in next {
+                    predecessors[next].push(i);
+                }
+            }
+        }
+
+        // start with every variable written, except at the entry point
+        let mut written = vec![vec![u64::MAX; words]; len];
+        if len > 0 {
+            written[0].fill(0);
+        }
+
+        let mut changed = true;
+        while changed {
+            changed = false;
+            for i in 1..len {
+                if !reachable[i] {
+                    continue;
+                }
+                let mut new = vec![u64::MAX; words];
+                for &prev in &predecessors[i] {
+                    let mut out = written[prev].clone();
+                    for &index in &writes[prev] {
+                        out[index / 64] |= 1 << (index % 64);
+                    }
+                    for (word, out) in new.iter_mut().zip(out) {
+                        *word &= out;
+                    }
+                }
+                if new != written[i] {
+                    written[i] = new;
+                    changed = true;
+                }
+            }
+        }
+
+        let mut reported = RapidHashSet::default();
+        for i in (0..len).filter(|&i| reachable[i]) {
+            for &index in &reads[i] {
+                if written[i][index / 64] & (1 << (index % 64)) == 0 && reported.insert(index) {
+                    lints[i].push(LintKind::ReadBeforeWrite(variables[index].clone()));
+                }
+            }
+        }
+
+        // report unused variables where they're first written
+        let mut first_writes = BTreeMap::new();
+        for (i, writes) in writes.iter().enumerate() {
+            for &index in writes {
+                first_writes.entry(index).or_insert(i);
+            }
+        }
+        for (index, i) in first_writes {
+            if !used.contains(&index) {
+                lints[i].push(LintKind::UnusedVariable(variables[index].clone()));
+            }
+        }
+
+        lints
+            .into_iter()
+            .zip(instructions)
+            .flat_map(|(lints, (_, _, span))| {
+                lints.into_iter().map(move |kind| Lint {
+                    level: self.level(&kind),
+                    kind,
+                    span,
+                })
+            })
+            .collect()
+    }
+
+    fn level(&self, kind: &LintKind) -> LintLevel {
+        match kind {
+            LintKind::BadInstruction(InstructionError::UnknownInstruction(_)) if self.lenient => {
+                LintLevel::Warning
+            }
+            LintKind::BadInstruction(_) => LintLevel::Error,
+            _ => LintLevel::Warning,
+        }
+    }
+
+    /// Returns true if `name` refers to a variable, false if it refers to a constant or link, or an error if it looks like a typo.
+    fn check_name(&self, name: &str) -> Result<bool, LintKind> {
+        if self.names.constants.contains(name) {
+            return Ok(false);
+        }
+
+        if name.starts_with('@') {
+            return Err(LintKind::UnknownConstant {
+                name: name.to_owned(),
+                suggestion: suggest(name, self.names.constants.iter().map(String::as_str)),
+            });
+        }
+
+        let prefix = name.trim_end_matches(|c: char| c.is_ascii_digit());
+        let link_like = self.names.is_link_like(name);
+
+        match &self.links {
+            Some(links) if links.iter().any(|link| link == name) => Ok(false),
+            // also catch typos in the prefix, eg. `cel1`
+            Some(links) if prefix.len() < name.len() => {
+                match suggest(name, links.iter().map(String::as_str)) {
+                    suggestion if link_like || suggestion.is_some() => Err(LintKind::UnknownLink {
+                        name: name.to_owned(),
+                        suggestion,
+                    }),
+                    _ => Ok(true),
+                }
+            }
+            _ => Ok(!link_like),
+        }
+    }
+}
+
+impl Default for Linter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Classifies the names used in mlog programs.
+#[derive(Debug, Clone)]
+pub(crate) struct Names {
+    constants: RapidHashSet<String>,
+    link_prefixes: RapidHashSet<&'static str>,
+}
+
+impl Names {
+    pub(crate) fn for_content(content: ContentVersion) -> Self {
+        Self {
+            constants: LVar::create_global_constants_for(content)
+                .keys()
+                .map(|name| name.to_string_lossy())
+                .chain(EXTRA_CONSTANTS.iter().map(|&name| name.to_owned()))
+                .collect(),
+            link_prefixes: content
+                .blocks()
+                .values()
+                .iter()
+                .map(|block| link_name_prefix(&block.name))
+                .collect(),
+        }
+    }
+
+    /// Returns true if `name` looks like the default name of a link, eg. `cell1`.
+    pub(crate) fn is_link_like(&self, name: &str) -> bool {
+        let prefix = name.trim_end_matches(|c: char| c.is_ascii_digit());
+        prefix.len() < name.len() && self.link_prefixes.contains(prefix)
+    }
+
+    /// Returns true if `name` can only refer to a variable, and not to a constant or a link.
+    pub(crate) fn is_variable(&self, name: &str) -> bool {
+        !name.starts_with('@') && !self.constants.contains(name) && !self.is_link_like(name)
+    }
+}
+
+/// A problem found by [`Linter`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct Lint {
+    pub level: LintLevel,
+    pub kind: LintKind,
+    /// The location of the instruction that caused this lint.
+    pub span: Span,
+}
+
+impl Display for Lint {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(f, "{} at {}: {}", self.level, self.span, self.kind)
+    }
+}
+
+#[derive(Error, Debug, Clone, PartialEq)]
+pub enum LintKind {
+    #[error("variable `{0}` may be read before it's written")]
+    ReadBeforeWrite(String),
+
+    #[error("variable `{0}` is written but never read")]
+    UnusedVariable(String),
+
+    #[error("unreachable instruction")]
+    UnreachableInstruction,
+
+    #[error("privileged instruction `{0}` is ignored by unprivileged processors")]
+    PrivilegedInstruction(&'static str),
+
+    #[error("unknown constant `{name}`{}", did_you_mean(.suggestion))]
+    UnknownConstant {
+        name: String,
+        suggestion: Option<String>,
+    },
+
+    #[error("unknown link `{name}`{}", did_you_mean(.suggestion))]
+    UnknownLink {
+        name: String,
+        suggestion: Option<String>,
+    },
+
+    /// A problem that would prevent the program from being loaded by the VM. These are errors, except for unknown instructions in [lenient](Linter::lenient) mode.
+    #[error(transparent)]
+    BadInstruction(#[from] InstructionError),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, strum::Display)]
+#[strum(serialize_all = "lowercase")]
+pub enum LintLevel {
+    Warning,
+    Error,
+}
+
+fn did_you_mean(suggestion: &Option<String>) -> String {
+    match suggestion {
+        Some(suggestion) => format!(" (did you mean `{suggestion}`?)"),
+        None => String::new(),
+    }
+}
+
+/// Returns the candidate most similar to `name`, if any are close enough to be a likely typo.
+fn suggest<'a>(name: &str, candidates: impl IntoIterator<Item = &'a str>) -> Option<String> {
+    let max_distance = (name.chars().count() / 3).max(1);
+    candidates
+        .into_iter()
+        .map(|candidate| (edit_distance(name, candidate), candidate))
+        .filter(|&(distance, _)| distance <= max_distance)
+        .min()
+        .map(|(_, candidate)| candidate.to_owned())
+}
+
+/// Returns the number of insertions, deletions, substitutions, and adjacent transpositions needed to turn `a` into `b`.
+fn edit_distance(a: &str, b: &str) -> usize {
+    let a: Vec<_> = a.chars().collect();
+    let b: Vec<_> = b.chars().collect();
+
+    // rows i-2, i-1, and i of the distance matrix
+    let mut prev2 = vec![0; b.len() + 1];
+    let mut prev: Vec<_> = (0..=b.len()).collect();
+    let mut row = vec![0; b.len() + 1];
+
+    for i in 1..=a.len() {
+        row[0] = i;
+        for j in 1..=b.len() {
+            let cost = usize::from(a[i - 1] != b[j - 1]);
+            row[j] = (prev[j] + 1).min(row[j - 1] + 1).min(prev[j - 1] + cost);
+            if i > 1 && j > 1 && a[i - 1] == b[j - 2] && a[i - 2] == b[j - 1] {
+                row[j] = row[j].min(prev2[j - 2] + 1);
+            }
+        }
+        (prev2, prev, row) = (prev, row, prev2);
+    }
+
+    prev[b.len()]
+}
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use alloc::string::ToString;
+
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+    use crate::parser::parse;
+
+    fn lint_with(linter: &Linter, code: &str) -> Vec<(usize, LintKind)> {
+        linter
+            .lint(&parse(code).unwrap())
+            .into_iter()
+            .map(|lint| (lint.span.start.line, lint.kind))
+            .collect()
+    }
+
+    fn lint(code: &str) -> Vec<(usize, LintKind)> {
+        lint_with(&Linter::new(), code)
+    }
+
+    #[test]
+    fn test_clean() {
+        let mut linter = Linter::new();
+        linter.links = Some(vec!["cell1".into(), "message1".into()]);
+        assert_eq!(
+            lint_with(
+                &linter,
+                r#"
+                set i 0
+                loop:
+                    read value cell1 i
+                    op add i i 1
+                    print value
+                    jump loop lessThan i @links
+                printflush message1
+                sensor x @this @x
+                print x
+                "#,
+            ),
+            vec![]
+        );
+    }
+
+    #[test]
+    fn test_read_before_write() {
+        assert_eq!(
+            lint(
+                "
+                print x
+                set x 1
+                jump skip lessThan @time 1000
+                set y 1
+                skip:
+                print y
+                print y
+                "
+            ),
+            vec![
+                (2, LintKind::ReadBeforeWrite("x".into())),
+                (7, LintKind::ReadBeforeWrite("y".into())),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_unused_variable() {
+        assert_eq!(
+            lint(
+                "
+                set x 1
+                set y 2
+                op add y y 1
+                print y
+                getlink z 0
+                "
+            ),
+            vec![
+                (2, LintKind::UnusedVariable("x".into())),
+                (6, LintKind::UnusedVariable("z".into())),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_unreachable() {
+        assert_eq!(
+            lint(
+                "
+                print 1
+                jump skip always
+                print 2
+                skip:
+                print 3
+                jump halt lessThan @time 1000
+                end
+                print 4
+                halt:
+                stop
+                print 5
+                "
+            ),
+            vec![
+                (4, LintKind::UnreachableInstruction),
+                (9, LintKind::UnreachableInstruction),
+                (12, LintKind::UnreachableInstruction),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_counter_jumps_anywhere() {
+        assert_eq!(
+            lint(
+                "
+                op add @counter @counter 1
+                end
+                print 1
+                "
+            ),
+            vec![]
+        );
+    }
+
+    #[test]
+    fn test_bad_jumps() {
+        let lints = Linter::new().lint(
+            &parse(
+                "
+                jump missing equal @time 0
+                jump 3 always
+                ",
+            )
+            .unwrap(),
+        );
+        assert_eq!(
+            lints
+                .iter()
+                .map(|lint| lint.kind.clone())
+                .collect::<Vec<_>>(),
+            vec![
+                InstructionError::LabelNotFound("missing".into()).into(),
+                InstructionError::JumpOutOfRange(3.).into(),
+            ]
+        );
+        assert!(lints.iter().all(|lint| lint.level == LintLevel::Error));
+    }
+
+    #[test]
+    fn test_privileged() {
+        let code = "
+            setrate 10
+            getblock building result 1 2
+            print result
+            ";
+
+        assert_eq!(
+            lint(code),
+            vec![
+                (2, LintKind::PrivilegedInstruction("setrate")),
+                (3, LintKind::PrivilegedInstruction("getblock")),
+            ]
+        );
+
+        let mut linter = Linter::new();
+        linter.privileged = true;
+        assert_eq!(lint_with(&linter, code), vec![]);
+    }
+
+    #[test]
+    fn test_unknown_constants() {
+        assert_eq!(
+            lint(
+                "
+                print @coper
+                print @thsi
+                print @somethingElse
+                print @copper
+                print @this
+                set @counter 0
+                "
+            ),
+            vec![
+                (
+                    2,
+                    LintKind::UnknownConstant {
+                        name: "@coper".into(),
+                        suggestion: Some("@copper".into()),
+                    }
+                ),
+                (
+                    3,
+                    LintKind::UnknownConstant {
+                        name: "@thsi".into(),
+                        suggestion: Some("@this".into()),
+                    }
+                ),
+                (
+                    4,
+                    LintKind::UnknownConstant {
+                        name: "@somethingElse".into(),
+                        suggestion: None,
+                    }
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_unknown_links() {
+        let code = "
+            printflush message1
+            printflush message2
+            printflush cel1
+            printflush switch1
+            set x1 0
+            print x1
+            ";
+
+        let mut linter = Linter::new();
+        linter.links = Some(vec!["message1".into(), "cell1".into()]);
+        assert_eq!(
+            lint_with(&linter, code),
+            vec![
+                (
+                    3,
+                    LintKind::UnknownLink {
+                        name: "message2".into(),
+                        suggestion: Some("message1".into()),
+                    }
+                ),
+                (
+                    4,
+                    LintKind::UnknownLink {
+                        name: "cel1".into(),
+                        suggestion: Some("cell1".into()),
+                    }
+                ),
+                (
+                    5,
+                    LintKind::UnknownLink {
+                        name: "switch1".into(),
+                        suggestion: None,
+                    }
+                ),
+            ]
+        );
+
+        // if the links aren't known, anything that looks like a link is assumed to be one
+        assert_eq!(
+            lint(code),
+            vec![(4, LintKind::ReadBeforeWrite("cel1".into()))]
+        );
+    }
+
+    #[test]
+    fn test_unknown_instruction() {
+        assert_eq!(
+            lint(
+                "
+                ubind @flare
+                ucontrol within 10 20 5 result
+                print result
+                "
+            ),
+            vec![
+                (
+                    2,
+                    InstructionError::UnknownInstruction("ubind".into()).into()
+                ),
+                (
+                    3,
+                    InstructionError::UnknownInstruction("ucontrol".into()).into()
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_custom_instructions() {
+        let code = "
+            double x 2
+            double
+            print x
+            ";
+
+        let mut linter = Linter::new();
+        linter.custom_instructions.insert("double".into(), 2);
+        assert_eq!(
+            lint_with(&linter, code),
+            vec![(
+                3,
+                InstructionError::NotEnoughArguments {
+                    name: "double".into(),
+                    want: 2,
+                    got: 0,
+                }
+                .into()
+            )]
+        );
+    }
+
+    #[test]
+    fn test_lenient() {
+        let code = parse("status false wet @unit 10").unwrap();
+
+        let mut linter = Linter::new();
+        assert_eq!(linter.lint(&code)[0].level, LintLevel::Error);
+
+        linter.lenient = true;
+        assert_eq!(linter.lint(&code)[0].level, LintLevel::Warning);
+    }
+
+    #[test]
+    fn test_display() {
+        let lints = Linter::new().lint(&parse("print @coper").unwrap());
+        assert_eq!(
+            lints[0].to_string(),
+            "warning at line 1, column 1: unknown constant `@coper` (did you mean `@copper`?)"
+        );
+    }
+
+    #[test]
+    fn test_edit_distance() {
+        assert_eq!(edit_distance("", ""), 0);
+        assert_eq!(edit_distance("abc", ""), 3);
+        assert_eq!(edit_distance("copper", "coper"), 1);
+        assert_eq!(edit_distance("this", "thsi"), 1);
+        assert_eq!(edit_distance("kitten", "sitting"), 3);
+    }
+}
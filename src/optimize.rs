@@ -0,0 +1,874 @@
+//! Behaviour-preserving optimisations for mlog programs.
+
+use alloc::{format, string::String, vec, vec::Vec};
+
+#[allow(unused_imports)]
+use num_traits::float::FloatCore;
+
+use crate::{
+    cfg,
+    lint::Names,
+    parser::ast::{ConditionOp, Instruction, Span, Statement, Value},
+    types::content::ContentVersion,
+    utils::{RapidHashMap, RapidHashSet},
+    vm::instructions::EQUALITY_EPSILON,
+};
+
+/// Makes mlog programs smaller and faster without changing what they do.
+///
+/// The optimiser runs these passes until none of them make any more changes:
+/// - Jump threading: jumps to an unconditional jump are redirected to that jump's target.
+/// - Constant propagation: variables that always hold the same literal value are replaced by that value, and jumps with constant conditions are made unconditional or removed.
+/// - Dead code elimination: unreachable instructions, `noop`, `set x x`, jumps to the next instruction, writes to variables that are never read, and a trailing `end` are removed.
+///
+/// Variables are assumed to be private to the program, ie. other processors don't read or write them by name. If the program might read or write its own variables by name (eg. `read`/`write` with a string address on something other than a memory cell or bank), constant propagation and dead store removal are skipped.
+#[derive(Debug, Clone)]
+pub struct Optimizer {
+    /// If true, instructions are never removed from programs that read or write `@counter`, since that could change the instruction indices that the program depends on, eg. in jump tables. Defaults to true.
+    ///
+    /// Instructions are still rewritten in place, so the number of instructions stays the same.
+    pub preserve_indices: bool,
+    names: Names,
+}
+
+impl Optimizer {
+    pub fn new() -> Self {
+        Self::for_content(ContentVersion::default())
+    }
+
+    /// Creates an optimiser that recognizes constants and link names from a specific version of Mindustry's content.
+    pub fn for_content(content: ContentVersion) -> Self {
+        Self {
+            preserve_indices: true,
+            names: Names::for_content(content),
+        }
+    }
+
+    /// Returns an optimised copy of a program.
+    ///
+    /// Programs with jumps to missing labels or out-of-range instructions are returned unchanged.
+    pub fn optimize(&self, code: &[Statement]) -> Vec<Statement> {
+        let Some(mut program) = Program::new(code) else {
+            return code.to_vec();
+        };
+
+        let can_remove = !self.preserve_indices || !program.uses_counter();
+        loop {
+            let mut changed = program.thread_jumps();
+            changed |= self.propagate_constants(&mut program);
+            if can_remove {
+                changed |= self.remove_dead_code(&mut program);
+            }
+            if !changed {
+                break;
+            }
+        }
+
+        program.into_statements()
+    }
+
+    /// Returns true if the program might read or write its own variables by name.
+    fn has_dynamic_access(&self, program: &Program) -> bool {
+        program.items.iter().any(|item| match &item.instruction {
+            Instruction::Read {
+                target, address, ..
+            }
+            | Instruction::Write {
+                target, address, ..
+            } => {
+                let is_memory = match target {
+                    Value::Variable(name) => {
+                        self.names.is_link_like(name)
+                            && matches!(
+                                name.trim_end_matches(|c: char| c.is_ascii_digit()),
+                                "cell" | "bank"
+                            )
+                    }
+                    _ => false,
+                };
+                !is_memory && !matches!(address, Value::Number(_))
+            }
+            _ => false,
+        })
+    }
+
+    fn propagate_constants(&self, program: &mut Program) -> bool {
+        if self.has_dynamic_access(program) {
+            return false;
+        }
+
+        let len = program.items.len();
+
+        let mut variable_indices = RapidHashMap::default();
+        for item in &program.items {
+            for value in item.values() {
+                if let Value::Variable(name) = value
+                    && self.names.is_variable(name)
+                {
+                    let next = variable_indices.len();
+                    variable_indices.entry(name.clone()).or_insert(next);
+                }
+            }
+        }
+        let variable = |value: &Value| match value {
+            Value::Variable(name) => variable_indices.get(name.as_str()).copied(),
+            _ => None,
+        };
+
+        // forward dataflow analysis of the value of each variable before each instruction executes
+        // variables have unknown values when the program starts, since they may have been restored from a save
+
+        let successors = program.successors();
+        let mut states: Vec<Option<Vec<Known>>> = vec![None; len];
+        let mut stack = Vec::new();
+        if len > 0 {
+            states[0] = Some(vec![Known::Varying; variable_indices.len()]);
+            stack.push(0);
+        }
+
+        while let Some(i) = stack.pop() {
+            let Some(state) = &states[i] else {
+                continue;
+            };
+            let mut state = state.clone();
+
+            let item = &program.items[i];
+            match &item.instruction {
+                Instruction::Set { to, from } => {
+                    if let Some(to) = variable(to) {
+                        state[to] = match variable(from) {
+                            Some(from) => state[from].clone(),
+                            None if is_literal(from) => Known::Const(from.clone()),
+                            None => Known::Varying,
+                        };
+                    }
+                }
+                Instruction::Unknown(_) => {
+                    // assume all of the arguments are written
+                    for value in &item.extra {
+                        if let Some(index) = variable(value) {
+                            state[index] = Known::Varying;
+                        }
+                    }
+                }
+                instruction => {
+                    for value in instruction.outputs() {
+                        if let Some(index) = variable(value) {
+                            state[index] = Known::Varying;
+                        }
+                    }
+                }
+            }
+
+            for &next in &successors[i] {
+                let changed = match &mut states[next] {
+                    Some(next_state) => {
+                        let mut changed = false;
+                        for (known, new) in next_state.iter_mut().zip(&state) {
+                            if *known != Known::Varying && !known.same_as(new) {
+                                *known = Known::Varying;
+                                changed = true;
+                            }
+                        }
+                        changed
+                    }
+                    next_state @ None => {
+                        *next_state = Some(state.clone());
+                        true
+                    }
+                };
+                if changed {
+                    stack.push(next);
+                }
+            }
+        }
+
+        // replace reads of constant variables with their values
+
+        let mut changed = false;
+        for (item, state) in program.items.iter_mut().zip(states) {
+            let Some(state) = state else {
+                continue;
+            };
+
+            for value in item.instruction.inputs_mut() {
+                if let Some(index) = variable(value)
+                    && let Known::Const(constant) = &state[index]
+                {
+                    *value = constant.clone();
+                    changed = true;
+                }
+            }
+
+            if let Instruction::Jump { op, x, y, .. } = &mut item.instruction
+                && *op != ConditionOp::Always
+                && evaluate(*op, x, y) == Some(true)
+            {
+                *op = ConditionOp::Always;
+                *x = Value::None;
+                *y = Value::None;
+                changed = true;
+            }
+        }
+        changed
+    }
+
+    fn remove_dead_code(&self, program: &mut Program) -> bool {
+        let len = program.items.len();
+        if len == 0 {
+            return false;
+        }
+
+        let reachable = cfg::reachable(&program.successors());
+
+        // variables that might be read by any instruction, including unreachable ones
+        let read = if self.has_dynamic_access(program) {
+            None
+        } else {
+            let mut read = RapidHashSet::default();
+            for item in &program.items {
+                let inputs = match &item.instruction {
+                    Instruction::Unknown(_) => item.extra.iter().collect(),
+                    instruction => instruction.inputs(),
+                };
+                for value in inputs {
+                    if let Value::Variable(name) = value {
+                        read.insert(name.clone());
+                    }
+                }
+            }
+            Some(read)
+        };
+
+        let wrap = |target| cfg::wrap(target, len);
+        let mut removed: Vec<_> = (0..len)
+            .map(|i| {
+                let item = &program.items[i];
+                !reachable[i]
+                    || match &item.instruction {
+                        Instruction::Noop => true,
+                        Instruction::Set {
+                            to: Value::Variable(to),
+                            from: Value::Variable(from),
+                        } if to == from => true,
+                        Instruction::End => i == len - 1,
+                        Instruction::Jump { op, x, y, .. } => {
+                            wrap(item.target) == wrap(i + 1) || evaluate(*op, x, y) == Some(false)
+                        }
+                        instruction @ (Instruction::Read { .. }
+                        | Instruction::GetLink { .. }
+                        | Instruction::Sensor { .. }
+                        | Instruction::Set { .. }
+                        | Instruction::Op { .. }
+                        | Instruction::Select { .. }
+                        | Instruction::Lookup { .. }
+                        | Instruction::PackColor { .. }
+                        | Instruction::UnpackColor { .. }) => read.as_ref().is_some_and(|read| {
+                            instruction.outputs().into_iter().all(|value| match value {
+                                Value::Variable(name) => {
+                                    self.names.is_variable(name) && !read.contains(name.as_str())
+                                }
+                                _ => false,
+                            })
+                        }),
+                        _ => false,
+                    }
+            })
+            .collect();
+
+        // keep one instruction, since processors without any code don't run at all
+        if removed.iter().all(|&removed| removed) {
+            removed[len - 1] = false;
+            let item = &mut program.items[len - 1];
+            if item.instruction != Instruction::Noop || !item.extra.is_empty() {
+                item.instruction = Instruction::Noop;
+                item.extra.clear();
+                program.remove(&removed);
+                return true;
+            }
+        }
+
+        if !removed.contains(&true) {
+            return false;
+        }
+        program.remove(&removed);
+        true
+    }
+}
+
+impl Default for Optimizer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// What the optimiser knows about the value of a variable.
+#[derive(Debug, Clone, PartialEq)]
+enum Known {
+    Const(Value),
+    Varying,
+}
+
+impl Known {
+    fn same_as(&self, other: &Self) -> bool {
+        match (self, other) {
+            // compare bits so that eg. 0 and -0 aren't merged
+            (Self::Const(Value::Number(a)), Self::Const(Value::Number(b))) => {
+                a.to_bits() == b.to_bits()
+            }
+            _ => self == other,
+        }
+    }
+}
+
+/// Returns true if `value` always evaluates to the same value.
+fn is_literal(value: &Value) -> bool {
+    match value {
+        Value::Number(n) => n.is_finite(),
+        Value::String(_) => true,
+        Value::Variable(name) => matches!(name.as_str(), "null" | "true" | "false"),
+        Value::None => false,
+    }
+}
+
+/// Evaluates a jump condition, if it only depends on number literals.
+fn evaluate(op: ConditionOp, x: &Value, y: &Value) -> Option<bool> {
+    if op == ConditionOp::Always {
+        return Some(true);
+    }
+
+    let (&Value::Number(x), &Value::Number(y)) = (x, y) else {
+        return None;
+    };
+    if !x.is_finite() || !y.is_finite() {
+        return None;
+    }
+
+    // same logic as instructions::Jump
+    Some(match op {
+        ConditionOp::Equal => (x - y).abs() < EQUALITY_EPSILON,
+        ConditionOp::NotEqual => (x - y).abs() >= EQUALITY_EPSILON,
+        ConditionOp::LessThan => x < y,
+        ConditionOp::LessThanEq => x <= y,
+        ConditionOp::GreaterThan => x > y,
+        ConditionOp::GreaterThanEq => x >= y,
+        ConditionOp::StrictEqual => x == y,
+        ConditionOp::Always => unreachable!(),
+    })
+}
+
+/// A program with its jump targets resolved to instruction indices.
+struct Program {
+    items: Vec<Item>,
+    /// Labels after the last instruction.
+    trailing_labels: Vec<(String, Span)>,
+}
+
+struct Item {
+    /// Labels immediately before this instruction.
+    labels: Vec<(String, Span)>,
+    instruction: Instruction,
+    extra: Vec<Value>,
+    span: Span,
+    /// For jumps, the index of the target instruction. This is equal to the number of instructions if the target is a label at the end of the program.
+    target: usize,
+}
+
+impl Item {
+    fn values(&self) -> impl Iterator<Item = &Value> {
+        self.instruction
+            .inputs()
+            .into_iter()
+            .chain(self.instruction.outputs())
+            .chain(&self.extra)
+    }
+}
+
+impl Program {
+    fn new(code: &[Statement]) -> Option<Self> {
+        let mut items = Vec::new();
+        let mut labels = Vec::new();
+        let mut positions = RapidHashMap::default();
+        for statement in code {
+            match statement {
+                Statement::Label(label, span) => {
+                    positions.insert(label.as_str(), items.len());
+                    labels.push((label.clone(), *span));
+                }
+                Statement::Instruction(instruction, extra, span) => items.push(Item {
+                    labels: core::mem::take(&mut labels),
+                    instruction: instruction.clone(),
+                    extra: extra.clone(),
+                    span: *span,
+                    target: 0,
+                }),
+            }
+        }
+
+        let len = items.len();
+        for item in &mut items {
+            if let Instruction::Jump { target, .. } = &item.instruction {
+                item.target = cfg::jump_target(target, &positions, len).ok()?;
+            }
+        }
+
+        Some(Self {
+            items,
+            trailing_labels: labels,
+        })
+    }
+
+    fn uses_counter(&self) -> bool {
+        self.items
+            .iter()
+            .flat_map(Item::values)
+            .any(|value| matches!(value, Value::Variable(name) if name == "@counter"))
+    }
+
+    /// Returns the instructions that may execute after each instruction.
+    fn successors(&self) -> Vec<Vec<usize>> {
+        let len = self.items.len();
+        self.items
+            .iter()
+            .enumerate()
+            .map(|(i, item)| {
+                let taken = match &item.instruction {
+                    Instruction::Jump { op, x, y, .. } => evaluate(*op, x, y),
+                    _ => None,
+                };
+                cfg::successors(
+                    i,
+                    len,
+                    &item.instruction,
+                    &item.extra,
+                    Some(item.target),
+                    taken,
+                )
+            })
+            .collect()
+    }
+
+    /// Redirects jumps to unconditional jumps to the final target. Returns true if any jumps were changed.
+    fn thread_jumps(&mut self) -> bool {
+        let len = self.items.len();
+
+        let mut changed = false;
+        for i in 0..len {
+            if !matches!(self.items[i].instruction, Instruction::Jump { .. }) {
+                continue;
+            }
+
+            let mut target = self.items[i].target;
+            let mut visited = vec![i];
+            loop {
+                let next = cfg::wrap(target, len);
+                match &self.items[next].instruction {
+                    Instruction::Jump {
+                        op: ConditionOp::Always,
+                        ..
+                    } if !visited.contains(&next) => {
+                        visited.push(next);
+                        target = self.items[next].target;
+                    }
+                    _ => break,
+                }
+            }
+
+            if target != self.items[i].target {
+                self.items[i].target = target;
+                changed = true;
+            }
+        }
+        changed
+    }
+
+    /// Removes instructions, moving their labels to the next remaining instruction.
+    fn remove(&mut self, removed: &[bool]) {
+        // new index of each old index, including the end of the program
+        let mut indices = Vec::with_capacity(removed.len() + 1);
+        let mut count = 0;
+        for &removed in removed {
+            indices.push(count);
+            if !removed {
+                count += 1;
+            }
+        }
+        indices.push(count);
+
+        let mut labels = Vec::new();
+        let mut items = Vec::with_capacity(count);
+        for (mut item, &removed) in core::mem::take(&mut self.items).into_iter().zip(removed) {
+            labels.append(&mut item.labels);
+            if !removed {
+                item.labels = core::mem::take(&mut labels);
+                item.target = indices[item.target];
+                items.push(item);
+            }
+        }
+        labels.append(&mut self.trailing_labels);
+
+        self.items = items;
+        self.trailing_labels = labels;
+    }
+
+    fn into_statements(mut self) -> Vec<Statement> {
+        let len = self.items.len();
+
+        // the position of each label, where later labels with the same name replace earlier ones
+        let mut positions = RapidHashMap::default();
+        for (i, item) in self.items.iter().enumerate() {
+            for (label, _) in &item.labels {
+                positions.insert(label.clone(), i);
+            }
+        }
+        for (label, _) in &self.trailing_labels {
+            positions.insert(label.clone(), len);
+        }
+
+        // point each jump at its target, adding labels where needed
+        let mut next_label = 0;
+        for i in 0..len {
+            let target = self.items[i].target;
+            let Instruction::Jump { target: value, .. } = &self.items[i].instruction else {
+                continue;
+            };
+
+            let new_value = match value {
+                Value::Number(_) => Value::Number((if target < len { target } else { 0 }) as f64),
+                Value::Variable(name) if positions.get(name) == Some(&target) => continue,
+                _ => {
+                    let labels = match self.items.get(target) {
+                        Some(item) => &item.labels,
+                        None => &self.trailing_labels,
+                    };
+                    let existing = labels
+                        .iter()
+                        .map(|(label, _)| label)
+                        .find(|&label| positions.get(label) == Some(&target));
+
+                    let label = match existing {
+                        Some(label) => label.clone(),
+                        None => {
+                            let label = loop {
+                                let label = format!("__opt{next_label}");
+                                next_label += 1;
+                                if !positions.contains_key(&label) {
+                                    break label;
+                                }
+                            };
+                            positions.insert(label.clone(), target);
+                            let labels = match self.items.get_mut(target) {
+                                Some(item) => &mut item.labels,
+                                None => &mut self.trailing_labels,
+                            };
+                            labels.push((label.clone(), Span::default()));
+                            label
+                        }
+                    };
+                    Value::Variable(label)
+                }
+            };
+
+            if let Instruction::Jump { target, .. } = &mut self.items[i].instruction {
+                *target = new_value;
+            }
+        }
+
+        let mut statements = Vec::new();
+        for item in self.items {
+            statements.extend(
+                item.labels
+                    .into_iter()
+                    .map(|(label, span)| Statement::Label(label, span)),
+            );
+            statements.push(Statement::Instruction(
+                item.instruction,
+                item.extra,
+                item.span,
+            ));
+        }
+        statements.extend(
+            self.trailing_labels
+                .into_iter()
+                .map(|(label, span)| Statement::Label(label, span)),
+        );
+        statements
+    }
+}
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use alloc::string::String;
+
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+    use crate::{
+        parser::{parse, print_ast},
+        types::{PackedPoint2, ProcessorConfig},
+        vm::{Building, LogicVMBuilder, buildings::HYPER_PROCESSOR},
+    };
+
+    fn optimize_with(optimizer: &Optimizer, code: &str) -> String {
+        print_ast(&optimizer.optimize(&parse(code).unwrap()))
+    }
+
+    /// Runs a program until it stops, and returns what it printed.
+    fn run(code: &str) -> String {
+        let mut builder = LogicVMBuilder::new();
+        builder.add_building(
+            Building::from_processor_config(
+                HYPER_PROCESSOR,
+                PackedPoint2::new(0, 0),
+                &ProcessorConfig::from_code(code),
+                &builder,
+            )
+            .unwrap(),
+        );
+        let mut vm = builder.build().unwrap();
+        assert!(vm.run(Some(100)), "program didn't stop");
+
+        vm.building(PackedPoint2::new(0, 0))
+            .unwrap()
+            .data
+            .borrow()
+            .unwrap_processor()
+            .state
+            .printbuffer
+            .to_string_lossy()
+    }
+
+    fn assert_optimized_with(optimizer: &Optimizer, code: &str, want: &str) {
+        let optimized = optimize_with(optimizer, code);
+        assert_eq!(optimized, want);
+        assert_eq!(run(&optimized), run(code));
+    }
+
+    fn assert_optimized(code: &str, want: &str) {
+        assert_optimized_with(&Optimizer::new(), code, want);
+    }
+
+    #[test]
+    fn test_self_assignment() {
+        assert_optimized(
+            "
+            op add i i 1
+            set i i
+            print i
+            jump 0 lessThan i 3
+            stop
+            ",
+            "op add i i 1\nprint i\njump 0 lessThan i 3\nstop\n",
+        );
+    }
+
+    #[test]
+    fn test_jump_threading() {
+        assert_optimized(
+            "
+            loop:
+            op add i i 1
+            jump next lessThan i 3
+            print i
+            stop
+            next:
+            jump loop always
+            ",
+            "loop:\nop add i i 1\njump loop lessThan i 3\nprint i\nstop\nnext:\n",
+        );
+    }
+
+    #[test]
+    fn test_jump_threading_new_label() {
+        assert_optimized(
+            r#"
+            op add i i 1
+            jump a lessThan i 3
+            print "done"
+            stop
+            a:
+            jump 0 always
+            "#,
+            "__opt0:\nop add i i 1\njump __opt0 lessThan i 3\nprint \"done\"\nstop\na:\n",
+        );
+    }
+
+    #[test]
+    fn test_jump_threading_cycle() {
+        assert_optimized(
+            "
+            jump b lessThan @tick 0
+            stop
+            a:
+            jump b always
+            b:
+            jump a always
+            ",
+            "jump a lessThan @tick 0\nstop\na:\njump a always\nb:\n",
+        );
+    }
+
+    #[test]
+    fn test_constant_propagation() {
+        assert_optimized(
+            r#"
+            set x 5
+            set y x
+            jump skip lessThan y 3
+            print "big"
+            skip:
+            print y
+            stop
+            "#,
+            "print \"big\"\nskip:\nprint 5\nstop\n",
+        );
+    }
+
+    #[test]
+    fn test_constant_propagation_merge() {
+        // x is 1 on one path and 2 on the other, so it can't be replaced after they meet
+        assert_optimized(
+            "
+            set x 1
+            jump a lessThan @tick 0
+            set x 2
+            a:
+            print x
+            set y \"a\"
+            jump b always
+            set y \"b\"
+            b:
+            print y
+            stop
+            ",
+            "set x 1\njump a lessThan @tick 0\nset x 2\na:\nprint x\nb:\nprint \"a\"\nstop\n",
+        );
+    }
+
+    #[test]
+    fn test_constant_propagation_loop() {
+        // i is null on the first iteration, so it's unknown at the start of the program
+        assert_optimized(
+            "
+            set step 2
+            op add i i step
+            print i
+            jump 0 lessThan i 6
+            stop
+            ",
+            "op add i i 2\nprint i\njump 0 lessThan i 6\nstop\n",
+        );
+    }
+
+    #[test]
+    fn test_unreachable() {
+        assert_optimized(
+            r#"
+            print "a"
+            stop
+            print "b"
+            end
+            "#,
+            "print \"a\"\nstop\n",
+        );
+    }
+
+    #[test]
+    fn test_dead_stores() {
+        assert_optimized(
+            "
+            op add x 1 2
+            sensor y @this @x
+            op add z 1 2
+            print z
+            noop
+            stop
+            ",
+            "op add z 1 2\nprint z\nstop\n",
+        );
+    }
+
+    #[test]
+    fn test_numeric_jump_targets() {
+        assert_optimized(
+            "
+            noop
+            op add i i 1
+            print i
+            jump 1 lessThan i 3
+            stop
+            ",
+            "op add i i 1\nprint i\njump 0 lessThan i 3\nstop\n",
+        );
+    }
+
+    #[test]
+    fn test_preserve_indices() {
+        let code = r#"
+            op add ret @counter 1
+            jump func always
+            print "done"
+            stop
+            func:
+            set x x
+            print "func"
+            set @counter ret
+        "#;
+
+        assert_optimized(
+            code,
+            "op add ret @counter 1\njump func always\nprint \"done\"\nstop\nfunc:\nset x x\nprint \"func\"\nset @counter ret\n",
+        );
+
+        let optimizer = Optimizer {
+            preserve_indices: false,
+            ..Optimizer::new()
+        };
+        assert_optimized_with(
+            &optimizer,
+            code,
+            "op add ret @counter 1\njump func always\nprint \"done\"\nstop\nfunc:\nprint \"func\"\nset @counter ret\n",
+        );
+    }
+
+    #[test]
+    fn test_dynamic_access() {
+        let code = "set x 5\nwrite 6 @this \"x\"\nprint x\nstop\n";
+        assert_eq!(optimize_with(&Optimizer::new(), code), code);
+
+        // memory cells don't have variables
+        assert_eq!(
+            optimize_with(&Optimizer::new(), "set x 5\nwrite x cell1 i\nstop\n"),
+            "write 5 cell1 i\nstop\n"
+        );
+    }
+
+    #[test]
+    fn test_keep_one_instruction() {
+        // these never stop, so just check the output
+        assert_eq!(optimize_with(&Optimizer::new(), "set x 1\nend\n"), "noop\n");
+        assert_eq!(optimize_with(&Optimizer::new(), "noop\n"), "noop\n");
+    }
+
+    #[test]
+    fn test_invalid_jumps() {
+        for code in ["jump missing always\nset x x\n", "jump 2 always\nset x x\n"] {
+            assert_eq!(optimize_with(&Optimizer::new(), code), code);
+        }
+    }
+
+    #[test]
+    fn test_links_and_constants() {
+        // links and constants aren't variables, so writes to them are ignored
+        assert_optimized(
+            "
+            set cell1 5
+            set @unit 5
+            print cell1
+            print @unit
+            stop
+            ",
+            "set cell1 5\nset @unit 5\nprint cell1\nprint @unit\nstop\n",
+        );
+    }
+}
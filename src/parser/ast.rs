@@ -1,19 +1,69 @@
-use alloc::{string::String, vec::Vec};
+use alloc::{string::String, vec, vec::Vec};
+use core::fmt::Display;
 
 use serde::{Deserialize, Serialize};
+use strum::{IntoStaticStr, VariantNames};
 
 use crate::types::{ContentType, LAccess};
 
 #[cfg_attr(feature = "serde_alloc", derive(Deserialize, Serialize))]
 #[derive(Debug, Clone, PartialEq)]
 pub enum Statement {
-    Label(String),
-    /// `1` contains any extra unused arguments.
-    Instruction(Instruction, Vec<Value>),
+    /// `1` is the location of the label in the source code.
+    Label(String, Span),
+    /// `1` contains any extra unused arguments, and `2` is the location of the instruction in the source code.
+    Instruction(Instruction, Vec<Value>, Span),
 }
 
+impl Statement {
+    pub fn span(&self) -> Span {
+        match self {
+            Self::Label(_, span) | Self::Instruction(_, _, span) => *span,
+        }
+    }
+}
+
+/// A range of source code, from `start` (inclusive) to `end` (exclusive).
+///
+/// Statements created without any source code, eg. by hand or by a program, should use [`Span::default`].
 #[cfg_attr(feature = "serde_alloc", derive(Deserialize, Serialize))]
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct Span {
+    pub start: Location,
+    pub end: Location,
+}
+
+impl Display for Span {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        self.start.fmt(f)
+    }
+}
+
+/// A position in source code. Lines and columns are 1-indexed, and columns are counted in characters.
+///
+/// The default location (line 0, column 0) is used for code that has no source.
+#[cfg_attr(feature = "serde_alloc", derive(Deserialize, Serialize))]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Location {
+    pub line: usize,
+    pub column: usize,
+}
+
+impl Location {
+    pub fn new(line: usize, column: usize) -> Self {
+        Self { line, column }
+    }
+}
+
+impl Display for Location {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(f, "line {}, column {}", self.line, self.column)
+    }
+}
+
+#[cfg_attr(feature = "serde_alloc", derive(Deserialize, Serialize))]
+#[derive(Debug, Clone, PartialEq, IntoStaticStr, VariantNames)]
+#[strum(serialize_all = "lowercase")]
 pub enum Instruction {
     // input/output
     Read {
@@ -67,6 +117,15 @@ pub enum Instruction {
         target: Value,
         sensor: Value,
     },
+    Radar {
+        target1: RadarTarget,
+        target2: RadarTarget,
+        target3: RadarTarget,
+        sort: RadarSort,
+        radar: Value,
+        order: Value,
+        result: Value,
+    },
     // operations
     Set {
         to: Value,
@@ -128,11 +187,150 @@ pub enum Instruction {
     SetRate {
         value: Value,
     },
+    Message {
+        message_type: MessageType,
+        duration: Value,
+        success: Value,
+    },
+    LocalePrint {
+        value: Value,
+    },
+    Fetch {
+        fetch_type: FetchType,
+        result: Value,
+        team: Value,
+        index: Value,
+        extra: Value,
+    },
+    SetProp {
+        prop: Value,
+        target: Value,
+        value: Value,
+    },
     // unknown
+    /// An instruction that isn't supported. Its arguments are stored in [`Statement::Instruction`]'s extra arguments.
     Unknown(String),
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Deserialize, Serialize)]
+/// Expands to a list of the arguments that an instruction reads from, borrowed the same way as `$self`.
+macro_rules! instruction_inputs {
+    ($self:expr) => {
+        match $self {
+            Instruction::Read {
+                target, address, ..
+            } => vec![target, address],
+            Instruction::Write {
+                value,
+                target,
+                address,
+            } => vec![value, target, address],
+            Instruction::Draw {
+                x,
+                y,
+                p1,
+                p2,
+                p3,
+                p4,
+                ..
+            } => vec![x, y, p1, p2, p3, p4],
+            Instruction::Print { value }
+            | Instruction::PrintChar { value }
+            | Instruction::Format { value }
+            | Instruction::Wait { value }
+            | Instruction::SetRate { value }
+            | Instruction::LocalePrint { value } => vec![value],
+            Instruction::DrawFlush { target } | Instruction::PrintFlush { target } => vec![target],
+            Instruction::GetLink { index, .. } => vec![index],
+            Instruction::Control {
+                target, p1, p2, p3, ..
+            } => vec![target, p1, p2, p3],
+            Instruction::Sensor { target, sensor, .. } => vec![target, sensor],
+            Instruction::Radar { radar, order, .. } => vec![radar, order],
+            Instruction::Set { from, .. } => vec![from],
+            Instruction::Op { x, y, .. }
+            | Instruction::Jump { x, y, .. }
+            | Instruction::GetBlock { x, y, .. } => {
+                vec![x, y]
+            }
+            Instruction::Select {
+                x,
+                y,
+                if_true,
+                if_false,
+                ..
+            } => vec![x, y, if_true, if_false],
+            Instruction::Lookup { id, .. } => vec![id],
+            Instruction::PackColor { r, g, b, a, .. } => vec![r, g, b, a],
+            Instruction::UnpackColor { value, .. } => vec![value],
+            Instruction::Message { duration, .. } => vec![duration],
+            Instruction::Fetch {
+                team, index, extra, ..
+            } => vec![team, index, extra],
+            Instruction::SetProp {
+                prop,
+                target,
+                value,
+            } => vec![prop, target, value],
+            Instruction::Noop | Instruction::Stop | Instruction::End | Instruction::Unknown(_) => {
+                vec![]
+            }
+        }
+    };
+}
+
+impl Instruction {
+    /// Returns true if this instruction can only be executed by privileged processors, eg. world processors.
+    pub fn is_privileged(&self) -> bool {
+        matches!(
+            self,
+            Self::GetBlock { .. }
+                | Self::SetRate { .. }
+                | Self::Message { .. }
+                | Self::LocalePrint { .. }
+                | Self::Fetch { .. }
+                | Self::SetProp { .. }
+        )
+    }
+
+    /// Returns the arguments that this instruction reads from.
+    ///
+    /// Jump targets are not included, since they refer to labels or instruction indices rather than variables.
+    pub fn inputs(&self) -> Vec<&Value> {
+        instruction_inputs!(self)
+    }
+
+    /// Returns mutable references to the arguments that this instruction reads from.
+    ///
+    /// See [`Instruction::inputs`].
+    pub fn inputs_mut(&mut self) -> Vec<&mut Value> {
+        instruction_inputs!(self)
+    }
+
+    /// Returns the arguments that this instruction writes to.
+    pub fn outputs(&self) -> Vec<&Value> {
+        match self {
+            Self::Read { result, .. }
+            | Self::GetLink { result, .. }
+            | Self::Sensor { result, .. }
+            | Self::Radar { result, .. }
+            | Self::Op { result, .. }
+            | Self::Select { result, .. }
+            | Self::Lookup { result, .. }
+            | Self::PackColor { result, .. }
+            | Self::GetBlock { result, .. }
+            | Self::Fetch { result, .. } => vec![result],
+            Self::Set { to, .. } => vec![to],
+            Self::UnpackColor { r, g, b, a, .. } => vec![r, g, b, a],
+            Self::Message { success, .. } => vec![success],
+            _ => vec![],
+        }
+    }
+}
+
+#[derive(
+    Debug, Clone, Copy, PartialEq, Eq, Hash, Deserialize, Serialize, IntoStaticStr, VariantNames,
+)]
+#[strum(serialize_all = "camelCase")]
 pub enum DrawOp {
     Clear,
     Color,
@@ -152,7 +350,10 @@ pub enum DrawOp {
     Reset,
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Deserialize, Serialize)]
+#[derive(
+    Debug, Clone, Copy, PartialEq, Eq, Hash, Deserialize, Serialize, IntoStaticStr, VariantNames,
+)]
+#[strum(serialize_all = "camelCase")]
 pub enum ConditionOp {
     Equal,
     NotEqual,
@@ -164,7 +365,10 @@ pub enum ConditionOp {
     Always,
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Deserialize, Serialize)]
+#[derive(
+    Debug, Clone, Copy, PartialEq, Eq, Hash, Deserialize, Serialize, IntoStaticStr, VariantNames,
+)]
+#[strum(serialize_all = "camelCase")]
 pub enum LogicOp {
     Add,
     Sub,
@@ -218,7 +422,10 @@ pub enum LogicOp {
     Atan,
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Deserialize, Serialize)]
+#[derive(
+    Debug, Clone, Copy, PartialEq, Eq, Hash, Deserialize, Serialize, IntoStaticStr, VariantNames,
+)]
+#[strum(serialize_all = "camelCase")]
 pub enum TileLayer {
     Floor,
     Ore,
@@ -226,6 +433,59 @@ pub enum TileLayer {
     Building,
 }
 
+#[derive(
+    Debug, Clone, Copy, PartialEq, Eq, Hash, Deserialize, Serialize, IntoStaticStr, VariantNames,
+)]
+#[strum(serialize_all = "camelCase")]
+pub enum MessageType {
+    Notify,
+    Announce,
+    Toast,
+    Mission,
+}
+
+#[derive(
+    Debug, Clone, Copy, PartialEq, Eq, Hash, Deserialize, Serialize, IntoStaticStr, VariantNames,
+)]
+#[strum(serialize_all = "camelCase")]
+pub enum FetchType {
+    Unit,
+    UnitCount,
+    Player,
+    PlayerCount,
+    Core,
+    CoreCount,
+    Build,
+    BuildCount,
+}
+
+#[derive(
+    Debug, Clone, Copy, PartialEq, Eq, Hash, Deserialize, Serialize, IntoStaticStr, VariantNames,
+)]
+#[strum(serialize_all = "camelCase")]
+pub enum RadarTarget {
+    Any,
+    Enemy,
+    Ally,
+    Player,
+    Attacker,
+    Flying,
+    Boss,
+    Ground,
+}
+
+#[derive(
+    Debug, Clone, Copy, PartialEq, Eq, Hash, Deserialize, Serialize, IntoStaticStr, VariantNames,
+)]
+#[strum(serialize_all = "camelCase")]
+pub enum RadarSort {
+    Distance,
+    Health,
+    Shield,
+    Armor,
+    MaxHealth,
+}
+
 #[cfg_attr(feature = "serde_alloc", derive(Deserialize, Serialize))]
 #[derive(Debug, Clone, PartialEq)]
 pub enum Value {
@@ -3,7 +3,13 @@ use alloc::vec::Vec;
 #[cfg(feature = "serde_alloc")]
 use core::error::Error;
 #[cfg(feature = "std")]
-use std::{boxed::Box, string::ToString};
+use std::{
+    boxed::Box,
+    fmt::{self, Display},
+    format,
+    string::{String, ToString},
+    vec,
+};
 
 // reexport for consumers
 #[cfg(feature = "std")]
@@ -12,6 +18,8 @@ pub use lalrpop_util::ParseError;
 #[cfg(feature = "std")]
 pub use self::grammar::LogicParser;
 #[cfg(feature = "std")]
+pub use self::printer::print_ast;
+#[cfg(feature = "std")]
 use crate::{
     types::{PackedPoint2, content},
     vm::{Building, LogicVMBuilder, ProcessorBuilder},
@@ -20,6 +28,8 @@ use crate::{
 pub mod ast;
 #[cfg(feature = "std")]
 mod grammar_util;
+#[cfg(feature = "std")]
+mod printer;
 
 #[cfg(feature = "std")]
 lalrpop_util::lalrpop_mod!(
@@ -28,13 +38,230 @@ lalrpop_util::lalrpop_mod!(
     "/parser/grammar.rs"
 );
 
+/// Parses mlog code into a list of statements.
+#[cfg(feature = "std")]
+pub fn parse(code: &str) -> Result<Vec<ast::Statement>, SyntaxError> {
+    parse_with(&LogicParser::new(), code)
+}
+
+/// Like [`parse`], but reuses an existing parser.
+#[cfg(feature = "std")]
+pub fn parse_with(parser: &LogicParser, code: &str) -> Result<Vec<ast::Statement>, SyntaxError> {
+    let lines = LineIndex::new(code);
+    parser
+        .parse(&lines, code)
+        .map_err(|e| SyntaxError::new(e, &lines))
+}
+
+/// Converts byte offsets in some source code to lines and columns.
+#[cfg(feature = "std")]
+#[derive(Debug, Clone)]
+pub struct LineIndex<'a> {
+    source: &'a str,
+    line_starts: Vec<usize>,
+}
+
+#[cfg(feature = "std")]
+impl<'a> LineIndex<'a> {
+    pub fn new(source: &'a str) -> Self {
+        let line_starts = core::iter::once(0)
+            .chain(source.match_indices('\n').map(|(i, _)| i + 1))
+            .collect();
+        Self {
+            source,
+            line_starts,
+        }
+    }
+
+    /// Returns the location of a byte offset in the source code.
+    pub fn location(&self, offset: usize) -> ast::Location {
+        let offset = offset.min(self.source.len());
+        let line = self.line_starts.partition_point(|&start| start <= offset);
+        let start = self.line_starts[line - 1];
+        let column = self.source[start..offset].chars().count() + 1;
+        ast::Location::new(line, column)
+    }
+
+    /// Returns the span between two byte offsets in the source code.
+    pub fn span(&self, start: usize, end: usize) -> ast::Span {
+        ast::Span {
+            start: self.location(start),
+            end: self.location(end),
+        }
+    }
+
+    /// Returns the byte offset of a location in the source code.
+    pub fn offset(&self, location: ast::Location) -> usize {
+        let Some(&start) = location
+            .line
+            .checked_sub(1)
+            .and_then(|line| self.line_starts.get(line))
+        else {
+            return if location.line == 0 {
+                0
+            } else {
+                self.source.len()
+            };
+        };
+        self.source[start..]
+            .char_indices()
+            .nth(location.column.saturating_sub(1))
+            .map_or(self.source.len(), |(i, _)| start + i)
+    }
+
+    /// Returns the source code covered by a span.
+    pub fn text(&self, span: ast::Span) -> &'a str {
+        let start = self.offset(span.start);
+        let end = self.offset(span.end).max(start);
+        &self.source[start..end]
+    }
+
+    /// Returns the text of a 1-indexed line, without the line ending.
+    pub fn line(&self, line: usize) -> Option<&'a str> {
+        let start = *self.line_starts.get(line.checked_sub(1)?)?;
+        let end = self
+            .line_starts
+            .get(line)
+            .map_or(self.source.len(), |&end| end - 1);
+        Some(self.source[start..end].trim_end_matches('\r'))
+    }
+
+    /// Renders the first line of a span, with the spanned characters underlined.
+    ///
+    /// Returns an empty string if the span is not in the source code.
+    pub fn snippet(&self, span: ast::Span) -> String {
+        let Some(text) = self.line(span.start.line) else {
+            return String::new();
+        };
+
+        let start = span.start.column.max(1);
+        let end = if span.end.line == span.start.line {
+            span.end.column
+        } else {
+            text.chars().count() + 1
+        };
+
+        let number = span.start.line.to_string();
+        let gutter = " ".repeat(number.len());
+        format!(
+            "{gutter} |\n{number} | {text}\n{gutter} | {}{}",
+            " ".repeat(start - 1),
+            "^".repeat(end.saturating_sub(start).max(1)),
+        )
+    }
+}
+
+/// Returns the number of tokens in a single statement, counting each string literal as one token.
+#[cfg(feature = "std")]
+pub(crate) fn count_tokens(statement: &str) -> usize {
+    let mut count = 0;
+    let mut chars = statement.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '#' | ';' => break,
+            ' ' | '\t' | '\r' | '\n' => {}
+            '"' => {
+                count += 1;
+                chars.by_ref().find(|&c| c == '"');
+            }
+            _ => {
+                count += 1;
+                while chars
+                    .next_if(|c| !matches!(c, ' ' | '\t' | '\r' | '\n' | '#' | ';'))
+                    .is_some()
+                {}
+            }
+        }
+    }
+    count
+}
+
+#[cfg(feature = "std")]
+const MAX_DISPLAYED_EXPECTED: usize = 8;
+
+/// An error encountered while parsing mlog code.
+#[cfg(feature = "std")]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SyntaxError {
+    pub message: String,
+    pub span: ast::Span,
+    /// The tokens that would have been accepted instead, if any.
+    pub expected: Vec<String>,
+    /// The line of code containing the error, rendered by [`LineIndex::snippet`].
+    pub snippet: String,
+}
+
+#[cfg(feature = "std")]
+impl SyntaxError {
+    fn new<T: Display>(error: ParseError<usize, T, String>, lines: &LineIndex) -> Self {
+        let (message, (start, end), expected) = match error {
+            ParseError::InvalidToken { location } => {
+                // highlight the character that couldn't be lexed
+                let len = lines.source[location..]
+                    .chars()
+                    .next()
+                    .map_or(0, char::len_utf8);
+                (
+                    "invalid token".to_string(),
+                    (location, location + len),
+                    vec![],
+                )
+            }
+            ParseError::UnrecognizedEof { location, expected } => (
+                "unexpected end of code".to_string(),
+                (location, location),
+                expected,
+            ),
+            ParseError::UnrecognizedToken {
+                token: (start, token, end),
+                expected,
+            } => {
+                let token = token.to_string();
+                let message = if token.trim().is_empty() {
+                    "unexpected end of line".to_string()
+                } else {
+                    format!("unexpected token `{token}`")
+                };
+                (message, (start, end), expected)
+            }
+            ParseError::ExtraToken {
+                token: (start, token, end),
+            } => (format!("extra token `{token}`"), (start, end), vec![]),
+            ParseError::User { error } => (error, (0, 0), vec![]),
+        };
+
+        let span = lines.span(start, end);
+        Self {
+            message,
+            span,
+            expected,
+            snippet: lines.snippet(span),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl Display for SyntaxError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.span, self.message)?;
+        // long lists are usually just "any instruction" or "any value", which isn't very helpful
+        if !self.expected.is_empty() && self.expected.len() <= MAX_DISPLAYED_EXPECTED {
+            write!(f, " (expected one of {})", self.expected.join(", "))?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(feature = "std")]
+impl Error for SyntaxError {}
+
 #[cfg(feature = "std")]
 pub fn parse_and_serialize_ast(
     parser: &LogicParser,
     code: &str,
     verify: bool,
 ) -> Result<Vec<u8>, Box<dyn Error>> {
-    let ast = parser.parse(code).map_err(|e| e.to_string())?;
+    let ast = parse_with(parser, code)?;
 
     // build a fake VM to make sure the AST will load properly
     if verify {
@@ -46,11 +273,13 @@ pub fn parse_and_serialize_ast(
                 ipt: 1.,
                 privileged: false,
                 code: ast.clone().into_boxed_slice(),
+                source: Some(code),
                 links: &[],
                 instruction_hook: None,
+                limits: None,
             },
             &builder,
-        ));
+        )?);
         builder.build()?;
     }
 
@@ -82,7 +311,7 @@ mod tests {
     macro_rules! assert_ast {
         ($input:expr, $($x:expr),* $(,)?) => {
             assert_eq!(
-                LogicParser::new().parse($input).unwrap(),
+                strip_spans(parse($input).unwrap()),
                 vec![$($x),*],
                 "{}",
                 $input,
@@ -95,10 +324,22 @@ mod tests {
             instruction!($i,)
         };
         ($i:expr, $($x:expr),* $(,)?) => {
-            Statement::Instruction($i, vec![$($x),*])
+            Statement::Instruction($i, vec![$($x),*], Span::default())
         };
     }
 
+    /// Replaces every span with the default, so that ASTs can be compared regardless of where their statements were parsed from.
+    pub(super) fn strip_spans(ast: Vec<Statement>) -> Vec<Statement> {
+        ast.into_iter()
+            .map(|statement| match statement {
+                Statement::Label(label, _) => Statement::Label(label, Span::default()),
+                Statement::Instruction(instruction, args, _) => {
+                    Statement::Instruction(instruction, args, Span::default())
+                }
+            })
+            .collect()
+    }
+
     fn variable(value: &str) -> Value {
         Value::Variable(value.into())
     }
@@ -228,9 +469,17 @@ mod tests {
             "
             foo
             bar baz 1
+            status false wet @unit 10
             ",
             instruction!(Unknown("foo".into())),
             instruction!(Unknown("bar".into()), variable("baz"), number(1)),
+            instruction!(
+                Unknown("status".into()),
+                variable("false"),
+                variable("wet"),
+                variable("@unit"),
+                number(10),
+            ),
         ];
     }
 
@@ -266,12 +515,75 @@ mod tests {
             bar"a":
             :a:
             "#,
-            Statement::Label("foo".into()),
-            Statement::Label(r#"bar"a""#.into()),
-            Statement::Label(":a".into()),
+            Statement::Label("foo".into(), Span::default()),
+            Statement::Label(r#"bar"a""#.into(), Span::default()),
+            Statement::Label(":a".into(), Span::default()),
         ];
     }
 
+    fn span(start: (usize, usize), end: (usize, usize)) -> Span {
+        Span {
+            start: Location::new(start.0, start.1),
+            end: Location::new(end.0, end.1),
+        }
+    }
+
+    #[test]
+    fn test_spans() {
+        let spans: Vec<_> = parse("foo:\n  set x 1 # comment\r\nnoop; stop\nprint \"ü\"; end")
+            .unwrap()
+            .iter()
+            .map(|statement| statement.span())
+            .collect();
+        assert_eq!(spans, vec![
+            span((1, 1), (1, 5)),
+            span((2, 3), (2, 10)),
+            span((3, 1), (3, 5)),
+            span((3, 7), (3, 11)),
+            span((4, 1), (4, 10)),
+            span((4, 12), (4, 15)),
+        ]);
+    }
+
+    #[test]
+    fn test_syntax_error() {
+        let err = parse("set x 1\n  jump\nend").unwrap_err();
+        assert_eq!(err.message, "unexpected end of line");
+        assert_eq!(err.span, span((2, 7), (3, 1)));
+        assert!(!err.expected.is_empty());
+        assert_eq!(err.snippet, "  |\n2 |   jump\n  |       ^");
+        assert_eq!(err.to_string(), "line 2, column 7: unexpected end of line");
+    }
+
+    #[test]
+    fn test_syntax_error_eof() {
+        let err = parse("print 1\nop add x").unwrap_err();
+        assert_eq!(err.message, "unexpected end of code");
+        assert_eq!(err.span, span((2, 9), (2, 9)));
+        assert_eq!(err.snippet, "  |\n2 | op add x\n  |         ^");
+    }
+
+    #[test]
+    fn test_line_index() {
+        let lines = LineIndex::new("ab\r\nçd\n\nef");
+        assert_eq!(lines.location(0), Location::new(1, 1));
+        assert_eq!(lines.location(4), Location::new(2, 1));
+        assert_eq!(lines.location(6), Location::new(2, 2));
+        assert_eq!(lines.location(8), Location::new(3, 1));
+        assert_eq!(lines.location(9), Location::new(4, 1));
+        assert_eq!(lines.location(100), Location::new(4, 3));
+
+        assert_eq!(lines.line(0), None);
+        assert_eq!(lines.line(1), Some("ab"));
+        assert_eq!(lines.line(2), Some("çd"));
+        assert_eq!(lines.line(3), Some(""));
+        assert_eq!(lines.line(4), Some("ef"));
+        assert_eq!(lines.line(5), None);
+
+        assert_eq!(lines.snippet(Span::default()), "");
+        assert_eq!(lines.snippet(span((2, 2), (4, 1))), "  |\n2 | çd\n  |  ^");
+    }
+
     // instruction-specific tests
 
     #[test]
@@ -294,6 +606,208 @@ mod tests {
         ];
     }
 
+    #[test]
+    fn test_message() {
+        assert_ast![
+            "
+            message notify 0 @wait
+            message announce 3 success
+            message toast duration @wait
+            message mission 0 success
+            ",
+            instruction!(Message {
+                message_type: MessageType::Notify,
+                duration: number(0),
+                success: variable("@wait"),
+            }),
+            instruction!(Message {
+                message_type: MessageType::Announce,
+                duration: number(3),
+                success: variable("success"),
+            }),
+            instruction!(Message {
+                message_type: MessageType::Toast,
+                duration: variable("duration"),
+                success: variable("@wait"),
+            }),
+            instruction!(Message {
+                message_type: MessageType::Mission,
+                duration: number(0),
+                success: variable("success"),
+            }),
+        ];
+    }
+
+    #[test]
+    fn test_localeprint() {
+        assert_ast![
+            r#"
+            localeprint "name"
+            print message
+            print notify
+            "#,
+            instruction!(LocalePrint {
+                value: string("name")
+            }),
+            instruction!(Print {
+                value: variable("message")
+            }),
+            instruction!(Print {
+                value: variable("notify")
+            }),
+        ];
+    }
+
+    #[test]
+    fn test_fetch() {
+        assert_ast![
+            "
+            fetch unit result @sharded 0 @poly
+            fetch unitCount result @sharded 0 null
+            fetch player result @sharded 0 null
+            fetch playerCount result @sharded 0 null
+            fetch core result @sharded index null
+            fetch coreCount result @sharded 0 null
+            fetch build result team 0 @sorter
+            fetch buildCount result 1 0 @sorter
+            ",
+            instruction!(Fetch {
+                fetch_type: FetchType::Unit,
+                result: variable("result"),
+                team: variable("@sharded"),
+                index: number(0),
+                extra: variable("@poly"),
+            }),
+            instruction!(Fetch {
+                fetch_type: FetchType::UnitCount,
+                result: variable("result"),
+                team: variable("@sharded"),
+                index: number(0),
+                extra: variable("null"),
+            }),
+            instruction!(Fetch {
+                fetch_type: FetchType::Player,
+                result: variable("result"),
+                team: variable("@sharded"),
+                index: number(0),
+                extra: variable("null"),
+            }),
+            instruction!(Fetch {
+                fetch_type: FetchType::PlayerCount,
+                result: variable("result"),
+                team: variable("@sharded"),
+                index: number(0),
+                extra: variable("null"),
+            }),
+            instruction!(Fetch {
+                fetch_type: FetchType::Core,
+                result: variable("result"),
+                team: variable("@sharded"),
+                index: variable("index"),
+                extra: variable("null"),
+            }),
+            instruction!(Fetch {
+                fetch_type: FetchType::CoreCount,
+                result: variable("result"),
+                team: variable("@sharded"),
+                index: number(0),
+                extra: variable("null"),
+            }),
+            instruction!(Fetch {
+                fetch_type: FetchType::Build,
+                result: variable("result"),
+                team: variable("team"),
+                index: number(0),
+                extra: variable("@sorter"),
+            }),
+            instruction!(Fetch {
+                fetch_type: FetchType::BuildCount,
+                result: variable("result"),
+                team: number(1),
+                index: number(0),
+                extra: variable("@sorter"),
+            }),
+        ];
+    }
+
+    #[test]
+    fn test_radar() {
+        assert_ast![
+            "
+            radar enemy any any distance turret 1 result
+            radar ally flying boss maxHealth @this order result
+            radar player attacker ground health turret 0 result
+            radar any any any shield turret 1 result
+            radar any any any armor turret 1 result
+            ",
+            instruction!(Radar {
+                target1: RadarTarget::Enemy,
+                target2: RadarTarget::Any,
+                target3: RadarTarget::Any,
+                sort: RadarSort::Distance,
+                radar: variable("turret"),
+                order: number(1),
+                result: variable("result"),
+            }),
+            instruction!(Radar {
+                target1: RadarTarget::Ally,
+                target2: RadarTarget::Flying,
+                target3: RadarTarget::Boss,
+                sort: RadarSort::MaxHealth,
+                radar: variable("@this"),
+                order: variable("order"),
+                result: variable("result"),
+            }),
+            instruction!(Radar {
+                target1: RadarTarget::Player,
+                target2: RadarTarget::Attacker,
+                target3: RadarTarget::Ground,
+                sort: RadarSort::Health,
+                radar: variable("turret"),
+                order: number(0),
+                result: variable("result"),
+            }),
+            instruction!(Radar {
+                target1: RadarTarget::Any,
+                target2: RadarTarget::Any,
+                target3: RadarTarget::Any,
+                sort: RadarSort::Shield,
+                radar: variable("turret"),
+                order: number(1),
+                result: variable("result"),
+            }),
+            instruction!(Radar {
+                target1: RadarTarget::Any,
+                target2: RadarTarget::Any,
+                target3: RadarTarget::Any,
+                sort: RadarSort::Armor,
+                radar: variable("turret"),
+                order: number(1),
+                result: variable("result"),
+            }),
+        ];
+    }
+
+    #[test]
+    fn test_setprop() {
+        assert_ast![
+            "
+            setprop @health building 100
+            setprop @copper @this amount
+            ",
+            instruction!(SetProp {
+                prop: variable("@health"),
+                target: variable("building"),
+                value: number(100),
+            }),
+            instruction!(SetProp {
+                prop: variable("@copper"),
+                target: variable("@this"),
+                value: variable("amount"),
+            }),
+        ];
+    }
+
     #[test]
     fn test_draw() {
         assert_ast![
@@ -0,0 +1,675 @@
+use core::fmt::{self, Display, Formatter, Write};
+use std::{
+    format,
+    string::{String, ToString},
+    vec,
+};
+
+use super::{ast::*, grammar_util::parse_number};
+
+/// Converts a list of statements back into mlog code, with one statement per line.
+///
+/// Parsing the result produces the same statements (except for spans), as long as they could have been produced by the parser in the first place. Hand-written ASTs may not round-trip exactly if they contain values that mlog can't represent, such as strings containing `"` or arguments that the parser would treat as unused.
+pub fn print_ast(ast: &[Statement]) -> String {
+    let mut out = String::new();
+    for statement in ast {
+        // writing to a String can't fail
+        writeln!(out, "{statement}").unwrap();
+    }
+    out
+}
+
+impl Display for Statement {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Label(label, _) => write!(f, "{label}:"),
+            Self::Instruction(instruction, extra, _) => {
+                instruction.fmt(f)?;
+                for value in extra {
+                    write!(f, " {value}")?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+/// Values are written so that they parse back to the same value.
+///
+/// mlog strings can't contain `"`, so any double quotes are replaced with `'`. NaN has no literal syntax, so it's written as `null`. [`Value::None`] is also written as `null`, but [`Instruction`]s omit it when it's in a trailing argument.
+impl Display for Value {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Variable(name) => f.write_str(name),
+            Self::String(value) => {
+                write!(f, r#""{}""#, value.replace('\n', r"\n").replace('"', "'"))
+            }
+            Self::Number(value) => f.write_str(&format_number(*value)),
+            Self::None => f.write_str("null"),
+        }
+    }
+}
+
+enum Arg<'a> {
+    Keyword(&'static str),
+    Value(&'a Value),
+}
+
+impl Display for Arg<'_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Keyword(keyword) => f.write_str(keyword),
+            Self::Value(value) => value.fmt(f),
+        }
+    }
+}
+
+impl Display for Instruction {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        use Arg::{Keyword as K, Value as V};
+
+        let (name, args) = match self {
+            // input/output
+            Self::Read {
+                result,
+                target,
+                address,
+            } => ("read", vec![V(result), V(target), V(address)]),
+            Self::Write {
+                value,
+                target,
+                address,
+            } => ("write", vec![V(value), V(target), V(address)]),
+            Self::Draw {
+                op,
+                x,
+                y,
+                p1,
+                p2,
+                p3,
+                p4,
+            } => (
+                "draw",
+                vec![K(op.into()), V(x), V(y), V(p1), V(p2), V(p3), V(p4)],
+            ),
+            Self::Print { value } => ("print", vec![V(value)]),
+            Self::PrintChar { value } => ("printchar", vec![V(value)]),
+            Self::Format { value } => ("format", vec![V(value)]),
+
+            // block control
+            Self::DrawFlush { target } => ("drawflush", vec![V(target)]),
+            Self::PrintFlush { target } => ("printflush", vec![V(target)]),
+            Self::GetLink { result, index } => ("getlink", vec![V(result), V(index)]),
+            Self::Control {
+                control,
+                target,
+                p1,
+                p2,
+                p3,
+            } => (
+                "control",
+                vec![K(control.into()), V(target), V(p1), V(p2), V(p3)],
+            ),
+            Self::Sensor {
+                result,
+                target,
+                sensor,
+            } => ("sensor", vec![V(result), V(target), V(sensor)]),
+            Self::Radar {
+                target1,
+                target2,
+                target3,
+                sort,
+                radar,
+                order,
+                result,
+            } => (
+                "radar",
+                vec![
+                    K(target1.into()),
+                    K(target2.into()),
+                    K(target3.into()),
+                    K(sort.into()),
+                    V(radar),
+                    V(order),
+                    V(result),
+                ],
+            ),
+
+            // operations
+            Self::Set { to, from } => ("set", vec![V(to), V(from)]),
+            Self::Op { op, result, x, y } => ("op", vec![K(op.into()), V(result), V(x), V(y)]),
+            Self::Select {
+                result,
+                op,
+                x,
+                y,
+                if_true,
+                if_false,
+            } => (
+                "select",
+                vec![V(result), K(op.into()), V(x), V(y), V(if_true), V(if_false)],
+            ),
+            Self::Lookup {
+                content_type,
+                result,
+                id,
+            } => ("lookup", vec![K(content_type.into()), V(result), V(id)]),
+            Self::PackColor { result, r, g, b, a } => {
+                ("packcolor", vec![V(result), V(r), V(g), V(b), V(a)])
+            }
+            Self::UnpackColor { r, g, b, a, value } => {
+                ("unpackcolor", vec![V(r), V(g), V(b), V(a), V(value)])
+            }
+
+            // flow control
+            Self::Noop => ("noop", vec![]),
+            Self::Wait { value } => ("wait", vec![V(value)]),
+            Self::Stop => ("stop", vec![]),
+            Self::End => ("end", vec![]),
+            Self::Jump { target, op, x, y } => ("jump", vec![V(target), K(op.into()), V(x), V(y)]),
+
+            // privileged
+            Self::GetBlock {
+                layer,
+                result,
+                x,
+                y,
+            } => ("getblock", vec![K(layer.into()), V(result), V(x), V(y)]),
+            Self::SetRate { value } => ("setrate", vec![V(value)]),
+            Self::Message {
+                message_type,
+                duration,
+                success,
+            } => (
+                "message",
+                vec![K(message_type.into()), V(duration), V(success)],
+            ),
+            Self::LocalePrint { value } => ("localeprint", vec![V(value)]),
+            Self::Fetch {
+                fetch_type,
+                result,
+                team,
+                index,
+                extra,
+            } => (
+                "fetch",
+                vec![K(fetch_type.into()), V(result), V(team), V(index), V(extra)],
+            ),
+            Self::SetProp {
+                prop,
+                target,
+                value,
+            } => ("setprop", vec![V(prop), V(target), V(value)]),
+
+            // unknown
+            Self::Unknown(name) => (name.as_str(), vec![]),
+        };
+
+        f.write_str(name)?;
+
+        // the parser fills in omitted trailing arguments with Value::None
+        let len = args
+            .iter()
+            .rposition(|arg| !matches!(arg, Arg::Value(Value::None)))
+            .map_or(0, |i| i + 1);
+        for arg in &args[..len] {
+            write!(f, " {arg}")?;
+        }
+        Ok(())
+    }
+}
+
+/// Formats a number so that [`parse_number`] returns exactly the same value.
+///
+/// Mindustry's number parser isn't exact, so the shortest representation of a number doesn't always parse back to the same value. Instead, we try progressively longer representations until we find one that works.
+///
+/// Packed colors and numbers parsed from literals between about `1e-100` and `1e18` in magnitude can be written exactly. Some numbers can't be produced by Mindustry's parser at all, so they're written as the closest literal instead.
+fn format_number(n: f64) -> String {
+    if n.is_nan() {
+        return "null".into();
+    }
+
+    // packed colors are tiny subnormal numbers, which can't be written as decimals
+    let bits = n.to_bits();
+    if bits != 0 && bits <= u32::MAX as u64 {
+        return format!("%{bits:08x}");
+    }
+
+    // the sign is applied separately from the rest of the number, and negating a float is exact
+    let sign = if n.is_sign_negative() { "-" } else { "" };
+    let n = n.abs();
+    if n.is_infinite() {
+        return format!("{sign}1e999");
+    }
+
+    let parses_exactly =
+        |s: &str| parse_number(s).is_ok_and(|value| value.to_bits() == n.to_bits());
+
+    for precision in 0..=17 {
+        let (digits, exp) = decimal_digits(&format!("{n:.precision$e}"));
+        // n == digits * 10^shift
+        let shift = exp - (digits.len() as i32 - 1);
+        let scientific = format!("{digits}e{shift}");
+
+        // prefer plain integers and decimals where they're reasonably short
+        let plain = if shift >= 0 {
+            (digits.len() as i32 + shift <= 19)
+                .then(|| format!("{digits}{}", "0".repeat(shift as usize)))
+        } else if exp >= 0 {
+            let (whole, frac) = digits.split_at(exp as usize + 1);
+            Some(format!("{whole}.{frac}"))
+        } else if exp >= -7 {
+            Some(format!("0.{}{digits}", "0".repeat((-exp - 1) as usize)))
+        } else {
+            None
+        };
+
+        for candidate in plain.into_iter().chain([scientific]) {
+            if parses_exactly(&candidate) {
+                return format!("{sign}{candidate}");
+            }
+        }
+    }
+
+    // if none of the nice representations work, search for a longer mantissa that happens to round correctly
+    let exp = n.log10().floor() as i32;
+    for shift in (exp - 18)..=(exp - 10) {
+        let ideal = (n / 10f64.powi(shift)).round();
+        let (mut below, mut above) = (ideal, ideal);
+        let mut mantissas = vec![ideal];
+        for _ in 0..64 {
+            below = below.next_down();
+            above = above.next_up();
+            mantissas.extend([below.round(), above.round()]);
+        }
+
+        for mantissa in mantissas {
+            // the mantissa must fit in an i64
+            if !(0. ..=i64::MAX as f64).contains(&mantissa) {
+                continue;
+            }
+            let digits = format!("{mantissa:.0}");
+            let mut candidates = vec![format!("{digits}e{shift}")];
+            // decimals are divided by a power of 10 instead of multiplied, so they round differently
+            if shift < 0 && -shift as usize >= digits.len() {
+                let zeros = "0".repeat(-shift as usize - digits.len());
+                candidates.push(format!("0.{zeros}{digits}"));
+            }
+            for candidate in candidates {
+                if parses_exactly(&candidate) {
+                    return format!("{sign}{candidate}");
+                }
+            }
+        }
+    }
+
+    let (digits, exp) = decimal_digits(&format!("{n:e}"));
+    format!("{sign}{digits}e{}", exp - (digits.len() as i32 - 1))
+}
+
+/// Returns the significant digits (without trailing zeros) and decimal exponent of a positive number formatted with `{:e}`.
+fn decimal_digits(formatted: &str) -> (String, i32) {
+    let (mantissa, exp) = formatted.split_once('e').unwrap();
+    let digits = mantissa.replace('.', "");
+    let digits = match digits.trim_end_matches('0') {
+        "" => "0".to_string(),
+        digits => digits.to_string(),
+    };
+    (digits, exp.parse().unwrap())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{borrow::ToOwned, vec::Vec};
+
+    use pretty_assertions::assert_eq;
+    use proptest::prelude::*;
+
+    use super::*;
+    use crate::{
+        parser::{parse, tests::strip_spans},
+        types::colors::to_double_bits,
+    };
+
+    fn reprint(code: &str) -> String {
+        print_ast(&parse(code).unwrap())
+    }
+
+    #[test]
+    fn test_print_ast() {
+        assert_eq!(
+            reprint(
+                r#"
+                start:
+                    set x 0x10 # comment
+                    op   add x x 1.5; op not y x
+                    draw clear 0 0 0
+                    draw color 255 255 255 255 extra
+                    control enabled switch1 0
+                    radar enemy any flying maxHealth turret1 1 result
+                    lookup mech_UNUSED result 0
+                    select result always x y "a"
+                    jump start always
+                    jump 0 lessThan x 10
+                    print "foo\nbar"
+                    unknown a b
+                "#
+            ),
+            r#"start:
+set x 16
+op add x x 1.5
+op not y x
+draw clear 0 0 0
+draw color 255 255 255 255 extra
+control enabled switch1 0
+radar enemy any flying maxHealth turret1 1 result
+lookup mech_UNUSED result 0
+select result always x y "a"
+jump start always
+jump 0 lessThan x 10
+print "foo\nbar"
+unknown a b
+"#
+        );
+    }
+
+    #[test]
+    fn test_print_hand_written() {
+        let statement = |instruction| Statement::Instruction(instruction, vec![], Span::default());
+        assert_eq!(
+            print_ast(&[
+                statement(Instruction::Set {
+                    to: Value::Variable("x".into()),
+                    from: Value::String(r#"say "hi""#.into()),
+                }),
+                statement(Instruction::Draw {
+                    op: DrawOp::LineRect,
+                    x: Value::Number(f64::NAN),
+                    y: Value::None,
+                    p1: Value::Number(f64::NEG_INFINITY),
+                    p2: Value::None,
+                    p3: Value::None,
+                    p4: Value::None,
+                }),
+            ]),
+            "set x \"say 'hi'\"\ndraw lineRect null null -1e999\n"
+        );
+    }
+
+    #[test]
+    fn test_format_number() {
+        for (n, want) in [
+            (0., "0"),
+            (-0., "-0"),
+            (1., "1"),
+            (-12345., "-12345"),
+            (1.5, "1.5"),
+            (0.1, "0.1"),
+            (-0.001, "-0.001"),
+            (123456.789, "123456.789"),
+            (1e20, "1e20"),
+            (1e-10, "1e-10"),
+            (f64::INFINITY, "1e999"),
+            (to_double_bits(0x12, 0xab, 0, 0xff), "%12ab00ff"),
+        ] {
+            assert_eq!(format_number(n), want, "{n}");
+        }
+    }
+
+    fn assert_number_roundtrip(n: f64) {
+        let formatted = format_number(n);
+        assert_eq!(
+            parse_number(&formatted).unwrap().to_bits(),
+            n.to_bits(),
+            "{n} was formatted as {formatted}",
+        );
+    }
+
+    #[test]
+    fn test_format_number_hard() {
+        // the shortest representations of these don't parse back to the same value
+        for n in [
+            0.30000000000000004,
+            0.9839363681050729,
+            0.49366146075379036,
+            0.021077126037795102,
+            f64::MAX / 3.,
+            i64::MAX as f64,
+        ] {
+            assert_number_roundtrip(n);
+            assert_number_roundtrip(-n);
+        }
+    }
+
+    /// Number literals in the range that [`format_number`] can write exactly.
+    fn number_literal() -> impl Strategy<Value = String> {
+        let in_range = |n: &f64| *n == 0. || (1e-100..1e18).contains(&n.abs());
+        prop_oneof![
+            any::<f64>()
+                .prop_filter("outside exact range", in_range)
+                .prop_map(|n| n.to_string()),
+            (
+                -999_999_999_999_999_999..=999_999_999_999_999_999i64,
+                -82..=0
+            )
+                .prop_map(|(m, e)| format!("{m}e{e}")),
+            (-1e6..1e6f64).prop_map(|n| n.to_string()),
+            (0.0..1.0f64).prop_map(|n| n.to_string()),
+        ]
+    }
+
+    const VARIABLES: &[&str] = &["x", "foo_bar", "@unit", "null", "true", "read", "always"];
+
+    fn value() -> impl Strategy<Value = String> {
+        prop_oneof![
+            prop::sample::select(VARIABLES).prop_map(|s| s.to_owned()),
+            "[a-z_@][a-zA-Z0-9_.]{0,8}",
+            r#""[a-zA-Z0-9 _\[\]:]{0,8}(\\n)?""#,
+            any::<i64>().prop_map(|n| n.to_string()),
+            number_literal(),
+            (-1e6..1e6f64).prop_map(|n| format!("{n:e}").replace('.', "")),
+            "[+-]?0x[0-9a-fA-F]{1,8}",
+            "[+-]?0b[01]{1,16}",
+            "%[0-9a-f]{6}([0-9a-f]{2})?",
+            prop::sample::select(&["%[red]", "%[accent]", "%[notacolor]"][..])
+                .prop_map(|s| s.to_owned()),
+        ]
+    }
+
+    /// Every instruction form accepted by the grammar, and the number of values it takes.
+    fn forms() -> Vec<(String, usize)> {
+        let mut forms: Vec<(String, usize)> = [
+            ("read", 3),
+            ("write", 3),
+            ("print", 1),
+            ("printchar", 1),
+            ("format", 1),
+            ("drawflush", 1),
+            ("printflush", 1),
+            ("getlink", 2),
+            ("sensor", 3),
+            ("set", 2),
+            ("packcolor", 5),
+            ("unpackcolor", 5),
+            ("noop", 0),
+            ("wait", 1),
+            ("stop", 0),
+            ("end", 0),
+            ("setrate", 1),
+            ("localeprint", 1),
+            ("setprop", 3),
+            ("foo", 0),
+            ("ubind", 1),
+        ]
+        .into_iter()
+        .map(|(name, n)| (name.to_owned(), n))
+        .collect();
+
+        let mut add = |prefix: &str, keywords: &[&str], n: usize| {
+            forms.extend(keywords.iter().map(|k| (format!("{prefix} {k}"), n)));
+        };
+
+        add("draw", &["reset"], 0);
+        add("draw", &["col", "stroke", "rotate"], 1);
+        add("draw", &["translate", "scale"], 2);
+        add("draw", &["clear", "print"], 3);
+        add("draw", &["color", "line", "rect", "lineRect"], 4);
+        add("draw", &["poly", "linePoly", "image"], 5);
+        add("draw", &["triangle"], 6);
+
+        add("control", &["enabled", "config", "color"], 2);
+        add("control", &["shootp"], 3);
+        add("control", &["shoot"], 4);
+
+        add(
+            "radar",
+            &[
+                "any enemy ally distance",
+                "player attacker flying health",
+                "boss ground any shield",
+                "enemy enemy enemy armor",
+                "ally any boss maxHealth",
+            ],
+            3,
+        );
+
+        let unary = [
+            "not", "abs", "sign", "log", "log10", "floor", "ceil", "round", "sqrt", "rand", "sin",
+            "cos", "tan", "asin", "acos", "atan",
+        ];
+        let binary = [
+            "add",
+            "sub",
+            "mul",
+            "div",
+            "idiv",
+            "mod",
+            "emod",
+            "pow",
+            "equal",
+            "notEqual",
+            "lessThan",
+            "lessThanEq",
+            "greaterThan",
+            "greaterThanEq",
+            "strictEqual",
+            "land",
+            "shl",
+            "shr",
+            "ushr",
+            "or",
+            "and",
+            "xor",
+            "max",
+            "min",
+            "angle",
+            "angleDiff",
+            "len",
+            "noise",
+            "logn",
+        ];
+        add("op", &unary, 2);
+        add("op", &binary, 3);
+
+        let conditions = [
+            "equal",
+            "notEqual",
+            "lessThan",
+            "lessThanEq",
+            "greaterThan",
+            "greaterThanEq",
+            "strictEqual",
+        ];
+        add("select result", &conditions, 4);
+        add("select result", &["always"], 3);
+        add("jump label", &conditions, 2);
+        add("jump 0", &conditions, 2);
+        add("jump label", &["always"], 0);
+        add("jump 0", &["always"], 0);
+
+        add(
+            "lookup",
+            &[
+                "item",
+                "block",
+                "mech_UNUSED",
+                "bullet",
+                "liquid",
+                "status",
+                "unit",
+                "weather",
+                "effect_UNUSED",
+                "sector",
+                "loadout_UNUSED",
+                "typeid_UNUSED",
+                "error",
+                "planet",
+                "ammo_UNUSED",
+                "team",
+                "unitCommand",
+                "unitStance",
+            ],
+            2,
+        );
+        add("getblock", &["floor", "ore", "block", "building"], 3);
+        add("message", &["notify", "announce", "toast", "mission"], 2);
+        add(
+            "fetch",
+            &[
+                "unit",
+                "unitCount",
+                "player",
+                "playerCount",
+                "core",
+                "coreCount",
+                "build",
+                "buildCount",
+            ],
+            4,
+        );
+
+        forms
+    }
+
+    fn statement() -> impl Strategy<Value = String> {
+        prop_oneof![
+            1 => "[a-z_][a-zA-Z0-9_]{0,8}".prop_map(|label| format!("{label}:")),
+            9 => prop::sample::select(forms()).prop_flat_map(|(prefix, n)| {
+                // add up to 2 extra unused arguments
+                prop::collection::vec(value(), n..=n + 2)
+                    .prop_map(move |values| format!("{prefix} {}", values.join(" ")))
+            }),
+        ]
+    }
+
+    proptest! {
+        #[test]
+        fn test_roundtrip(statements in prop::collection::vec(statement(), 0..20)) {
+            let code = format!("label:\n{}", statements.join("\n"));
+            let ast = strip_spans(parse(&code).unwrap());
+            let printed = print_ast(&ast);
+            prop_assert_eq!(strip_spans(parse(&printed).unwrap()), ast, "{}", printed);
+        }
+
+        #[test]
+        fn test_roundtrip_number(
+            n in number_literal().prop_filter_map("not a number", |s| parse_number(&s).ok())
+        ) {
+            let code = format!("set x {}", format_number(n));
+            prop_assert_eq!(
+                strip_spans(parse(&code).unwrap()),
+                vec![Statement::Instruction(
+                    Instruction::Set {
+                        to: Value::Variable("x".into()),
+                        from: Value::Number(n),
+                    },
+                    vec![],
+                    Span::default(),
+                )],
+                "{}",
+                code,
+            );
+        }
+    }
+}
@@ -0,0 +1,654 @@
+//! Renders schematics and VMs to images, for reviewing designs without opening Mindustry.
+
+use std::{fmt::Write as _, io::Write, prelude::rust_2024::*, vec::Vec};
+
+use thiserror::Error;
+
+use crate::{
+    types::{Point2, ProcessorConfig, Schematic, is_processor},
+    utils::RapidHashMap,
+    vm::{BuildingData, LogicVM},
+};
+
+/// Size of one tile in SVG user units.
+const SVG_TILE_SIZE: i32 = 32;
+
+/// The largest PNG that [`Layout::write_png`] will allocate, in pixels.
+const MAX_PNG_PIXELS: u64 = 8192 * 8192;
+
+const BACKGROUND_COLOR: [u8; 3] = [0x1e, 0x1e, 0x24];
+const LINK_COLOR: [u8; 3] = [0xff, 0xd3, 0x7f];
+
+#[derive(Error, Debug)]
+pub enum RenderError {
+    #[error("image is too large to render ({width}x{height} pixels)")]
+    TooLarge { width: u64, height: u64 },
+
+    #[error("failed to encode PNG")]
+    Png(#[from] png::EncodingError),
+}
+
+/// The blocks and processor links of a schematic or VM, ready to be drawn.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Layout {
+    pub blocks: Vec<LayoutBlock>,
+    pub links: Vec<LayoutLink>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LayoutBlock {
+    pub name: String,
+    /// The bottom left tile covered by this block.
+    pub position: Point2,
+    pub size: i32,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LayoutLink {
+    /// The index of the linking processor in [`Layout::blocks`].
+    pub processor: usize,
+    /// The linked tile. Links are drawn to the center of the block covering this tile, if any, and links to tiles outside of [`Layout::bounds`] are not drawn.
+    pub target: Point2,
+}
+
+impl LayoutBlock {
+    /// Returns the center of this block, in tiles.
+    fn center(&self) -> (f64, f64) {
+        let half = self.size as f64 / 2.;
+        (self.position.x as f64 + half, self.position.y as f64 + half)
+    }
+}
+
+impl Layout {
+    /// Creates a layout from a schematic.
+    ///
+    /// Block sizes are looked up by name, and unknown blocks are drawn as 1x1. Processors with invalid configs are drawn without links.
+    pub fn from_schematic(schematic: &Schematic) -> Self {
+        let mut layout = Self::default();
+        for tile in schematic.tiles() {
+            let processor = layout.blocks.len();
+            layout.blocks.push(LayoutBlock {
+                name: tile.block.clone(),
+                position: tile.corner(),
+                size: tile.size(),
+            });
+
+            // processor links are relative to the processor's position, which is the center of the block
+            if is_processor(&tile.block)
+                && let Ok(config) = ProcessorConfig::parse(&tile.config)
+            {
+                let origin = Point2::from(tile.position);
+                layout
+                    .links
+                    .extend(config.links.iter().map(|link| LayoutLink {
+                        processor,
+                        target: Point2::new(origin.x + link.x as i32, origin.y + link.y as i32),
+                    }));
+            }
+        }
+        layout
+    }
+
+    /// Creates a layout from the buildings in a VM.
+    pub fn from_vm(vm: &LogicVM) -> Self {
+        let mut layout = Self::default();
+        for (processor, building) in vm.buildings().iter().enumerate() {
+            layout.blocks.push(LayoutBlock {
+                name: building.block.name.to_string(),
+                position: building.position.into(),
+                size: building.block.size.into(),
+            });
+
+            if let BuildingData::Processor(p) = &*building.data.borrow() {
+                layout
+                    .links
+                    .extend(p.state.links().iter().map(|link| LayoutLink {
+                        processor,
+                        target: link.building.position.into(),
+                    }));
+            }
+        }
+        layout
+    }
+
+    /// Returns the minimum and maximum tiles (inclusive) covered by the blocks in this layout.
+    ///
+    /// Link targets are not included, since processors can link to arbitrary tiles far away from any block. An empty layout is treated as a single tile at the origin.
+    pub fn bounds(&self) -> (Point2, Point2) {
+        let points = self.blocks.iter().flat_map(|block| {
+            let max = Point2::new(
+                block.position.x + block.size - 1,
+                block.position.y + block.size - 1,
+            );
+            [block.position, max]
+        });
+
+        let mut bounds: Option<(Point2, Point2)> = None;
+        for p in points {
+            bounds = Some(match bounds {
+                Some((min, max)) => (
+                    Point2::new(min.x.min(p.x), min.y.min(p.y)),
+                    Point2::new(max.x.max(p.x), max.y.max(p.y)),
+                ),
+                None => (p, p),
+            });
+        }
+        bounds.unwrap_or((Point2::new(0, 0), Point2::new(0, 0)))
+    }
+
+    /// Renders this layout as an SVG image.
+    ///
+    /// Each block is drawn as a rectangle labelled with its name, and each processor link is drawn as a line from the processor to the linked block.
+    pub fn to_svg(&self) -> Result<String, RenderError> {
+        let canvas = Canvas::new(self, SVG_TILE_SIZE)?;
+        let mut out = String::new();
+
+        // writing to a String can't fail
+        writeln!(
+            out,
+            r#"<svg xmlns="http://www.w3.org/2000/svg" width="{w}" height="{h}" viewBox="0 0 {w} {h}">"#,
+            w = canvas.width,
+            h = canvas.height,
+        )
+        .unwrap();
+        writeln!(
+            out,
+            r#"<rect width="100%" height="100%" fill="{}"/>"#,
+            hex(BACKGROUND_COLOR)
+        )
+        .unwrap();
+
+        for block in &self.blocks {
+            let (x, y) = canvas.top_left(block);
+            let size = block.size * SVG_TILE_SIZE;
+            let (cx, cy) = canvas.to_pixels(block.center());
+            let name = escape(&block.name);
+            // shrink long names to fit inside the block
+            let font_size =
+                (size as f64 / 3.).min(size as f64 * 1.6 / block.name.len().max(1) as f64);
+
+            writeln!(out, "<g>").unwrap();
+            writeln!(
+                out,
+                "<title>{name} at ({}, {})</title>",
+                block.position.x, block.position.y
+            )
+            .unwrap();
+            writeln!(
+                out,
+                r#"<rect x="{x}" y="{y}" width="{size}" height="{size}" fill="{}" stroke="{}" stroke-width="2"/>"#,
+                hex(block_color(&block.name)),
+                hex(darken(block_color(&block.name))),
+            )
+            .unwrap();
+            writeln!(
+                out,
+                r#"<text x="{cx}" y="{cy}" font-family="monospace" font-size="{font_size:.1}" fill="white" text-anchor="middle" dominant-baseline="middle">{name}</text>"#,
+            )
+            .unwrap();
+            writeln!(out, "</g>").unwrap();
+        }
+
+        for link in &self.links {
+            let Some(((x1, y1), (x2, y2))) = canvas.link_pixels(link) else {
+                continue;
+            };
+            writeln!(
+                out,
+                r#"<line x1="{x1}" y1="{y1}" x2="{x2}" y2="{y2}" stroke="{}" stroke-width="2" stroke-opacity="0.8"/>"#,
+                hex(LINK_COLOR),
+            )
+            .unwrap();
+            writeln!(
+                out,
+                r#"<circle cx="{x2}" cy="{y2}" r="4" fill="{}"/>"#,
+                hex(LINK_COLOR)
+            )
+            .unwrap();
+        }
+
+        out.push_str("</svg>\n");
+        Ok(out)
+    }
+
+    /// Renders this layout as a PNG image with `tile_size` pixels per tile.
+    ///
+    /// This draws the same shapes as [`Layout::to_svg`], but without block names, since this crate does not include a font for rasterizing text. Blocks are instead colored by name, so blocks of the same type have the same color.
+    ///
+    /// Returns an error instead of allocating the image if it would be larger than 8192x8192 pixels.
+    pub fn write_png<W: Write>(&self, writer: W, tile_size: u32) -> Result<(), RenderError> {
+        let tile_size = i32::try_from(tile_size.max(1)).unwrap_or(i32::MAX);
+        let canvas = Canvas::new(self, tile_size)?;
+        let (width, height) = (canvas.width as u64, canvas.height as u64);
+        if width * height > MAX_PNG_PIXELS {
+            return Err(RenderError::TooLarge { width, height });
+        }
+        let mut image = Image::new(canvas.width, canvas.height);
+
+        for block in &self.blocks {
+            let (x, y) = canvas.top_left(block);
+            let size = block.size * tile_size;
+            let color = block_color(&block.name);
+            image.fill_rect(x, y, size, size, darken(color));
+            if size > 2 {
+                image.fill_rect(x + 1, y + 1, size - 2, size - 2, color);
+            }
+        }
+
+        for link in &self.links {
+            let Some(((x1, y1), (x2, y2))) = canvas.link_pixels(link) else {
+                continue;
+            };
+            let (x1, y1, x2, y2) = (x1 as i32, y1 as i32, x2 as i32, y2 as i32);
+            image.draw_line(x1, y1, x2, y2, LINK_COLOR);
+            image.fill_rect(x2 - 1, y2 - 1, 3, 3, LINK_COLOR);
+        }
+
+        let mut encoder = png::Encoder::new(writer, image.width as u32, image.height as u32);
+        encoder.set_color(png::ColorType::Rgb);
+        encoder.set_depth(png::BitDepth::Eight);
+        encoder.write_header()?.write_image_data(&image.data)?;
+        Ok(())
+    }
+}
+
+/// Converts tile coordinates to pixel coordinates, flipping the y axis so that the image matches the in-game orientation.
+struct Canvas<'a> {
+    layout: &'a Layout,
+    tiles: RapidHashMap<Point2, usize>,
+    min: Point2,
+    max: Point2,
+    tile_size: i32,
+    width: i32,
+    height: i32,
+}
+
+impl<'a> Canvas<'a> {
+    fn new(layout: &'a Layout, tile_size: i32) -> Result<Self, RenderError> {
+        let (min, max) = layout.bounds();
+        let pixels = |tiles: i32| tiles.checked_mul(tile_size);
+        let (Some(width), Some(height)) = (pixels(max.x - min.x + 1), pixels(max.y - min.y + 1))
+        else {
+            return Err(RenderError::TooLarge {
+                width: (max.x - min.x + 1) as u64 * tile_size as u64,
+                height: (max.y - min.y + 1) as u64 * tile_size as u64,
+            });
+        };
+
+        let mut tiles = RapidHashMap::default();
+        for (i, block) in layout.blocks.iter().enumerate() {
+            for x in 0..block.size {
+                for y in 0..block.size {
+                    tiles.insert(Point2::new(block.position.x + x, block.position.y + y), i);
+                }
+            }
+        }
+        Ok(Self {
+            layout,
+            tiles,
+            min,
+            max,
+            tile_size,
+            width,
+            height,
+        })
+    }
+
+    fn to_pixels(&self, (x, y): (f64, f64)) -> (f64, f64) {
+        let size = self.tile_size as f64;
+        (
+            (x - self.min.x as f64) * size,
+            (self.max.y as f64 + 1. - y) * size,
+        )
+    }
+
+    fn top_left(&self, block: &LayoutBlock) -> (i32, i32) {
+        (
+            (block.position.x - self.min.x) * self.tile_size,
+            (self.max.y + 1 - block.position.y - block.size) * self.tile_size,
+        )
+    }
+
+    /// Returns the start and end of a link, or `None` if the link target is outside of the canvas.
+    fn link_pixels(&self, link: &LayoutLink) -> Option<((f64, f64), (f64, f64))> {
+        let from = self.layout.blocks[link.processor].center();
+        let to = match self.tiles.get(&link.target) {
+            Some(&i) => self.layout.blocks[i].center(),
+            None if (self.min.x..=self.max.x).contains(&link.target.x)
+                && (self.min.y..=self.max.y).contains(&link.target.y) =>
+            {
+                (link.target.x as f64 + 0.5, link.target.y as f64 + 0.5)
+            }
+            None => return None,
+        };
+        Some((self.to_pixels(from), self.to_pixels(to)))
+    }
+}
+
+struct Image {
+    width: i32,
+    height: i32,
+    data: Vec<u8>,
+}
+
+impl Image {
+    fn new(width: i32, height: i32) -> Self {
+        Self {
+            width,
+            height,
+            data: BACKGROUND_COLOR.repeat((width * height) as usize),
+        }
+    }
+
+    fn set_pixel(&mut self, x: i32, y: i32, color: [u8; 3]) {
+        if (0..self.width).contains(&x) && (0..self.height).contains(&y) {
+            let i = (y * self.width + x) as usize * 3;
+            self.data[i..i + 3].copy_from_slice(&color);
+        }
+    }
+
+    fn fill_rect(&mut self, x: i32, y: i32, width: i32, height: i32, color: [u8; 3]) {
+        for py in y..y + height {
+            for px in x..x + width {
+                self.set_pixel(px, py, color);
+            }
+        }
+    }
+
+    /// Draws a line using Bresenham's algorithm.
+    fn draw_line(&mut self, mut x1: i32, mut y1: i32, x2: i32, y2: i32, color: [u8; 3]) {
+        let dx = (x2 - x1).abs();
+        let dy = -(y2 - y1).abs();
+        let sx = if x1 < x2 { 1 } else { -1 };
+        let sy = if y1 < y2 { 1 } else { -1 };
+        let mut err = dx + dy;
+        loop {
+            self.set_pixel(x1, y1, color);
+            if x1 == x2 && y1 == y2 {
+                break;
+            }
+            let e2 = 2 * err;
+            if e2 >= dy {
+                err += dy;
+                x1 += sx;
+            }
+            if e2 <= dx {
+                err += dx;
+                y1 += sy;
+            }
+        }
+    }
+}
+
+/// Picks a stable color for a block name, so that blocks of the same type are easy to spot.
+fn block_color(name: &str) -> [u8; 3] {
+    // FNV-1a, since the standard library's hashers aren't guaranteed to be stable between releases
+    let hash = name.bytes().fold(0x811c9dc5u32, |hash, b| {
+        (hash ^ b as u32).wrapping_mul(0x01000193)
+    });
+    hsl_to_rgb((hash % 360) as f64, 0.45, 0.4)
+}
+
+fn darken([r, g, b]: [u8; 3]) -> [u8; 3] {
+    [r / 2, g / 2, b / 2]
+}
+
+fn hsl_to_rgb(h: f64, s: f64, l: f64) -> [u8; 3] {
+    let c = (1. - (2. * l - 1.).abs()) * s;
+    let h = h / 60.;
+    let x = c * (1. - (h % 2. - 1.).abs());
+    let (r, g, b) = match h as u32 {
+        0 => (c, x, 0.),
+        1 => (x, c, 0.),
+        2 => (0., c, x),
+        3 => (0., x, c),
+        4 => (x, 0., c),
+        _ => (c, 0., x),
+    };
+    let m = l - c / 2.;
+    [r, g, b].map(|v| ((v + m) * 255.).round() as u8)
+}
+
+fn hex([r, g, b]: [u8; 3]) -> String {
+    format!("#{r:02x}{g:02x}{b:02x}")
+}
+
+fn escape(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{string::ToString, vec};
+
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+    use crate::types::{Object, PackedPoint2, ProcessorLinkConfig, SchematicTile};
+
+    fn tile(block: &str, x: i16, y: i16, config: Object) -> SchematicTile {
+        SchematicTile {
+            block: block.to_string(),
+            position: PackedPoint2 { x, y },
+            config,
+            rotation: 0,
+        }
+    }
+
+    fn linked_schematic() -> Schematic {
+        Schematic::new()
+            .add_tile(tile(
+                "micro-processor",
+                0,
+                0,
+                ProcessorConfig {
+                    code: "end".to_string(),
+                    links: vec![
+                        ProcessorLinkConfig::unnamed(2, 0),
+                        ProcessorLinkConfig::unnamed(1, 0),
+                    ],
+                }
+                .to_object()
+                .unwrap(),
+            ))
+            .add_tile(tile("switch", 1, 0, Object::Null))
+            .add_tile(tile("memory-bank", 2, 0, Object::Null))
+            .to_owned()
+    }
+
+    fn linked_layout() -> Layout {
+        let block = |name: &str, x, y, size| LayoutBlock {
+            name: name.to_string(),
+            position: Point2::new(x, y),
+            size,
+        };
+        Layout {
+            blocks: vec![
+                block("micro-processor", 0, 0, 1),
+                block("switch", 1, 0, 1),
+                block("memory-bank", 2, 0, 2),
+            ],
+            links: vec![
+                LayoutLink {
+                    processor: 0,
+                    target: Point2::new(2, 0),
+                },
+                LayoutLink {
+                    processor: 0,
+                    target: Point2::new(1, 0),
+                },
+            ],
+        }
+    }
+
+    #[test]
+    fn test_from_schematic() {
+        let layout = Layout::from_schematic(&linked_schematic());
+        assert_eq!(layout, linked_layout());
+        assert_eq!(layout.bounds(), (Point2::new(0, 0), Point2::new(3, 1)));
+    }
+
+    #[test]
+    fn test_from_vm() {
+        let vm = LogicVM::from_schematic(&linked_schematic()).unwrap();
+        let mut layout = Layout::from_vm(&vm);
+        // the VM may store buildings in a different order than the schematic
+        let order: Vec<_> = layout.blocks.iter().map(|b| b.name.clone()).collect();
+        let expected = linked_layout();
+        let processor = order.iter().position(|n| n == "micro-processor").unwrap();
+        layout.blocks.sort_by_key(|b| b.position.x);
+        for link in &mut layout.links {
+            assert_eq!(link.processor, processor);
+            link.processor = 0;
+        }
+        assert_eq!(layout, expected);
+    }
+
+    #[test]
+    fn test_large_blocks() {
+        // schematic positions are the centers of the blocks
+        let schematic = Schematic::new()
+            .add_tile(tile(
+                "hyper-processor",
+                1,
+                1,
+                ProcessorConfig {
+                    code: "end".to_string(),
+                    links: vec![ProcessorLinkConfig::unnamed(5, 1)],
+                }
+                .to_object()
+                .unwrap(),
+            ))
+            .add_tile(tile("large-logic-display", 6, 2, Object::Null))
+            .to_owned();
+
+        let layout = Layout::from_schematic(&schematic);
+        assert_eq!(layout, Layout {
+            blocks: vec![
+                LayoutBlock {
+                    name: "hyper-processor".to_string(),
+                    position: Point2::new(0, 0),
+                    size: 3,
+                },
+                LayoutBlock {
+                    name: "large-logic-display".to_string(),
+                    position: Point2::new(4, 0),
+                    size: 6,
+                },
+            ],
+            links: vec![LayoutLink {
+                processor: 0,
+                target: Point2::new(6, 2),
+            }],
+        });
+        assert_eq!(layout.bounds(), (Point2::new(0, 0), Point2::new(9, 5)));
+
+        let vm = LogicVM::from_schematic(&schematic).unwrap();
+        let mut from_vm = Layout::from_vm(&vm);
+        from_vm.blocks.sort_by_key(|b| b.position.x);
+        assert_eq!(from_vm.blocks, layout.blocks);
+        assert_eq!(from_vm.links.len(), 1);
+        assert_eq!(from_vm.links[0].target, Point2::new(4, 0));
+    }
+
+    #[test]
+    fn test_far_links() {
+        let mut layout = linked_layout();
+        layout.links.push(LayoutLink {
+            processor: 0,
+            target: Point2::new(i16::MAX as i32, i16::MIN as i32),
+        });
+        assert_eq!(layout.bounds(), (Point2::new(0, 0), Point2::new(3, 1)));
+
+        // links outside of the image are skipped
+        let svg = layout.to_svg().unwrap();
+        assert_eq!(svg.matches("<line ").count(), 2);
+
+        let mut buf = Vec::new();
+        layout.write_png(&mut buf, 4).unwrap();
+        let reader = png::Decoder::new(buf.as_slice()).read_info().unwrap();
+        assert_eq!((reader.info().width, reader.info().height), (16, 8));
+    }
+
+    #[test]
+    fn test_too_large() {
+        let mut layout = linked_layout();
+        assert!(matches!(
+            layout.write_png(&mut Vec::new(), u32::MAX),
+            Err(RenderError::TooLarge { .. })
+        ));
+
+        layout.blocks.push(LayoutBlock {
+            name: "switch".to_string(),
+            position: Point2::new(i16::MAX as i32, i16::MAX as i32),
+            size: 1,
+        });
+        assert!(matches!(
+            layout.write_png(&mut Vec::new(), 1),
+            Err(RenderError::TooLarge {
+                width: 32768,
+                height: 32768,
+            })
+        ));
+        layout.to_svg().unwrap();
+    }
+
+    #[test]
+    fn test_to_svg() {
+        let svg = linked_layout().to_svg().unwrap();
+        assert!(
+            svg.starts_with(r#"<svg xmlns="http://www.w3.org/2000/svg" width="128" height="64" "#)
+        );
+        assert!(svg.ends_with("</svg>\n"));
+        for name in ["micro-processor", "switch", "memory-bank"] {
+            assert!(svg.contains(&format!(">{name}</text>")), "{name}");
+        }
+        // micro-processor center to memory-bank center, with the y axis flipped
+        assert!(svg.contains(r#"<line x1="16" y1="48" x2="96" y2="32" "#));
+        assert!(svg.contains(r#"<line x1="16" y1="48" x2="48" y2="48" "#));
+    }
+
+    #[test]
+    fn test_to_svg_escape() {
+        let layout = Layout {
+            blocks: vec![LayoutBlock {
+                name: "<a&b>".to_string(),
+                position: Point2::new(0, 0),
+                size: 1,
+            }],
+            links: vec![],
+        };
+        assert!(layout.to_svg().unwrap().contains(">&lt;a&amp;b&gt;</text>"));
+    }
+
+    #[test]
+    fn test_write_png() {
+        let mut buf = Vec::new();
+        linked_layout().write_png(&mut buf, 4).unwrap();
+
+        let mut reader = png::Decoder::new(buf.as_slice()).read_info().unwrap();
+        let mut data = vec![0; reader.output_buffer_size()];
+        let info = reader.next_frame(&mut data).unwrap();
+        assert_eq!((info.width, info.height), (16, 8));
+
+        let pixel = |x: usize, y: usize| {
+            let i = (y * info.width as usize + x) * 3;
+            [data[i], data[i + 1], data[i + 2]]
+        };
+        assert_eq!(pixel(1, 1), BACKGROUND_COLOR);
+        assert_eq!(pixel(1, 5), block_color("micro-processor"));
+        assert_eq!(pixel(0, 7), darken(block_color("micro-processor")));
+        assert_eq!(pixel(12, 4), LINK_COLOR);
+    }
+
+    #[test]
+    fn test_empty() {
+        let layout = Layout::default();
+        assert_eq!(layout.bounds(), (Point2::new(0, 0), Point2::new(0, 0)));
+        assert!(layout.to_svg().unwrap().contains(r#"width="32" height="32""#));
+        layout.write_png(&mut Vec::new(), 8).unwrap();
+    }
+}
@@ -2,8 +2,12 @@ use core::{fmt::Display, ops::Deref};
 
 use itertools::Itertools;
 use serde::Deserialize;
+use strum::EnumString;
 use widestring::U16Str;
 
+use super::{ContentID, ContentType};
+use crate::utils::RapidHashMap;
+
 macro_rules! impl_content {
     ($typ:ident) => {
         impl PartialEq for $typ {
@@ -27,20 +31,20 @@ pub struct Block {
     pub range: f64,
     pub item_capacity: i32,
     pub liquid_capacity: f32,
+    pub has_items: bool,
+    pub has_liquids: bool,
+    pub has_power: bool,
+    pub consumes_power: bool,
     /*
     pub visibility: Visibility,
     pub subclass: MultiStr,
     pub configurable: bool,
     pub category: Category,
-    pub has_items: bool,
     pub accepts_items: bool,
     pub separate_item_capacity: bool,
     pub no_side_blend: bool,
     pub unloadable: bool,
-    pub has_liquids: bool,
     pub outputs_liquid: bool,
-    pub has_power: bool,
-    pub consumes_power: bool,
     pub outputs_power: bool,
     pub connected_power: bool,
     pub conductive_power: bool,
@@ -53,6 +57,13 @@ pub struct Block {
 
 impl_content!(Block);
 
+impl Block {
+    /// Returns the offset from the bottom left corner of this block to its center tile, which Mindustry uses as the block's position in schematics and maps.
+    pub fn center_offset(&self) -> i16 {
+        (self.size - 1) / 2
+    }
+}
+
 #[derive(Debug, Clone, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct Item {
@@ -182,8 +193,7 @@ macro_rules! include_content {
 
         use lazy_static::lazy_static;
 
-        use super::$typ;
-        use crate::utils::RapidHashMap;
+        use crate::{types::content::$typ, utils::RapidHashMap};
 
         #[cfg(feature = "std")]
         lazy_static! {
@@ -258,3 +268,164 @@ pub mod units {
         "content/units.json"; 0
     );
 }
+
+/// Content data for Mindustry v7 (build 146).
+#[cfg(feature = "std")]
+pub mod v7 {
+    pub mod blocks {
+        include_content!(
+            Block,
+            "../../submodules/mimex-data/data/v7/mimex-blocks.txt",
+            "content/blocks.json"; 2
+        );
+    }
+
+    pub mod items {
+        include_content!(
+            Item,
+            "../../submodules/mimex-data/data/v7/mimex-items.txt",
+            "content/items.json"; 0
+        );
+    }
+
+    pub mod liquids {
+        include_content!(
+            Liquid,
+            "../../submodules/mimex-data/data/v7/mimex-liquids.txt",
+            "content/liquids.json"; 0
+        );
+    }
+
+    pub mod units {
+        include_content!(
+            Unit,
+            "../../submodules/mimex-data/data/v7/mimex-units.txt",
+            "content/units.json"; 0
+        );
+    }
+}
+
+/// A version of Mindustry's content, which determines the names, IDs and logic IDs of blocks, items, etc.
+///
+/// The content data in [`blocks`], [`items`], [`liquids`] and [`units`] is for the default version.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default, EnumString, strum::Display)]
+#[strum(serialize_all = "lowercase")]
+pub enum ContentVersion {
+    /// Mindustry v7 (build 146).
+    #[cfg(feature = "std")]
+    V7,
+    /// Bleeding Edge (v8).
+    #[default]
+    BE,
+}
+
+macro_rules! content_version_tables {
+    ($($module:ident: $typ:ident),+ $(,)?) => {
+        impl ContentVersion {
+            $(
+                pub fn $module(self) -> ContentTable<$typ> {
+                    match self {
+                        #[cfg(feature = "std")]
+                        Self::V7 => ContentTable {
+                            values: &v7::$module::VALUES[..],
+                            from_id: &v7::$module::FROM_ID,
+                            from_logic_id: &v7::$module::FROM_LOGIC_ID,
+                            from_name: &v7::$module::FROM_NAME,
+                        },
+                        Self::BE => ContentTable {
+                            values: &$module::VALUES[..],
+                            from_id: &$module::FROM_ID,
+                            from_logic_id: &$module::FROM_LOGIC_ID,
+                            from_name: &$module::FROM_NAME,
+                        },
+                    }
+                }
+            )+
+        }
+    };
+}
+
+content_version_tables!(blocks: Block, items: Item, liquids: Liquid, units: Unit);
+
+impl ContentVersion {
+    /// Converts a content ID from this version into the ID of the content with the same name in another version.
+    ///
+    /// Returns `None` if the content doesn't exist in both versions. IDs of other content types are returned unchanged.
+    pub fn convert_id(self, id: ContentID, to: ContentVersion) -> Option<ContentID> {
+        if self == to {
+            return Some(id);
+        }
+
+        let old_id = id.id as i32;
+        let new_id = match id.type_ {
+            ContentType::Block => {
+                let name = &self.blocks().values().iter().find(|v| v.id == old_id)?.name;
+                to.blocks().from_name(name)?.id
+            }
+            ContentType::Item => {
+                let name = &self.items().values().iter().find(|v| v.id == old_id)?.name;
+                to.items().from_name(name)?.id
+            }
+            ContentType::Liquid => {
+                let name = &self
+                    .liquids()
+                    .values()
+                    .iter()
+                    .find(|v| v.id == old_id)?
+                    .name;
+                to.liquids().from_name(name)?.id
+            }
+            ContentType::Unit => {
+                let name = &self.units().values().iter().find(|v| v.id == old_id)?.name;
+                to.units().from_name(name)?.id
+            }
+            _ => return Some(id),
+        };
+
+        Some(ContentID {
+            type_: id.type_,
+            id: new_id.try_into().ok()?,
+        })
+    }
+}
+
+/// The data for one type of content in a [`ContentVersion`].
+#[derive(Debug)]
+pub struct ContentTable<T: 'static> {
+    values: &'static [T],
+    from_id: &'static RapidHashMap<i32, &'static T>,
+    from_logic_id: &'static RapidHashMap<i32, &'static T>,
+    from_name: &'static RapidHashMap<&'static str, &'static T>,
+}
+
+impl<T> ContentTable<T> {
+    pub fn values(&self) -> &'static [T] {
+        self.values
+    }
+
+    /// Only finds values that have a valid logic id.
+    pub fn from_id(&self, id: i32) -> Option<&'static T> {
+        self.from_id.get(&id).copied()
+    }
+
+    pub fn from_logic_id(&self, logic_id: i32) -> Option<&'static T> {
+        self.from_logic_id.get(&logic_id).copied()
+    }
+
+    pub fn from_name(&self, name: &str) -> Option<&'static T> {
+        self.from_name.get(name).copied()
+    }
+
+    /// Returns the number of values with a valid logic id, eg. `@blockCount`.
+    pub fn logic_count(&self) -> usize {
+        self.from_logic_id.len()
+    }
+}
+
+impl<T> Clone for ContentTable<T> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<T> Copy for ContentTable<T> {}
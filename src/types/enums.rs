@@ -14,11 +14,13 @@ use crate::utils::leak_u16string;
 
 #[binrw]
 #[brw(big, repr = i8)]
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Deserialize, Serialize)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, IntoStaticStr, Deserialize, Serialize)]
+#[strum(serialize_all = "camelCase")]
 pub enum ContentType {
     Item,
     Block,
     #[deprecated]
+    #[strum(serialize = "mech_UNUSED")]
     Mech,
     Bullet,
     Liquid,
@@ -26,15 +28,19 @@ pub enum ContentType {
     Unit,
     Weather,
     #[deprecated]
+    #[strum(serialize = "effect_UNUSED")]
     Effect,
     Sector,
     #[deprecated]
+    #[strum(serialize = "loadout_UNUSED")]
     Loadout,
     #[deprecated]
+    #[strum(serialize = "typeid_UNUSED")]
     TypeID,
     Error,
     Planet,
     #[deprecated]
+    #[strum(serialize = "ammo_UNUSED")]
     Ammo,
     Team,
     UnitCommand,
@@ -207,7 +213,7 @@ impl Team {
 
 #[binrw]
 #[brw(big, repr = u16)]
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Deserialize, Serialize)]
 pub enum UnitCommand {
     Move,
     Repair,
@@ -16,8 +16,8 @@ use binrw::prelude::*;
 #[brw(big)]
 #[derive(Debug, Clone, Default)]
 pub struct JavaString {
-    /// The length of the string.
-    #[bw(try_calc = u16::try_from(value.len()))]
+    /// The length of the encoded string in bytes, which may be longer than [`Self::value`] because of the modified UTF-8 encoding.
+    #[bw(try_calc = u16::try_from(map_write(value).len()))]
     count: u16,
 
     /// The string value.
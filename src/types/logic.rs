@@ -56,6 +56,16 @@ impl ProcessorConfig {
             }),
         }
     }
+
+    /// Serializes this config into the format used by processor tiles in schematics.
+    #[cfg(feature = "std")]
+    pub fn to_object(&self) -> BinResult<Object> {
+        let mut cur = Cursor::new(Vec::new());
+        self.write(&mut cur)?;
+        Ok(Object::ByteArray {
+            values: cur.into_inner(),
+        })
+    }
 }
 
 /// A relative processor link.
@@ -0,0 +1,878 @@
+use std::{
+    boxed::Box,
+    collections::HashMap,
+    format,
+    io::{Cursor, Read, Seek, SeekFrom},
+    prelude::rust_2024::*,
+    vec,
+};
+
+use binrw::prelude::*;
+use flate2::read::ZlibDecoder;
+use indexmap::IndexMap;
+
+use crate::types::{
+    ContentID, ContentType, JavaString, Object, PackedPoint2, ProcessorConfig, ProcessorLinkConfig,
+    Rules, SchematicTile, Team, content,
+};
+
+/// The oldest save format version that can be read.
+const MIN_VERSION: i32 = 7;
+
+/// A map or save file (`.msav`), equivalent to the parts of Mindustry's `SaveVersion` that are relevant to logic.
+///
+/// Entities (eg. units) and custom chunks are not read.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Map {
+    /// The save format version.
+    pub version: i32,
+    /// The map's metadata, eg. `name`, `author`, `description`, `wave` and `rules`.
+    pub tags: IndexMap<String, String>,
+    pub width: u16,
+    pub height: u16,
+    /// The names of the blocks used by this map, indexed by the IDs in [`MapTile`].
+    pub blocks: Vec<String>,
+    /// Every tile in the map, in row-major order starting from the bottom left.
+    pub tiles: Vec<MapTile>,
+    pub buildings: Vec<MapBuilding>,
+}
+
+impl Map {
+    /// Reads a map from the contents of a `.msav` file.
+    pub fn read(reader: impl Read) -> BinResult<Self> {
+        let mut buf = Vec::new();
+        ZlibDecoder::new(reader).read_to_end(&mut buf)?;
+        let reader = &mut Cursor::new(buf);
+
+        let magic: [u8; 4] = reader.read_be()?;
+        if &magic != b"MSAV" {
+            return Err(binrw::Error::BadMagic {
+                pos: 0,
+                found: Box::new(magic),
+            });
+        }
+
+        let version: i32 = reader.read_be()?;
+        if version < MIN_VERSION {
+            return Err(custom_error(
+                reader,
+                format!("unsupported save version: {version}"),
+            ));
+        }
+
+        let tags = read_chunk(reader, false, read_string_map)?;
+        let content = read_chunk(reader, false, read_content_header)?;
+
+        let mut map = Self {
+            version,
+            tags,
+            blocks: content
+                .get(&ContentType::Block)
+                .cloned()
+                .unwrap_or_default(),
+            ..Default::default()
+        };
+        read_chunk(reader, false, |r| map.read_tiles(r, &content))?;
+
+        Ok(map)
+    }
+
+    /// Returns the map's name, or `None` if it doesn't have one.
+    pub fn name(&self) -> Option<&str> {
+        self.tags
+            .get("name")
+            .or_else(|| self.tags.get("mapname"))
+            .map(|s| s.as_str())
+    }
+
+    /// Returns the map's rules, or the default rules if they are missing or invalid.
+    pub fn rules(&self) -> Rules {
+        self.tags
+            .get("rules")
+            .and_then(|s| Rules::parse(s))
+            .unwrap_or_default()
+    }
+
+    /// Returns the current wave number.
+    pub fn wave(&self) -> i32 {
+        self.tags
+            .get("wave")
+            .and_then(|s| s.parse().ok())
+            .unwrap_or(1)
+    }
+
+    /// Returns the time until the next wave, in ticks.
+    pub fn wave_time(&self) -> f64 {
+        self.tags
+            .get("wavetime")
+            .and_then(|s| s.parse().ok())
+            .unwrap_or(0.)
+    }
+
+    /// Returns the tile at a position, or `None` if the position is out of bounds.
+    pub fn tile(&self, x: i32, y: i32) -> Option<&MapTile> {
+        if x < 0 || y < 0 || x >= self.width as i32 || y >= self.height as i32 {
+            return None;
+        }
+        self.tiles
+            .get(y as usize * self.width as usize + x as usize)
+    }
+
+    /// Returns the name of the block with the given ID in this map.
+    pub fn block_name(&self, id: i16) -> Option<&str> {
+        self.blocks
+            .get(usize::try_from(id).ok()?)
+            .map(|s| s.as_str())
+    }
+
+    fn read_tiles(
+        &mut self,
+        reader: &mut Cursor<Vec<u8>>,
+        content: &ContentHeader,
+    ) -> BinResult<()> {
+        self.width = reader.read_be()?;
+        self.height = reader.read_be()?;
+        let total = self.width as usize * self.height as usize;
+
+        // floors and overlays are run-length encoded
+        self.tiles = Vec::with_capacity(total);
+        while self.tiles.len() < total {
+            let floor: i16 = reader.read_be()?;
+            let overlay: i16 = reader.read_be()?;
+            let consecutive: u8 = reader.read_be()?;
+            for _ in 0..=consecutive {
+                self.tiles.push(MapTile {
+                    floor,
+                    overlay,
+                    block: 0,
+                });
+            }
+        }
+        self.tiles.truncate(total);
+
+        // blocks are also run-length encoded, except for tiles with buildings or extra data
+        let mut i = 0;
+        while i < total {
+            let block: i16 = reader.read_be()?;
+            let packed: u8 = reader.read_be()?;
+            let has_building = packed & 1 != 0;
+            let has_legacy_data = packed & 2 != 0;
+            let has_data = packed & 4 != 0;
+
+            if has_data {
+                // data, floor data, overlay data, extra data
+                reader.seek(SeekFrom::Current(7))?;
+            }
+
+            self.tiles[i].block = block;
+
+            if has_building {
+                let is_center: u8 = reader.read_be()?;
+                if is_center != 0 {
+                    let name = self.block_name(block).ok_or_else(|| {
+                        custom_error(reader, format!("unknown block id: {block}"))
+                    })?;
+                    let position = PackedPoint2 {
+                        x: (i % self.width as usize) as i16,
+                        y: (i / self.width as usize) as i16,
+                    };
+                    let building = read_chunk(reader, true, |r| {
+                        MapBuilding::read(r, name, position, content)
+                    })?;
+                    self.buildings.push(building);
+                }
+                i += 1;
+            } else if has_legacy_data || has_data {
+                if has_legacy_data {
+                    reader.seek(SeekFrom::Current(1))?;
+                }
+                i += 1;
+            } else {
+                let consecutive: u8 = reader.read_be()?;
+                for tile in self.tiles.iter_mut().skip(i + 1).take(consecutive as usize) {
+                    tile.block = block;
+                }
+                i += consecutive as usize + 1;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// A single tile in a map. Each field is an index into [`Map::blocks`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct MapTile {
+    pub floor: i16,
+    pub overlay: i16,
+    /// The block on this tile. Tiles covered by multi-tile buildings all contain the building's block.
+    pub block: i16,
+}
+
+/// A building in a map.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MapBuilding {
+    pub block: String,
+    /// The center of the building, rounded down and to the left for even-sized blocks.
+    pub position: PackedPoint2,
+    pub team: Team,
+    /// The direction that the building is facing, from 0 (right) to 3 (down).
+    pub rotation: i8,
+    pub enabled: bool,
+    pub health: f32,
+    /// Item amounts by item name.
+    pub items: Vec<(String, i32)>,
+    /// Liquid amounts by liquid name.
+    pub liquids: Vec<(String, f32)>,
+    /// The building's power module, if it has one.
+    pub power: Option<MapPower>,
+    /// The building's configuration, in the same format as schematic tiles.
+    ///
+    /// This is only decoded for blocks that mindy knows how to read; for other blocks, it is [`Object::Null`] and the raw data can be found in [`Self::data`]. Content IDs are converted to the IDs of the default [`ContentVersion`](content::ContentVersion).
+    pub config: Object,
+    /// The saved state of a processor.
+    pub processor: Option<MapProcessorState>,
+    /// The contents of a memory cell or bank.
+    pub memory: Option<Vec<f64>>,
+    /// The block-specific data following the common building fields.
+    pub data: Vec<u8>,
+}
+
+/// The power module of a building in a map.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MapPower {
+    /// The absolute positions of the buildings that are laser-linked to this building.
+    pub links: Vec<PackedPoint2>,
+    /// The amount of power stored (for batteries) or the power satisfaction (for consumers), from 0 to 1.
+    pub status: f32,
+}
+
+/// The state of a processor in a map, other than its code and links.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct MapProcessorState {
+    /// The values of the processor's variables when the map was saved.
+    pub variables: Vec<(String, Object)>,
+    /// The instructions per tick set by `setrate`, for world processors.
+    pub ipt: Option<i16>,
+}
+
+impl MapBuilding {
+    /// Converts this building into a schematic tile, discarding everything except its block, position, config and rotation.
+    pub fn to_schematic_tile(&self) -> SchematicTile {
+        SchematicTile {
+            block: self.block.clone(),
+            position: self.position,
+            config: self.config.clone(),
+            rotation: self.rotation,
+        }
+    }
+
+    // https://github.com/Anuken/Mindustry/blob/v146/core/src/mindustry/entities/comp/BuildingComp.java#L262
+    fn read(
+        reader: &mut Cursor<Vec<u8>>,
+        block: &str,
+        position: PackedPoint2,
+        content: &ContentHeader,
+    ) -> BinResult<Self> {
+        let revision: i8 = reader.read_be()?;
+        let health: f32 = reader.read_be()?;
+        let rotation: u8 = reader.read_be()?;
+        let team: Team = reader.read_be()?;
+
+        if rotation & 0x80 == 0 {
+            return Err(custom_error(
+                reader,
+                "legacy building format is not supported",
+            ));
+        }
+
+        let version: u8 = reader.read_be()?;
+        let enabled = match version {
+            0 => true,
+            _ => reader.read_be::<u8>()? == 1,
+        };
+        if version < 2 {
+            return Err(custom_error(
+                reader,
+                format!("unsupported building version: {version}"),
+            ));
+        }
+        let modules: u8 = reader.read_be()?;
+
+        let mut items = Vec::new();
+        if modules & 1 != 0 {
+            let count: i16 = reader.read_be()?;
+            for _ in 0..count {
+                let id: i16 = reader.read_be()?;
+                let amount: i32 = reader.read_be()?;
+                if let Some(name) = content.name(ContentType::Item, id) {
+                    items.push((name.to_string(), amount));
+                }
+            }
+        }
+
+        let power = if modules & 2 != 0 {
+            let count: i16 = reader.read_be()?;
+            let links = (0..count)
+                .map(|_| reader.read_be())
+                .collect::<BinResult<_>>()?;
+            let status: f32 = reader.read_be()?;
+            Some(MapPower {
+                links,
+                status: if status.is_finite() { status } else { 0. },
+            })
+        } else {
+            None
+        };
+
+        let mut liquids = Vec::new();
+        if modules & 4 != 0 {
+            let count: i16 = reader.read_be()?;
+            for _ in 0..count {
+                let id: i16 = reader.read_be()?;
+                let amount: f32 = reader.read_be()?;
+                if let Some(name) = content.name(ContentType::Liquid, id) {
+                    liquids.push((name.to_string(), amount));
+                }
+            }
+        }
+
+        match version {
+            // consume module
+            2 => {
+                reader.seek(SeekFrom::Current(1))?;
+            }
+            // efficiency and optional efficiency, then visibility flags
+            3 => {
+                reader.seek(SeekFrom::Current(2))?;
+            }
+            _ => {
+                reader.seek(SeekFrom::Current(2 + 8))?;
+            }
+        }
+
+        let mut data = Vec::new();
+        reader.read_to_end(&mut data)?;
+
+        let mut building = Self {
+            block: block.to_string(),
+            position,
+            team,
+            rotation: (rotation & 0x7f) as i8,
+            enabled,
+            health,
+            items,
+            liquids,
+            power,
+            config: Object::Null,
+            processor: None,
+            memory: None,
+            data,
+        };
+        building.read_config(revision, content)?;
+        Ok(building)
+    }
+
+    /// Decodes the block-specific data for the blocks that we know about.
+    fn read_config(&mut self, revision: i8, content: &ContentHeader) -> BinResult<()> {
+        let data = self.data.clone();
+        let reader = &mut Cursor::new(data);
+        self.config = match self.block.as_str() {
+            "micro-processor" | "logic-processor" | "hyper-processor" | "world-processor" => {
+                // https://github.com/Anuken/Mindustry/blob/v146/core/src/mindustry/world/blocks/logic/LogicBlock.java#L749
+                let mut config = if revision >= 1 {
+                    let count: i32 = reader.read_be()?;
+                    let mut bytes = vec![0; count.try_into().unwrap_or(0)];
+                    reader.read_exact(&mut bytes)?;
+                    ProcessorConfig::read(&mut Cursor::new(bytes))?
+                } else {
+                    let code: JavaString = reader.read_be()?;
+                    let count: i16 = reader.read_be()?;
+                    reader.seek(SeekFrom::Current(4 * count as i64))?;
+                    ProcessorConfig::from_code(&code)
+                };
+
+                // links are absolute in saves, but relative in schematics
+                for link in &mut config.links {
+                    *link = ProcessorLinkConfig {
+                        name: link.name.clone(),
+                        x: link.x - self.position.x,
+                        y: link.y - self.position.y,
+                    };
+                }
+
+                let mut state = MapProcessorState::default();
+
+                let count: i32 = reader.read_be()?;
+                for _ in 0..count {
+                    let name: JavaString = reader.read_be()?;
+                    let value: Object = reader.read_be()?;
+                    state
+                        .variables
+                        .push((name.into(), content.remap_object(value)));
+                }
+
+                // legacy memory, which is no longer used
+                let memory: i32 = reader.read_be()?;
+                reader.seek(SeekFrom::Current(8 * memory as i64))?;
+
+                if self.block == "world-processor" && revision >= 2 {
+                    state.ipt = Some(reader.read_be()?);
+                }
+
+                self.processor = Some(state);
+                config.to_object()?
+            }
+
+            "memory-cell" | "memory-bank" | "world-cell" => {
+                let count: i32 = reader.read_be()?;
+                self.memory = Some(
+                    (0..count)
+                        .map(|_| reader.read_be())
+                        .collect::<BinResult<_>>()?,
+                );
+                Object::Null
+            }
+
+            "message" | "world-message" => reader.read_be::<JavaString>()?.into(),
+
+            "switch" | "world-switch" => Object::Bool(match revision {
+                1 => reader.read_be::<u8>()? != 0,
+                _ => self.enabled,
+            }),
+
+            "sorter" | "inverted-sorter" => content.item_config(reader.read_be()?),
+
+            "unloader" => content.item_config(match revision {
+                1 => reader.read_be()?,
+                _ => reader.read_be::<i8>()? as i16,
+            }),
+
+            "illuminator" => Object::Int(reader.read_be()?),
+
+            "door" | "door-large" => Object::Bool(reader.read_be::<u8>()? != 0),
+
+            // power node links are relative in schematics
+            "power-node" | "power-node-large" | "surge-tower" => match &self.power {
+                Some(power) => power
+                    .links
+                    .iter()
+                    .map(|link| PackedPoint2 {
+                        x: link.x - self.position.x,
+                        y: link.y - self.position.y,
+                    })
+                    .collect::<Vec<_>>()
+                    .into(),
+                None => Object::Null,
+            },
+
+            _ => Object::Null,
+        };
+        Ok(())
+    }
+}
+
+/// The names of the content used in a map, indexed by type and then by the IDs used in the map.
+///
+/// Content IDs are not stable between game versions, so maps store the name of every piece of content.
+#[derive(Debug, Default)]
+struct ContentHeader(HashMap<ContentType, Vec<String>>);
+
+impl ContentHeader {
+    fn name(&self, type_: ContentType, id: i16) -> Option<&str> {
+        self.0
+            .get(&type_)?
+            .get(usize::try_from(id).ok()?)
+            .map(|s| s.as_str())
+    }
+
+    fn get(&self, type_: &ContentType) -> Option<&Vec<String>> {
+        self.0.get(type_)
+    }
+
+    /// Converts a content ID from this map into the corresponding ID in the default content version.
+    fn remap(&self, ContentID { type_, id }: ContentID) -> Option<ContentID> {
+        let name = self.name(type_, id)?;
+        let id = match type_ {
+            ContentType::Block => content::blocks::FROM_NAME.get(name)?.id,
+            ContentType::Item => content::items::FROM_NAME.get(name)?.id,
+            ContentType::Liquid => content::liquids::FROM_NAME.get(name)?.id,
+            ContentType::Unit => content::units::FROM_NAME.get(name)?.id,
+            _ => id as i32,
+        };
+        Some(ContentID {
+            type_,
+            id: id.try_into().ok()?,
+        })
+    }
+
+    fn remap_object(&self, value: Object) -> Object {
+        match value {
+            Object::Content(id) => self.remap(id).map_or(Object::Null, Object::Content),
+            Object::TechNode(id) => self.remap(id).map_or(Object::Null, Object::TechNode),
+            Object::ObjectArray { values } => Object::ObjectArray {
+                values: values.into_iter().map(|v| self.remap_object(v)).collect(),
+            },
+            value => value,
+        }
+    }
+
+    /// Converts an item ID from this map into a schematic item config.
+    fn item_config(&self, id: i16) -> Object {
+        self.remap_object(Object::Content(ContentID {
+            type_: ContentType::Item,
+            id,
+        }))
+    }
+}
+
+fn read_chunk<T>(
+    reader: &mut Cursor<Vec<u8>>,
+    short: bool,
+    f: impl FnOnce(&mut Cursor<Vec<u8>>) -> BinResult<T>,
+) -> BinResult<T> {
+    let len = if short {
+        reader.read_be::<u16>()? as usize
+    } else {
+        let len: i32 = reader.read_be()?;
+        usize::try_from(len).map_err(|_| custom_error(reader, "negative chunk length"))?
+    };
+    let mut buf = vec![0; len];
+    reader.read_exact(&mut buf)?;
+    f(&mut Cursor::new(buf))
+}
+
+fn read_string_map(reader: &mut Cursor<Vec<u8>>) -> BinResult<IndexMap<String, String>> {
+    let count: i16 = reader.read_be()?;
+    (0..count)
+        .map(|_| {
+            let key: JavaString = reader.read_be()?;
+            let value: JavaString = reader.read_be()?;
+            Ok((key.into(), value.into()))
+        })
+        .collect()
+}
+
+fn read_content_header(reader: &mut Cursor<Vec<u8>>) -> BinResult<ContentHeader> {
+    let count: i8 = reader.read_be()?;
+    let mut header = ContentHeader::default();
+    for _ in 0..count {
+        let type_: ContentType = reader.read_be()?;
+        let total: i16 = reader.read_be()?;
+        let names = (0..total)
+            .map(|_| reader.read_be::<JavaString>().map(String::from))
+            .collect::<BinResult<_>>()?;
+        header.0.insert(type_, names);
+    }
+    Ok(header)
+}
+
+fn custom_error(reader: &mut impl Seek, err: impl Into<String>) -> binrw::Error {
+    binrw::Error::Custom {
+        pos: reader.stream_position().unwrap_or(0),
+        err: Box::new(err.into()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+
+    use binrw::Endian;
+    use flate2::{Compression, write::ZlibEncoder};
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    fn chunk(short: bool, f: impl FnOnce(&mut Cursor<Vec<u8>>)) -> Vec<u8> {
+        let mut body = Cursor::new(Vec::new());
+        f(&mut body);
+        let body = body.into_inner();
+
+        let mut result = Cursor::new(Vec::new());
+        if short {
+            (body.len() as u16).write_be(&mut result).unwrap();
+        } else {
+            (body.len() as i32).write_be(&mut result).unwrap();
+        }
+        result.write_all(&body).unwrap();
+        result.into_inner()
+    }
+
+    fn write<T>(w: &mut Cursor<Vec<u8>>, value: T)
+    where
+        T: for<'a> BinWrite<Args<'a> = ()>,
+    {
+        value.write_options(w, Endian::Big, ()).unwrap();
+    }
+
+    fn write_str(w: &mut Cursor<Vec<u8>>, value: &str) {
+        write(w, JavaString::from(value));
+    }
+
+    /// Writes the common fields of a building with no modules.
+    fn write_building_base(w: &mut Cursor<Vec<u8>>, revision: i8, rotation: u8, team: u8) {
+        write(w, revision);
+        write(w, 100f32); // health
+        write(w, rotation | 0x80);
+        write(w, team);
+        write(w, 3u8); // version
+        write(w, 1u8); // enabled
+        write(w, 0u8); // modules
+        write(w, [255u8, 255u8]); // efficiency
+    }
+
+    /// Writes a processor config with absolute links.
+    fn write_processor_config(w: &mut Cursor<Vec<u8>>, config: &ProcessorConfig) {
+        let Object::ByteArray { values } = config.to_object().unwrap() else {
+            unreachable!();
+        };
+        write(w, values.len() as i32);
+        w.write_all(&values).unwrap();
+    }
+
+    fn test_map() -> Vec<u8> {
+        let mut w = Cursor::new(Vec::new());
+        w.write_all(b"MSAV").unwrap();
+        write(&mut w, 7i32);
+
+        w.write_all(&chunk(false, |w| {
+            write(w, 3i16);
+            for (key, value) in [
+                ("name", "Test Map"),
+                ("wave", "5"),
+                ("rules", "{waves:true,disableWorldProcessors:false}"),
+            ] {
+                write_str(w, key);
+                write_str(w, value);
+            }
+        }))
+        .unwrap();
+
+        // deliberately use different IDs than the current game version
+        let blocks = [
+            "air",
+            "stone",
+            "sand-floor",
+            "ore-copper",
+            "world-processor",
+            "message",
+            "memory-cell",
+            "sorter",
+            "logic-processor",
+        ];
+        w.write_all(&chunk(false, |w| {
+            write(w, 2i8);
+            write(w, ContentType::Item);
+            write(w, 2i16);
+            write_str(w, "lead");
+            write_str(w, "copper");
+            write(w, ContentType::Block);
+            write(w, blocks.len() as i16);
+            for block in blocks {
+                write_str(w, block);
+            }
+        }))
+        .unwrap();
+
+        // 4x3 map, where W is a world processor, M is a message, S is a sorter, C is a memory cell and L is a logic processor
+        // 2: W . . .
+        // 1: S C L L
+        // 0: M . L L
+        w.write_all(&chunk(false, |w| {
+            write(w, 4u16);
+            write(w, 3u16);
+
+            // floors: 4 sand with copper ore, then 8 stone
+            write(w, (2i16, 3i16, 3u8));
+            write(w, (1i16, 0i16, 7u8));
+
+            // y = 0: message, 3 air
+            write(w, (5i16, 1u8, 1u8));
+            w.write_all(&chunk(true, |w| {
+                write_building_base(w, 0, 0, 1);
+                write_str(w, "hello");
+            }))
+            .unwrap();
+            write(w, (0i16, 0u8, 0u8));
+            // logic processor centered at (2, 0)
+            write(w, (8i16, 1u8, 1u8));
+            w.write_all(&chunk(true, |w| {
+                write_building_base(w, 3, 0, 1);
+                write_processor_config(w, &ProcessorConfig::from_code("end"));
+                write(w, 0i32); // no variables
+                write(w, 0i32); // no memory
+                write_str(w, ""); // tag
+                write(w, 0i16); // icon
+            }))
+            .unwrap();
+            write(w, (8i16, 1u8, 0u8));
+
+            // y = 1: sorter, memory cell, logic processor
+            write(w, (7i16, 1u8, 1u8));
+            w.write_all(&chunk(true, |w| {
+                write_building_base(w, 0, 0, 2);
+                write(w, 1i16); // copper
+            }))
+            .unwrap();
+            write(w, (6i16, 1u8, 1u8));
+            w.write_all(&chunk(true, |w| {
+                write_building_base(w, 0, 0, 1);
+                write(w, 2i32);
+                write(w, 1.5f64);
+                write(w, 2.5f64);
+            }))
+            .unwrap();
+            write(w, (8i16, 1u8, 0u8));
+            write(w, (8i16, 1u8, 0u8));
+
+            // y = 2: world processor, 3 air
+            write(w, (4i16, 1u8, 1u8));
+            w.write_all(&chunk(true, |w| {
+                write_building_base(w, 2, 1, 1);
+                write_processor_config(
+                    w,
+                    &ProcessorConfig {
+                        code: "print x".into(),
+                        links: vec![ProcessorLinkConfig {
+                            name: "message1".into(),
+                            x: 0,
+                            y: 0,
+                        }],
+                    },
+                );
+                write(w, 2i32);
+                write_str(w, "x");
+                write(w, Object::Double(4.));
+                write_str(w, "item");
+                write(
+                    w,
+                    Object::Content(ContentID {
+                        type_: ContentType::Item,
+                        id: 0,
+                    }),
+                );
+                write(w, 0i32);
+                write(w, 100i16); // ipt
+            }))
+            .unwrap();
+            write(w, (0i16, 0u8, 2u8));
+        }))
+        .unwrap();
+
+        // entities, which should be ignored
+        w.write_all(&chunk(false, |w| write(w, [1u8, 2, 3])))
+            .unwrap();
+
+        let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(&w.into_inner()).unwrap();
+        encoder.finish().unwrap()
+    }
+
+    #[test]
+    fn test_read_map() {
+        let map = Map::read(&test_map()[..]).unwrap();
+
+        assert_eq!(map.version, 7);
+        assert_eq!(map.name(), Some("Test Map"));
+        assert_eq!(map.wave(), 5);
+        assert_eq!(
+            map.rules(),
+            Rules {
+                waves: true,
+                ..Default::default()
+            }
+        );
+        assert_eq!((map.width, map.height), (4, 3));
+        assert_eq!(map.tiles.len(), 12);
+
+        let tile = |x, y| {
+            let tile = map.tile(x, y).unwrap();
+            (
+                map.block_name(tile.floor).unwrap(),
+                map.block_name(tile.overlay).unwrap(),
+                map.block_name(tile.block).unwrap(),
+            )
+        };
+        assert_eq!(tile(0, 0), ("sand-floor", "ore-copper", "message"));
+        assert_eq!(tile(3, 0), ("sand-floor", "ore-copper", "logic-processor"));
+        assert_eq!(tile(3, 1), ("stone", "air", "logic-processor"));
+        assert_eq!(tile(1, 1), ("stone", "air", "memory-cell"));
+        assert_eq!(tile(3, 2), ("stone", "air", "air"));
+        assert_eq!(map.tile(4, 0), None);
+
+        let buildings = map
+            .buildings
+            .iter()
+            .map(|b| (b.block.as_str(), b.position))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            buildings,
+            vec![
+                ("message", PackedPoint2::new(0, 0)),
+                ("logic-processor", PackedPoint2::new(2, 0)),
+                ("sorter", PackedPoint2::new(0, 1)),
+                ("memory-cell", PackedPoint2::new(1, 1)),
+                ("world-processor", PackedPoint2::new(0, 2)),
+            ]
+        );
+
+        let message = &map.buildings[0];
+        assert_eq!(message.config, Object::String(Some("hello".into())));
+        assert_eq!(message.team, Team::SHARDED);
+        assert!(message.enabled);
+
+        let sorter = &map.buildings[2];
+        assert_eq!(sorter.team, Team::CRUX);
+        assert_eq!(
+            sorter.config,
+            Object::Content(ContentID {
+                type_: ContentType::Item,
+                id: content::items::FROM_NAME["copper"].id as i16,
+            })
+        );
+
+        assert_eq!(map.buildings[3].memory, Some(vec![1.5, 2.5]));
+
+        let processor = &map.buildings[4];
+        assert_eq!(processor.rotation, 1);
+        assert_eq!(
+            ProcessorConfig::parse(&processor.config).unwrap(),
+            ProcessorConfig {
+                code: "print x".into(),
+                links: vec![ProcessorLinkConfig {
+                    name: "message1".into(),
+                    x: 0,
+                    y: -2,
+                }],
+            }
+        );
+        assert_eq!(
+            processor.processor,
+            Some(MapProcessorState {
+                variables: vec![
+                    ("x".into(), Object::Double(4.)),
+                    (
+                        "item".into(),
+                        Object::Content(ContentID {
+                            type_: ContentType::Item,
+                            id: content::items::FROM_NAME["lead"].id as i16,
+                        })
+                    ),
+                ],
+                ipt: Some(100),
+            })
+        );
+    }
+
+    #[test]
+    fn test_read_bad_magic() {
+        let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(b"MSCH\x00\x00\x00\x07").unwrap();
+        let data = encoder.finish().unwrap();
+        assert!(matches!(
+            Map::read(&data[..]),
+            Err(binrw::Error::BadMagic { .. })
+        ));
+    }
+}
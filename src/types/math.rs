@@ -78,6 +78,6 @@ impl Display for PackedPoint2 {
 #[brw(big)]
 #[derive(Debug, Clone, Copy, PartialEq)]
 pub struct Vec2 {
-    x: f32,
-    y: f32,
+    pub x: f32,
+    pub y: f32,
 }
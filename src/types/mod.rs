@@ -1,13 +1,18 @@
+pub use self::{enums::*, java::*, logic::*, math::*, rules::*, type_io::*};
 #[cfg(feature = "std")]
-pub use self::schematics::*;
-pub use self::{enums::*, java::*, logic::*, math::*, type_io::*};
+pub use self::{map::*, schematic_json::*, schematics::*};
 
 pub mod colors;
 pub mod content;
 mod enums;
 mod java;
 mod logic;
+#[cfg(feature = "std")]
+mod map;
 mod math;
+mod rules;
+#[cfg(feature = "std")]
+mod schematic_json;
 #[cfg(feature = "std")]
 mod schematics;
 mod type_io;
@@ -0,0 +1,237 @@
+#[cfg(feature = "std")]
+use std::{prelude::rust_2024::*, vec};
+
+use serde::{Deserialize, Serialize};
+#[cfg(feature = "std")]
+use serde_json::{Map, Number, Value};
+
+/// The game rules that affect logic, equivalent to a subset of Mindustry's `Rules`.
+///
+/// Fields that aren't present when deserializing use the same defaults as Mindustry.
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct Rules {
+    /// Whether waves are spawned.
+    pub waves: bool,
+    /// Whether waves are spawned on a timer.
+    pub wave_timer: bool,
+    /// The time between waves, in ticks.
+    pub wave_spacing: f32,
+    /// The time before the first wave, in ticks. If 0, [`Self::wave_spacing`] is doubled instead.
+    pub initial_wave_spacing: f32,
+    /// The wave that must be reached to win, or 0 if there is no such wave.
+    pub win_wave: i32,
+    /// The base unit cap for each team.
+    pub unit_cap: i32,
+    /// If true, world processors do not run.
+    pub disable_world_processors: bool,
+}
+
+impl Rules {
+    /// Parses rules from the format used by the `rules` tag of a map.
+    ///
+    /// Mindustry writes this tag using libGDX's "minimal" JSON, which allows unquoted keys and string values, so it can't be parsed by most JSON libraries.
+    #[cfg(feature = "std")]
+    pub fn parse(s: &str) -> Option<Self> {
+        serde_json::from_value(parse_minimal_json(s)?).ok()
+    }
+}
+
+impl Default for Rules {
+    fn default() -> Self {
+        Self {
+            waves: false,
+            wave_timer: true,
+            wave_spacing: 2. * 60. * 60.,
+            initial_wave_spacing: 0.,
+            win_wave: 0,
+            unit_cap: 0,
+            disable_world_processors: false,
+        }
+    }
+}
+
+/// Parses a libGDX-style JSON string, where object keys and string values may be unquoted.
+#[cfg(feature = "std")]
+fn parse_minimal_json(s: &str) -> Option<Value> {
+    let mut parser = MinimalJsonParser {
+        chars: s.chars().collect(),
+        pos: 0,
+    };
+    let value = parser.value()?;
+    parser.skip_whitespace();
+    (parser.pos == parser.chars.len()).then_some(value)
+}
+
+#[cfg(feature = "std")]
+struct MinimalJsonParser {
+    chars: Vec<char>,
+    pos: usize,
+}
+
+#[cfg(feature = "std")]
+impl MinimalJsonParser {
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.pos).copied()
+    }
+
+    fn skip_whitespace(&mut self) {
+        while self.peek().is_some_and(|c| c.is_whitespace()) {
+            self.pos += 1;
+        }
+    }
+
+    /// Consumes `c` (and any whitespace before it) if it's the next character.
+    fn eat(&mut self, c: char) -> bool {
+        self.skip_whitespace();
+        if self.peek() == Some(c) {
+            self.pos += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn value(&mut self) -> Option<Value> {
+        self.skip_whitespace();
+        match self.peek()? {
+            '{' => self.object(),
+            '[' => self.array(),
+            '"' => self.quoted().map(Value::String),
+            _ => {
+                let token = self.unquoted()?;
+                Some(match token.as_str() {
+                    "true" => Value::Bool(true),
+                    "false" => Value::Bool(false),
+                    "null" => Value::Null,
+                    _ => match token.parse::<i64>() {
+                        Ok(n) => n.into(),
+                        Err(_) => match token.parse::<f64>().ok().and_then(Number::from_f64) {
+                            Some(n) => Value::Number(n),
+                            None => Value::String(token),
+                        },
+                    },
+                })
+            }
+        }
+    }
+
+    fn object(&mut self) -> Option<Value> {
+        self.pos += 1;
+        let mut map = Map::new();
+        while !self.eat('}') {
+            self.skip_whitespace();
+            let key = match self.peek()? {
+                '"' => self.quoted()?,
+                _ => self.unquoted()?,
+            };
+            if !self.eat(':') {
+                return None;
+            }
+            map.insert(key, self.value()?);
+            self.eat(',');
+        }
+        Some(Value::Object(map))
+    }
+
+    fn array(&mut self) -> Option<Value> {
+        self.pos += 1;
+        let mut values = vec![];
+        while !self.eat(']') {
+            values.push(self.value()?);
+            self.eat(',');
+        }
+        Some(Value::Array(values))
+    }
+
+    fn quoted(&mut self) -> Option<String> {
+        self.pos += 1;
+        let mut result = String::new();
+        loop {
+            match self.peek()? {
+                '"' => {
+                    self.pos += 1;
+                    return Some(result);
+                }
+                '\\' => {
+                    self.pos += 1;
+                    result.push(match self.peek()? {
+                        'n' => '\n',
+                        't' => '\t',
+                        'r' => '\r',
+                        'b' => '\u{8}',
+                        'f' => '\u{c}',
+                        'u' => {
+                            let hex: String =
+                                self.chars.get(self.pos + 1..self.pos + 5)?.iter().collect();
+                            self.pos += 4;
+                            char::from_u32(u32::from_str_radix(&hex, 16).ok()?)?
+                        }
+                        c => c,
+                    });
+                }
+                c => result.push(c),
+            }
+            self.pos += 1;
+        }
+    }
+
+    fn unquoted(&mut self) -> Option<String> {
+        let start = self.pos;
+        while self
+            .peek()
+            .is_some_and(|c| !matches!(c, ',' | ':' | '{' | '}' | '[' | ']' | '\n' | '\r'))
+        {
+            self.pos += 1;
+        }
+        let token: String = self.chars[start..self.pos].iter().collect();
+        let token = token.trim();
+        (!token.is_empty()).then(|| token.to_string())
+    }
+}
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use pretty_assertions::assert_eq;
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn test_parse_minimal_json() {
+        assert_eq!(
+            parse_minimal_json(
+                r#"{waves:true,waveSpacing:1800.5,"quoted key":"a, b",spawns:[{type:dagger,end:10},{type:flare}],name:My Map,n:null,e:"A\n"}"#
+            ),
+            Some(json!({
+                "waves": true,
+                "waveSpacing": 1800.5,
+                "quoted key": "a, b",
+                "spawns": [{"type": "dagger", "end": 10}, {"type": "flare"}],
+                "name": "My Map",
+                "n": null,
+                "e": "A\n",
+            }))
+        );
+        assert_eq!(parse_minimal_json("{a:1"), None);
+        assert_eq!(parse_minimal_json("{a:1} x"), None);
+    }
+
+    #[test]
+    fn test_rules_parse() {
+        assert_eq!(
+            Rules::parse(
+                "{waves:true,waveTimer:false,winWave:30,disableWorldProcessors:true,spawns:[]}"
+            ),
+            Some(Rules {
+                waves: true,
+                wave_timer: false,
+                win_wave: 30,
+                disable_world_processors: true,
+                ..Default::default()
+            })
+        );
+        assert_eq!(Rules::parse("{}"), Some(Rules::default()));
+        assert_eq!(Rules::parse("{waves:maybe}"), None);
+    }
+}
@@ -0,0 +1,908 @@
+use std::{
+    collections::HashSet,
+    fmt, format, fs, io, mem,
+    path::{Component, Path, PathBuf},
+    prelude::rust_2024::*,
+    vec::Vec,
+};
+
+use base64::prelude::*;
+use indexmap::IndexMap;
+use serde::{
+    Deserialize, Deserializer, Serialize, Serializer,
+    de::{MapAccess, Visitor},
+};
+use thiserror::Error;
+
+use super::schematics::is_processor;
+use crate::types::{
+    ContentID, ContentType, LAccess, Object, PackedPoint2, Point2, ProcessorConfig,
+    ProcessorLinkConfig, Schematic, SchematicTile, Team, UnitCommand, Vec2, content,
+};
+
+impl Schematic {
+    /// Serializes this schematic into a human-readable JSON format, intended to be stored in version control.
+    ///
+    /// Processor configs are stored as code and links, with one array element per line of code. Content is stored by name where possible. Converting the result back with [`Schematic::from_json`] produces an identical schematic, except that processor configs may be recompressed differently.
+    pub fn to_json(&self) -> Result<String, SchematicJsonError> {
+        Ok(serde_json::to_string_pretty(&SchematicJson::from(self))?)
+    }
+
+    /// Parses a schematic from the format produced by [`Schematic::to_json`].
+    pub fn from_json(s: &str) -> Result<Self, SchematicJsonError> {
+        serde_json::from_str::<SchematicJson>(s)?.try_into()
+    }
+
+    /// Writes this schematic to a directory as a manifest plus one `.mlog` file per processor, so that the code of logic-heavy schematics can be reviewed in version control.
+    ///
+    /// The manifest (`manifest.json`) uses the same format as [`Schematic::to_json`], except that each processor's code is replaced with the name of the file containing it. The files are named after each processor's block and position, eg. `micro-processor_3_-1.mlog`. Use [`Schematic::from_extracted_processors`] to rebuild the schematic.
+    pub fn extract_processors(&self, dir: impl AsRef<Path>) -> Result<(), SchematicJsonError> {
+        let dir = dir.as_ref();
+        fs::create_dir_all(dir).map_err(|e| SchematicJsonError::file(dir, e))?;
+
+        let mut json = SchematicJson::from(self);
+        let mut files = HashSet::new();
+        for (i, tile) in json.tiles.iter_mut().enumerate() {
+            let Some(ConfigJson::Processor(processor)) = &mut tile.config else {
+                continue;
+            };
+
+            let mut file = format!("{}_{}_{}.mlog", tile.block, tile.x, tile.y);
+            // only possible if multiple tiles are at the same position
+            if files.contains(&file) {
+                file = format!("{}_{}_{}_{i}.mlog", tile.block, tile.x, tile.y);
+            }
+
+            let path = dir.join(&file);
+            fs::write(&path, processor.code.join("\n"))
+                .map_err(|e| SchematicJsonError::file(&path, e))?;
+
+            let links = mem::take(&mut processor.links);
+            files.insert(file.clone());
+            tile.config = Some(ConfigJson::ProcessorFile(ProcessorFileJson { file, links }));
+        }
+
+        let path = dir.join(PROCESSOR_MANIFEST);
+        fs::write(&path, serde_json::to_string_pretty(&json)? + "\n")
+            .map_err(|e| SchematicJsonError::file(&path, e))
+    }
+
+    /// Rebuilds a schematic from a directory written by [`Schematic::extract_processors`].
+    pub fn from_extracted_processors(dir: impl AsRef<Path>) -> Result<Self, SchematicJsonError> {
+        let dir = dir.as_ref();
+        let read = |path: PathBuf| {
+            fs::read_to_string(&path).map_err(|e| SchematicJsonError::file(&path, e))
+        };
+
+        let mut json: SchematicJson = serde_json::from_str(&read(dir.join(PROCESSOR_MANIFEST))?)?;
+        for tile in &mut json.tiles {
+            let Some(ConfigJson::ProcessorFile(processor)) = &mut tile.config else {
+                continue;
+            };
+
+            // don't let the manifest read files outside of its directory
+            let file = Path::new(&processor.file);
+            if file.is_absolute()
+                || file.components().any(|component| {
+                    matches!(
+                        component,
+                        Component::ParentDir | Component::RootDir | Component::Prefix(_)
+                    )
+                })
+            {
+                return Err(SchematicJsonError::UnsafeProcessorFile(
+                    processor.file.clone(),
+                ));
+            }
+
+            let code = read(dir.join(file))?;
+            let links = mem::take(&mut processor.links);
+            tile.config = Some(ConfigJson::Processor(ProcessorJson {
+                code: code.split('\n').map(|s| s.to_string()).collect(),
+                links,
+            }));
+        }
+
+        json.try_into()
+    }
+}
+
+/// The name of the manifest written by [`Schematic::extract_processors`].
+const PROCESSOR_MANIFEST: &str = "manifest.json";
+
+#[derive(Error, Debug)]
+pub enum SchematicJsonError {
+    #[error("invalid schematic JSON: {0}")]
+    Json(#[from] serde_json::Error),
+
+    #[error("unknown {type_:?} content: {name}")]
+    UnknownContent { type_: ContentType, name: String },
+
+    #[error("content must have a name or an id")]
+    MissingContentID,
+
+    #[error("failed to decode byte array")]
+    BadByteArray(#[from] base64::DecodeError),
+
+    #[error("failed to encode processor config")]
+    BadProcessorConfig(#[from] binrw::Error),
+
+    #[error("processor code in {0} can only be loaded with Schematic::from_extracted_processors")]
+    UnresolvedProcessorFile(String),
+
+    #[error("processor file {0:?} must be a relative path inside the manifest's directory")]
+    UnsafeProcessorFile(String),
+
+    #[error("failed to access {}", path.display())]
+    File {
+        path: PathBuf,
+        #[source]
+        source: io::Error,
+    },
+}
+
+impl SchematicJsonError {
+    fn file(path: &Path, source: io::Error) -> Self {
+        Self::File {
+            path: path.to_path_buf(),
+            source,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct SchematicJson {
+    /// Excludes `labels`, which is regenerated from [`Self::labels`].
+    #[serde(default, with = "ordered_map")]
+    tags: IndexMap<String, String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    labels: Vec<String>,
+    tiles: Vec<TileJson>,
+}
+
+impl From<&Schematic> for SchematicJson {
+    fn from(schem: &Schematic) -> Self {
+        Self {
+            tags: schem
+                .tags
+                .iter()
+                .filter(|(key, _)| key.as_str() != "labels")
+                .map(|(key, value)| (key.to_string(), value.to_string()))
+                .collect(),
+            labels: schem.labels.clone(),
+            tiles: schem.tiles().iter().map(TileJson::from).collect(),
+        }
+    }
+}
+
+impl TryFrom<SchematicJson> for Schematic {
+    type Error = SchematicJsonError;
+
+    fn try_from(value: SchematicJson) -> Result<Self, Self::Error> {
+        let mut schem = Schematic::new();
+
+        schem.tags = value
+            .tags
+            .into_iter()
+            .map(|(key, value)| (key.into(), value.into()))
+            .collect();
+        // Schematic::read keeps the labels tag, so add it back to make sure the schematics are equal
+        schem.tags.insert(
+            "labels".into(),
+            serde_json::to_string(&value.labels)?.into(),
+        );
+        schem.labels = value.labels;
+
+        for tile in value.tiles {
+            schem.add_tile(tile.try_into()?);
+        }
+
+        Ok(schem)
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct TileJson {
+    block: String,
+    x: i16,
+    y: i16,
+    #[serde(default, skip_serializing_if = "is_zero")]
+    rotation: i8,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    config: Option<ConfigJson>,
+}
+
+fn is_zero(value: &i8) -> bool {
+    *value == 0
+}
+
+impl From<&SchematicTile> for TileJson {
+    fn from(tile: &SchematicTile) -> Self {
+        let config = match &tile.config {
+            config @ Object::ByteArray { .. } if is_processor(&tile.block) => {
+                match ProcessorConfig::parse(config) {
+                    Ok(processor) => Some(ConfigJson::Processor(processor.into())),
+                    Err(_) => ConfigJson::from_object(config),
+                }
+            }
+            config => ConfigJson::from_object(config),
+        };
+
+        Self {
+            block: tile.block.clone(),
+            x: tile.position.x,
+            y: tile.position.y,
+            rotation: tile.rotation,
+            config,
+        }
+    }
+}
+
+impl TryFrom<TileJson> for SchematicTile {
+    type Error = SchematicJsonError;
+
+    fn try_from(value: TileJson) -> Result<Self, Self::Error> {
+        Ok(Self {
+            block: value.block,
+            position: PackedPoint2::new(value.x, value.y),
+            config: ConfigJson::to_object(value.config)?,
+            rotation: value.rotation,
+        })
+    }
+}
+
+/// The JSON representation of a non-null [`Object`].
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+enum ConfigJson {
+    /// A processor config, only used for processor blocks.
+    Processor(ProcessorJson),
+    /// A processor config with the code stored in a separate file, only used by [`Schematic::extract_processors`].
+    ProcessorFile(ProcessorFileJson),
+    Int(i32),
+    Long(i64),
+    Float(f32),
+    String(Option<String>),
+    Content(ContentJson),
+    IntSeq(Vec<i32>),
+    Point2([i32; 2]),
+    Point2Array(Vec<[i16; 2]>),
+    TechNode(ContentJson),
+    Bool(bool),
+    Double(f64),
+    Building(i32),
+    LAccess(LAccess),
+    /// Base64-encoded bytes.
+    ByteArray(String),
+    LegacyUnitCommand(i8),
+    BoolArray(Vec<bool>),
+    Unit(i32),
+    Vec2Array(Vec<[f32; 2]>),
+    Vec2([f32; 2]),
+    Team(u8),
+    IntArray(Vec<i32>),
+    ObjectArray(Vec<Option<ConfigJson>>),
+    UnitCommand(UnitCommand),
+}
+
+impl ConfigJson {
+    #[allow(deprecated)]
+    fn from_object(value: &Object) -> Option<Self> {
+        Some(match value {
+            Object::Null => return None,
+            Object::Int(v) => Self::Int(*v),
+            Object::Long(v) => Self::Long(*v),
+            Object::Float(v) => Self::Float(*v),
+            Object::String(v) => Self::String(v.as_ref().map(|s| s.to_string())),
+            Object::Content(v) => Self::Content((*v).into()),
+            Object::IntSeq { values } => Self::IntSeq(values.clone()),
+            Object::Point2(v) => Self::Point2([v.x, v.y]),
+            Object::Point2Array { values } => {
+                Self::Point2Array(values.iter().map(|p| [p.x, p.y]).collect())
+            }
+            Object::TechNode(v) => Self::TechNode((*v).into()),
+            Object::Bool(v) => Self::Bool(*v),
+            Object::Double(v) => Self::Double(*v),
+            Object::Building(v) => Self::Building(*v),
+            Object::LAccess(v) => Self::LAccess(*v),
+            Object::ByteArray { values } => Self::ByteArray(BASE64_STANDARD.encode(values)),
+            Object::LegacyUnitCommand { value } => Self::LegacyUnitCommand(*value),
+            Object::BoolArray { values } => Self::BoolArray(values.clone()),
+            Object::Unit { id } => Self::Unit(*id),
+            Object::Vec2Array { values } => {
+                Self::Vec2Array(values.iter().map(|v| [v.x, v.y]).collect())
+            }
+            Object::Vec2(v) => Self::Vec2([v.x, v.y]),
+            Object::Team(v) => Self::Team(v.0),
+            Object::IntArray { values } => Self::IntArray(values.clone()),
+            Object::ObjectArray { values } => {
+                Self::ObjectArray(values.iter().map(Self::from_object).collect())
+            }
+            Object::UnitCommand(v) => Self::UnitCommand(*v),
+        })
+    }
+
+    #[allow(deprecated)]
+    fn to_object(value: Option<Self>) -> Result<Object, SchematicJsonError> {
+        let Some(value) = value else {
+            return Ok(Object::Null);
+        };
+        Ok(match value {
+            Self::Processor(v) => ProcessorConfig::from(v).to_object()?,
+            Self::ProcessorFile(v) => {
+                return Err(SchematicJsonError::UnresolvedProcessorFile(v.file));
+            }
+            Self::Int(v) => Object::Int(v),
+            Self::Long(v) => Object::Long(v),
+            Self::Float(v) => Object::Float(v),
+            Self::String(v) => v.into(),
+            Self::Content(v) => Object::Content(v.try_into()?),
+            Self::IntSeq(values) => Object::IntSeq { values },
+            Self::Point2([x, y]) => Object::Point2(Point2::new(x, y)),
+            Self::Point2Array(values) => Object::Point2Array {
+                values: values
+                    .into_iter()
+                    .map(|[x, y]| PackedPoint2::new(x, y))
+                    .collect(),
+            },
+            Self::TechNode(v) => Object::TechNode(v.try_into()?),
+            Self::Bool(v) => Object::Bool(v),
+            Self::Double(v) => Object::Double(v),
+            Self::Building(v) => Object::Building(v),
+            Self::LAccess(v) => Object::LAccess(v),
+            Self::ByteArray(v) => Object::ByteArray {
+                values: BASE64_STANDARD.decode(v)?,
+            },
+            Self::LegacyUnitCommand(value) => Object::LegacyUnitCommand { value },
+            Self::BoolArray(values) => Object::BoolArray { values },
+            Self::Unit(id) => Object::Unit { id },
+            Self::Vec2Array(values) => Object::Vec2Array {
+                values: values.into_iter().map(|[x, y]| Vec2 { x, y }).collect(),
+            },
+            Self::Vec2([x, y]) => Object::Vec2(Vec2 { x, y }),
+            Self::Team(v) => Object::Team(Team(v)),
+            Self::IntArray(values) => Object::IntArray { values },
+            Self::ObjectArray(values) => Object::ObjectArray {
+                values: values
+                    .into_iter()
+                    .map(Self::to_object)
+                    .collect::<Result<_, _>>()?,
+            },
+            Self::UnitCommand(v) => Object::UnitCommand(v),
+        })
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ProcessorJson {
+    /// The processor's code, split into lines so that it's readable in diffs.
+    code: Vec<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    links: Vec<LinkJson>,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ProcessorFileJson {
+    /// The path of the file containing the processor's code, relative to the manifest.
+    file: String,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    links: Vec<LinkJson>,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct LinkJson {
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    name: String,
+    x: i16,
+    y: i16,
+}
+
+impl From<ProcessorConfig> for ProcessorJson {
+    fn from(value: ProcessorConfig) -> Self {
+        Self {
+            code: value.code.split('\n').map(|s| s.to_string()).collect(),
+            links: value
+                .links
+                .into_iter()
+                .map(|link| LinkJson {
+                    name: link.name.into(),
+                    x: link.x,
+                    y: link.y,
+                })
+                .collect(),
+        }
+    }
+}
+
+impl From<ProcessorJson> for ProcessorConfig {
+    fn from(value: ProcessorJson) -> Self {
+        Self {
+            code: value.code.join("\n"),
+            links: value
+                .links
+                .into_iter()
+                .map(|link| ProcessorLinkConfig {
+                    name: link.name.into(),
+                    x: link.x,
+                    y: link.y,
+                })
+                .collect(),
+        }
+    }
+}
+
+/// A content ID, which is stored by name if the content exists in the current game version.
+#[derive(Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ContentJson {
+    #[serde(rename = "type")]
+    type_: ContentType,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    name: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    id: Option<i16>,
+}
+
+impl From<ContentID> for ContentJson {
+    fn from(ContentID { type_, id }: ContentID) -> Self {
+        let id_ = id as i32;
+        let name = match type_ {
+            ContentType::Block => content::blocks::VALUES
+                .iter()
+                .find(|v| v.id == id_)
+                .map(|v| v.name.as_str()),
+            ContentType::Item => content::items::VALUES
+                .iter()
+                .find(|v| v.id == id_)
+                .map(|v| v.name.as_str()),
+            ContentType::Liquid => content::liquids::VALUES
+                .iter()
+                .find(|v| v.id == id_)
+                .map(|v| v.name.as_str()),
+            ContentType::Unit => content::units::VALUES
+                .iter()
+                .find(|v| v.id == id_)
+                .map(|v| v.name.as_str()),
+            _ => None,
+        };
+        Self {
+            type_,
+            name: name.map(|s| s.to_string()),
+            id: name.is_none().then_some(id),
+        }
+    }
+}
+
+impl TryFrom<ContentJson> for ContentID {
+    type Error = SchematicJsonError;
+
+    fn try_from(ContentJson { type_, name, id }: ContentJson) -> Result<Self, Self::Error> {
+        let Some(name) = name else {
+            return id
+                .map(|id| ContentID { type_, id })
+                .ok_or(SchematicJsonError::MissingContentID);
+        };
+
+        let id = match type_ {
+            ContentType::Block => content::blocks::FROM_NAME.get(name.as_str()).map(|v| v.id),
+            ContentType::Item => content::items::FROM_NAME.get(name.as_str()).map(|v| v.id),
+            ContentType::Liquid => content::liquids::FROM_NAME.get(name.as_str()).map(|v| v.id),
+            ContentType::Unit => content::units::FROM_NAME.get(name.as_str()).map(|v| v.id),
+            _ => None,
+        };
+
+        match id.and_then(|id| i16::try_from(id).ok()) {
+            Some(id) => Ok(ContentID { type_, id }),
+            None => Err(SchematicJsonError::UnknownContent { type_, name }),
+        }
+    }
+}
+
+/// (De)serializes an [`IndexMap`] as a map, preserving the order of its keys.
+mod ordered_map {
+    use super::*;
+
+    pub fn serialize<S>(map: &IndexMap<String, String>, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serializer.collect_map(map)
+    }
+
+    pub fn deserialize<'de, D>(deserializer: D) -> Result<IndexMap<String, String>, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        struct OrderedMapVisitor;
+
+        impl<'de> Visitor<'de> for OrderedMapVisitor {
+            type Value = IndexMap<String, String>;
+
+            fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+                formatter.write_str("a map of strings")
+            }
+
+            fn visit_map<A>(self, mut access: A) -> Result<Self::Value, A::Error>
+            where
+                A: MapAccess<'de>,
+            {
+                let mut map = IndexMap::new();
+                while let Some((key, value)) = access.next_entry()? {
+                    map.insert(key, value);
+                }
+                Ok(map)
+            }
+        }
+
+        deserializer.deserialize_map(OrderedMapVisitor)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::error::Error;
+
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    type TestResult = Result<(), Box<dyn Error>>;
+
+    fn tile(block: &str, x: i16, y: i16, config: Object, rotation: i8) -> SchematicTile {
+        SchematicTile {
+            block: block.to_string(),
+            position: PackedPoint2 { x, y },
+            config,
+            rotation,
+        }
+    }
+
+    fn assert_roundtrip(schem: &Schematic) -> TestResult {
+        assert_eq!(Schematic::from_json(&schem.to_json()?)?, *schem);
+        Ok(())
+    }
+
+    #[test]
+    fn test_roundtrip_base64() -> TestResult {
+        for data in [
+            "bXNjaAF4nBXIUQqAIBAA0dkKgzpi9GEqtGBbpNH1y/l5MAjSM5g/EuNjzcgcUwm3XlVPA1z2W8qFblkFV16tYf+30JrgAzvqDyA=",
+            "bXNjaAF4nDWIOw6AIBTACigODh7QOKC8gQTBAN7fX+zSphiUoUtuF4YzPfaMXupWwtFCToCNbpVY0fOisDWXJuXehh8F/Vf6rYkLXaIPQw==",
+        ] {
+            assert_roundtrip(&Schematic::read_base64(data)?)?;
+        }
+        Ok(())
+    }
+
+    #[test]
+    #[allow(deprecated)]
+    fn test_roundtrip_configs() -> TestResult {
+        let mut schem = Schematic::new();
+        schem.tags.insert("name".into(), "configs".into());
+        schem.tags.insert("description".into(), "a\nb".into());
+        schem.tags.insert("labels".into(), r#"["x"]"#.into());
+        schem.labels = vec!["x".to_string()];
+
+        let configs = vec![
+            Object::Int(-1),
+            Object::Long(i64::MAX),
+            Object::Float(0.1),
+            Object::String(None),
+            "".to_string().into(),
+            ContentID {
+                type_: ContentType::Item,
+                id: 1,
+            }
+            .into(),
+            // no such item
+            ContentID {
+                type_: ContentType::Item,
+                id: 1000,
+            }
+            .into(),
+            Object::TechNode(ContentID {
+                type_: ContentType::Planet,
+                id: 2,
+            }),
+            Object::IntSeq { values: vec![1, 2] },
+            Point2::new(-3, 4).into(),
+            vec![PackedPoint2::new(1, 0), PackedPoint2::new(0, -1)].into(),
+            Object::Bool(true),
+            Object::Double(-0.5),
+            Object::Building(123),
+            LAccess::Enabled.into(),
+            vec![0u8, 255].into(),
+            Object::LegacyUnitCommand { value: 2 },
+            vec![true, false].into(),
+            Object::Unit { id: 7 },
+            vec![Vec2 { x: 1.5, y: -2. }].into(),
+            Vec2 { x: 0.25, y: 8. }.into(),
+            Team::CRUX.into(),
+            Object::IntArray { values: vec![3] },
+            vec![Object::Null, Object::Int(1)].into(),
+            UnitCommand::Mine.into(),
+        ];
+        for (i, config) in configs.into_iter().enumerate() {
+            schem.add_tile(tile("sorter", i as i16, 0, config, (i % 4) as i8));
+        }
+
+        assert_roundtrip(&schem)
+    }
+
+    #[test]
+    fn test_roundtrip_processor() -> TestResult {
+        let schem = Schematic::new()
+            .add_tile(tile(
+                "micro-processor",
+                0,
+                0,
+                ProcessorConfig {
+                    code: "print \"a\"\n\nprintflush message1\n".to_string(),
+                    links: vec![ProcessorLinkConfig {
+                        name: "message1".into(),
+                        x: 1,
+                        y: 0,
+                    }],
+                }
+                .to_object()?,
+                0,
+            ))
+            .add_tile(tile("message", 1, 0, "hi".to_string().into(), 0))
+            // not a valid processor config, so it should be stored as bytes
+            .add_tile(tile("micro-processor", 2, 0, vec![1u8, 2, 3].into(), 0))
+            .to_owned();
+
+        let json = schem.to_json()?;
+        let got = Schematic::from_json(&json)?;
+        assert_eq!(
+            ProcessorConfig::parse(&got.tiles()[0].config)?,
+            ProcessorConfig::parse(&schem.tiles()[0].config)?
+        );
+        assert_eq!(got.tiles()[1..], schem.tiles()[1..]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_extract_processors() -> TestResult {
+        let processor = |code: &str, links| {
+            ProcessorConfig {
+                code: code.to_string(),
+                links,
+            }
+            .to_object()
+        };
+        let schem = Schematic::new()
+            .add_tile(tile(
+                "micro-processor",
+                0,
+                -1,
+                processor(
+                    "print \"a\"\n\nprintflush display\n",
+                    vec![ProcessorLinkConfig {
+                        name: "display".into(),
+                        x: 2,
+                        y: 1,
+                    }],
+                )?,
+                0,
+            ))
+            .add_tile(tile("message", 2, 0, "hi".to_string().into(), 0))
+            .add_tile(tile(
+                "logic-processor",
+                3,
+                0,
+                processor("end", vec![ProcessorLinkConfig::unnamed(-1, 0)])?,
+                0,
+            ))
+            .to_owned();
+
+        let dir = std::env::temp_dir().join(format!("mindy-extract-{}", std::process::id()));
+        let result = (|| -> TestResult {
+            schem.extract_processors(&dir)?;
+
+            assert_eq!(
+                fs::read_to_string(dir.join("micro-processor_0_-1.mlog"))?,
+                "print \"a\"\n\nprintflush display\n"
+            );
+            assert_eq!(
+                fs::read_to_string(dir.join("logic-processor_3_0.mlog"))?,
+                "end"
+            );
+
+            let manifest = fs::read_to_string(dir.join(PROCESSOR_MANIFEST))?;
+            assert!(manifest.contains(r#""file": "micro-processor_0_-1.mlog""#));
+            assert!(manifest.contains(r#""name": "display""#));
+            assert!(matches!(
+                Schematic::from_json(&manifest),
+                Err(SchematicJsonError::UnresolvedProcessorFile(_))
+            ));
+
+            // edits to the code should be picked up when rebuilding
+            fs::write(dir.join("logic-processor_3_0.mlog"), "stop")?;
+
+            let got = Schematic::from_extracted_processors(&dir)?;
+            assert_eq!(got.tiles().len(), 3);
+            assert_eq!(
+                ProcessorConfig::parse(&got.tiles()[0].config)?,
+                ProcessorConfig::parse(&schem.tiles()[0].config)?
+            );
+            assert_eq!(got.tiles()[1], schem.tiles()[1]);
+            assert_eq!(
+                ProcessorConfig::parse(&got.tiles()[2].config)?,
+                ProcessorConfig {
+                    code: "stop".to_string(),
+                    links: vec![ProcessorLinkConfig::unnamed(-1, 0)],
+                }
+            );
+            Ok(())
+        })();
+        fs::remove_dir_all(&dir)?;
+        result
+    }
+
+    #[test]
+    fn test_extract_processors_unsafe_paths() -> TestResult {
+        let schem = Schematic::new()
+            .add_tile(tile(
+                "micro-processor",
+                0,
+                0,
+                ProcessorConfig::from_code("end").to_object()?,
+                0,
+            ))
+            .to_owned();
+
+        let dir = std::env::temp_dir().join(format!("mindy-unsafe-{}", std::process::id()));
+        let result = (|| -> TestResult {
+            schem.extract_processors(&dir)?;
+            let manifest = fs::read_to_string(dir.join(PROCESSOR_MANIFEST))?;
+
+            let check = |file: &str| -> TestResult {
+                fs::write(
+                    dir.join(PROCESSOR_MANIFEST),
+                    manifest.replace(
+                        r#""micro-processor_0_0.mlog""#,
+                        &serde_json::to_string(file)?,
+                    ),
+                )?;
+                assert!(
+                    matches!(
+                        Schematic::from_extracted_processors(&dir),
+                        Err(SchematicJsonError::UnsafeProcessorFile(got)) if got == file
+                    ),
+                    "{file:?} should be rejected"
+                );
+                Ok(())
+            };
+
+            // absolute
+            check(&std::env::current_dir()?.join("code.mlog").to_string_lossy())?;
+            // parent
+            check("../code.mlog")?;
+            check("code/../../code.mlog")?;
+            // root
+            check("/code.mlog")?;
+            // prefix
+            #[cfg(windows)]
+            check("C:code.mlog")?;
+
+            // subdirectories are still allowed
+            fs::create_dir(dir.join("code"))?;
+            fs::rename(
+                dir.join("micro-processor_0_0.mlog"),
+                dir.join("code/a.mlog"),
+            )?;
+            fs::write(
+                dir.join(PROCESSOR_MANIFEST),
+                manifest.replace("micro-processor_0_0.mlog", "code/a.mlog"),
+            )?;
+            Schematic::from_extracted_processors(&dir)?;
+            Ok(())
+        })();
+        fs::remove_dir_all(&dir)?;
+        result
+    }
+
+    #[test]
+    fn test_to_json() -> TestResult {
+        let schem = Schematic::read_base64(
+            "bXNjaAF4nDWIOw6AIBTACigODh7QOKC8gQTBAN7fX+zSphiUoUtuF4YzPfaMXupWwtFCToCNbpVY0fOisDWXJuXehh8F/Vf6rYkLXaIPQw==",
+        )?
+        .add_tile(tile(
+            "micro-processor",
+            3,
+            0,
+            ProcessorConfig {
+                code: "set x 1\nend".to_string(),
+                links: vec![ProcessorLinkConfig::unnamed(-1, 0)],
+            }
+            .to_object()?,
+            1,
+        ))
+        .to_owned();
+
+        assert_eq!(
+            schem.to_json()?,
+            r#"{
+  "tags": {
+    "name": "unnamed",
+    "description": ""
+  },
+  "tiles": [
+    {
+      "block": "sorter",
+      "x": 0,
+      "y": 0
+    },
+    {
+      "block": "sorter",
+      "x": 1,
+      "y": 0,
+      "config": {
+        "content": {
+          "type": "Item",
+          "name": "copper"
+        }
+      }
+    },
+    {
+      "block": "sorter",
+      "x": 2,
+      "y": 0,
+      "config": {
+        "content": {
+          "type": "Item",
+          "name": "pyratite"
+        }
+      }
+    },
+    {
+      "block": "micro-processor",
+      "x": 3,
+      "y": 0,
+      "rotation": 1,
+      "config": {
+        "processor": {
+          "code": [
+            "set x 1",
+            "end"
+          ],
+          "links": [
+            {
+              "x": -1,
+              "y": 0
+            }
+          ]
+        }
+      }
+    }
+  ]
+}"#
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_from_json_errors() {
+        assert!(matches!(
+            Schematic::from_json(
+                r#"{"tiles": [{"block": "sorter", "x": 0, "y": 0, "config": {"content": {"type": "Item", "name": "unobtainium"}}}]}"#
+            ),
+            Err(SchematicJsonError::UnknownContent { .. })
+        ));
+        assert!(matches!(
+            Schematic::from_json(
+                r#"{"tiles": [{"block": "sorter", "x": 0, "y": 0, "config": {"content": {"type": "Item"}}}]}"#
+            ),
+            Err(SchematicJsonError::MissingContentID)
+        ));
+        assert!(matches!(
+            Schematic::from_json(r#"{"tiles": [{"block": "sorter", "x": 0, "y": 0, "extra": 1}]}"#),
+            Err(SchematicJsonError::Json(_))
+        ));
+    }
+}
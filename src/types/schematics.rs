@@ -1,19 +1,18 @@
 use std::{
+    collections::HashMap,
     error::Error,
     format,
     io::{self, Cursor, Read, Seek},
-    ops::Sub,
     prelude::rust_2024::*,
     vec,
 };
 
 use base64::prelude::*;
-use binrw::{helpers::count, io::NoSeek, prelude::*};
+use binrw::{Endian, helpers::count, io::NoSeek, prelude::*};
 use flate2::{Compression, read::ZlibDecoder, write::ZlibEncoder};
 use indexmap::{IndexMap, IndexSet};
-use itertools::{Itertools, MinMaxResult};
 
-use crate::types::{JavaString, Object, PackedPoint2};
+use crate::types::{JavaString, Object, PackedPoint2, Point2, ProcessorConfig, content};
 
 #[binrw]
 #[brw(big, magic = b"msch\x01")]
@@ -21,13 +20,11 @@ use crate::types::{JavaString, Object, PackedPoint2};
 #[bw(map_stream = |s| NoSeek::new(ZlibEncoder::new(s, Compression::default())))]
 #[derive(Debug, Clone, PartialEq, Default)]
 pub struct Schematic {
-    // FIXME: ew
-    #[bw(map = |_| minmax_range::<_, i16>(tiles.iter().map(|t| t.position.x).minmax()) + 1)]
+    #[bw(try_map = |_: &i16| i16::try_from(self.size().0))]
     width: i16,
-    #[bw(map = |_| minmax_range::<_, i16>(tiles.iter().map(|t| t.position.y).minmax()) + 1)]
+    #[bw(try_map = |_: &i16| i16::try_from(self.size().1))]
     height: i16,
 
-    #[bw(assert(width <= 128 && height <= 128))] // FIXME: hack
     #[bw(try_calc = (tags.len() + if tags.contains_key("labels") { 0 } else { 1 }).try_into())]
     tags_count: i8,
     #[br(parse_with = count(tags_count as usize))]
@@ -38,16 +35,17 @@ pub struct Schematic {
     #[bw(ignore)]
     pub labels: Vec<String>,
 
-    #[bw(try_calc = blocks.len().try_into())]
+    // the block palette is only needed while reading, since it's generated from the tiles when writing
+    #[bw(try_calc = BlockPalette::from_tiles(tiles).0.len().try_into())]
     blocks_count: u8,
-    #[br(parse_with = count(blocks_count as usize))]
-    #[bw(map = |v| v.iter().cloned().collect_vec())]
-    blocks: IndexSet<JavaString>,
+    #[br(temp, parse_with = count(blocks_count as usize))]
+    #[bw(calc = BlockPalette::from_tiles(tiles))]
+    blocks: BlockPalette,
 
     #[bw(try_calc = tiles.len().try_into())]
     tiles_count: i32,
-    #[br(count = tiles_count, args { inner: (&blocks,) })]
-    #[bw(args(&self.blocks), assert(tiles.len() <= 128 * 128))]
+    #[br(count = tiles_count, args { inner: (&blocks.0,) })]
+    #[bw(args(&blocks.0))]
     tiles: Vec<SchematicTile>,
 }
 
@@ -71,17 +69,131 @@ impl Schematic {
         Ok(BASE64_STANDARD.encode(cur.into_inner()))
     }
 
+    /// Returns the width and height of this schematic, as read from the file or as of the last edit.
+    pub fn dimensions(&self) -> (i16, i16) {
+        (self.width, self.height)
+    }
+
+    /// Returns the width and height of the smallest rectangle containing every tile in this schematic.
+    pub fn size(&self) -> (i32, i32) {
+        match self.bounds() {
+            Some((min, max)) => (max.x - min.x + 1, max.y - min.y + 1),
+            None => (0, 0),
+        }
+    }
+
+    /// Returns the minimum and maximum points (inclusive) occupied by the tiles in this schematic, taking block sizes into account.
+    pub fn bounds(&self) -> Option<(Point2, Point2)> {
+        self.tiles
+            .iter()
+            .map(|tile| tile.bounds())
+            .reduce(|(min1, max1), (min2, max2)| {
+                (
+                    Point2::new(min1.x.min(min2.x), min1.y.min(min2.y)),
+                    Point2::new(max1.x.max(max2.x), max1.y.max(max2.y)),
+                )
+            })
+    }
+
     pub fn add_tile(&mut self, tile: SchematicTile) -> &mut Self {
-        self.blocks.insert(tile.block.clone().into());
         self.tiles.push(tile);
+        self.update_dimensions();
         self
     }
 
+    /// Removes and returns the tile at `index`, or returns `None` if the index is out of bounds.
+    ///
+    /// Links from other tiles to the removed tile are left unchanged.
+    pub fn remove_tile(&mut self, index: usize) -> Option<SchematicTile> {
+        if index >= self.tiles.len() {
+            return None;
+        }
+        let tile = self.tiles.remove(index);
+        self.update_dimensions();
+        Some(tile)
+    }
+
+    /// Moves the tile at `index` to a new position.
+    ///
+    /// Relative links (eg. processor links and power node lasers) to and from the moved tile are updated so that they still point to the same buildings.
+    ///
+    /// Returns `false` if the index is out of bounds.
+    pub fn move_tile(&mut self, index: usize, position: PackedPoint2) -> bool {
+        if index >= self.tiles.len() {
+            return false;
+        }
+        let old_positions = self.positions();
+        self.tiles[index].position = position;
+        self.remap_links(&old_positions, |point| point);
+        self.update_dimensions();
+        true
+    }
+
+    /// Moves every tile in this schematic by the given offset.
+    pub fn translate(&mut self, dx: i16, dy: i16) -> &mut Self {
+        for tile in &mut self.tiles {
+            tile.position.x += dx;
+            tile.position.y += dy;
+        }
+        self
+    }
+
+    /// Translates this schematic so that the bottom left corner of its bounding box is at `(0, 0)`.
+    pub fn normalize(&mut self) -> &mut Self {
+        if let Some((min, _)) = self.bounds() {
+            self.translate(-min.x as i16, -min.y as i16);
+        }
+        self.update_dimensions();
+        self
+    }
+
+    /// Rotates this schematic by 90 degrees counterclockwise (or clockwise if `clockwise` is true), then normalizes it.
+    ///
+    /// Block rotations and relative links are rotated as well.
+    pub fn rotate(&mut self, clockwise: bool) -> &mut Self {
+        if clockwise {
+            self.transform(|x, y| (y, -x), |rotation| (rotation + 3) % 4)
+        } else {
+            self.transform(|x, y| (-y, x), |rotation| (rotation + 1) % 4)
+        }
+    }
+
+    /// Mirrors this schematic horizontally (if `x` is true) or vertically, then normalizes it.
+    ///
+    /// Block rotations and relative links are mirrored as well.
+    pub fn mirror(&mut self, x: bool) -> &mut Self {
+        if x {
+            self.transform(
+                |x, y| (-x, y),
+                |rotation| {
+                    if rotation % 2 == 0 {
+                        (rotation + 2) % 4
+                    } else {
+                        rotation
+                    }
+                },
+            )
+        } else {
+            self.transform(
+                |x, y| (x, -y),
+                |rotation| {
+                    if rotation % 2 == 1 {
+                        (rotation + 2) % 4
+                    } else {
+                        rotation
+                    }
+                },
+            )
+        }
+    }
+
     pub fn tiles(&self) -> &Vec<SchematicTile> {
         &self.tiles
     }
 
-    /// WARNING: Inserting or removing tiles via this method will cause the serialized schematic to be invalid.
+    /// Returns a mutable reference to the tiles in this schematic.
+    ///
+    /// The block palette is generated when writing, so tiles may be freely added or removed. Links between tiles are not updated; use [`Self::move_tile`] to move a tile while keeping its links intact.
     pub fn tiles_mut(&mut self) -> &mut Vec<SchematicTile> {
         &mut self.tiles
     }
@@ -89,6 +201,114 @@ impl Schematic {
     pub fn tile_mut(&mut self, index: usize) -> Option<&mut SchematicTile> {
         self.tiles.get_mut(index)
     }
+
+    fn update_dimensions(&mut self) {
+        let (width, height) = self.size();
+        self.width = width.try_into().unwrap_or(i16::MAX);
+        self.height = height.try_into().unwrap_or(i16::MAX);
+    }
+
+    fn positions(&self) -> Vec<PackedPoint2> {
+        self.tiles.iter().map(|tile| tile.position).collect()
+    }
+
+    /// Applies a linear transformation to the centre of every tile, then normalizes the schematic.
+    fn transform(
+        &mut self,
+        point: impl Fn(i32, i32) -> (i32, i32),
+        rotation: impl Fn(i8) -> i8,
+    ) -> &mut Self {
+        let old_positions = self.positions();
+
+        for tile in &mut self.tiles {
+            // transform the centre of the block, using doubled coordinates so that even-sized blocks stay on the grid
+            let offset = (tile.size() + 1) % 2;
+            let (x, y) = point(
+                2 * tile.position.x as i32 + offset,
+                2 * tile.position.y as i32 + offset,
+            );
+            tile.position = PackedPoint2 {
+                x: ((x - offset) / 2) as i16,
+                y: ((y - offset) / 2) as i16,
+            };
+            tile.rotation = rotation(tile.rotation.rem_euclid(4));
+        }
+
+        self.remap_links(&old_positions, |p| {
+            let (x, y) = point(2 * p.x as i32, 2 * p.y as i32);
+            PackedPoint2 {
+                x: (x / 2) as i16,
+                y: (y / 2) as i16,
+            }
+        });
+
+        self.normalize()
+    }
+
+    /// Updates the relative links of every tile after the tiles have been moved.
+    ///
+    /// Links that pointed to a tile's old position are changed to point to its new position. Other links are moved using `map_point`.
+    fn remap_links(
+        &mut self,
+        old_positions: &[PackedPoint2],
+        map_point: impl Fn(PackedPoint2) -> PackedPoint2,
+    ) {
+        let moved: HashMap<_, _> = old_positions
+            .iter()
+            .zip(self.tiles.iter().map(|tile| tile.position))
+            .map(|(&old, new)| (old, new))
+            .collect();
+
+        let remap = |old_origin: PackedPoint2, new_origin: PackedPoint2, x: i32, y: i32| {
+            let target = PackedPoint2 {
+                x: (old_origin.x as i32 + x) as i16,
+                y: (old_origin.y as i32 + y) as i16,
+            };
+            let target = moved
+                .get(&target)
+                .copied()
+                .unwrap_or_else(|| map_point(target));
+            (
+                target.x as i32 - new_origin.x as i32,
+                target.y as i32 - new_origin.y as i32,
+            )
+        };
+
+        for (tile, &old_origin) in self.tiles.iter_mut().zip(old_positions) {
+            let new_origin = tile.position;
+            match &mut tile.config {
+                Object::Point2(point) => {
+                    (point.x, point.y) = remap(old_origin, new_origin, point.x, point.y);
+                }
+                Object::Point2Array { values } => {
+                    for point in values {
+                        let (x, y) = remap(old_origin, new_origin, point.x as i32, point.y as i32);
+                        *point = PackedPoint2::new(x as i16, y as i16);
+                    }
+                }
+                config @ Object::ByteArray { .. } if is_processor(&tile.block) => {
+                    let Ok(mut processor) = ProcessorConfig::parse(config) else {
+                        continue;
+                    };
+                    for link in &mut processor.links {
+                        let (x, y) = remap(old_origin, new_origin, link.x as i32, link.y as i32);
+                        (link.x, link.y) = (x as i16, y as i16);
+                    }
+                    if let Ok(value) = processor.to_object() {
+                        *config = value;
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+}
+
+pub(crate) fn is_processor(block: &str) -> bool {
+    matches!(
+        block,
+        "micro-processor" | "logic-processor" | "hyper-processor" | "world-processor"
+    )
 }
 
 enum ResultReader<R> {
@@ -125,14 +345,38 @@ where
     }
 }
 
-fn minmax_range<T, R>(mm: MinMaxResult<T>) -> R
-where
-    T: Sub + Clone,
-    R: Default + From<<T as Sub>::Output>,
-{
-    match mm.into_option() {
-        Some((min, max)) => (max - min).into(),
-        None => R::default(),
+/// The names of the blocks used in a schematic, which tiles refer to by index.
+struct BlockPalette(IndexSet<JavaString>);
+
+impl BlockPalette {
+    /// Returns the blocks used by some tiles, in the order that they first appear.
+    fn from_tiles(tiles: &[SchematicTile]) -> Self {
+        tiles
+            .iter()
+            .map(|tile| JavaString::from(tile.block.as_str()))
+            .collect()
+    }
+}
+
+impl FromIterator<JavaString> for BlockPalette {
+    fn from_iter<T: IntoIterator<Item = JavaString>>(iter: T) -> Self {
+        Self(iter.into_iter().collect())
+    }
+}
+
+impl BinWrite for BlockPalette {
+    type Args<'a> = ();
+
+    fn write_options<W: io::Write + Seek>(
+        &self,
+        writer: &mut W,
+        endian: Endian,
+        args: Self::Args<'_>,
+    ) -> BinResult<()> {
+        for block in &self.0 {
+            block.write_options(writer, endian, args)?;
+        }
+        Ok(())
     }
 }
 
@@ -174,14 +418,46 @@ pub struct SchematicTile {
     pub rotation: i8,
 }
 
+impl SchematicTile {
+    /// Returns the size of this tile's block, or 1 if the block is unknown.
+    pub fn size(&self) -> i32 {
+        self.content_block().map_or(1, |block| block.size as i32)
+    }
+
+    /// Returns the bottom left point occupied by this tile.
+    ///
+    /// Like in Mindustry, a tile's position is the center of its block, rounded down and to the left for even-sized blocks.
+    pub fn corner(&self) -> Point2 {
+        let offset = self
+            .content_block()
+            .map_or(0, |block| block.center_offset() as i32);
+        Point2::new(
+            self.position.x as i32 - offset,
+            self.position.y as i32 - offset,
+        )
+    }
+
+    /// Returns the minimum and maximum points (inclusive) occupied by this tile.
+    pub fn bounds(&self) -> (Point2, Point2) {
+        let size = self.size();
+        let min = self.corner();
+        let max = Point2::new(min.x + size - 1, min.y + size - 1);
+        (min, max)
+    }
+
+    fn content_block(&self) -> Option<&'static content::Block> {
+        content::blocks::FROM_NAME.get(self.block.as_str()).copied()
+    }
+}
+
 fn block_to_index(
     block: &String,
     blocks: &IndexSet<JavaString>,
 ) -> Result<i8, Box<dyn Error + Send + Sync>> {
-    blocks
+    let index = blocks
         .get_index_of(block)
-        .map(|i| i as i8)
-        .ok_or_else(|| format!("unknown block: {block}").into())
+        .ok_or_else(|| format!("unknown block: {block}"))?;
+    Ok(i8::try_from(index)?)
 }
 
 fn index_to_block(
@@ -202,7 +478,7 @@ mod tests {
     use velcro::map_iter_from;
 
     use super::*;
-    use crate::types::{ContentID, ContentType};
+    use crate::types::{ContentID, ContentType, ProcessorLinkConfig};
 
     type TestResult = Result<(), Box<dyn Error>>;
 
@@ -430,4 +706,164 @@ mod tests {
         });
         Ok(())
     }
+
+    fn tile(block: &str, x: i16, y: i16, config: Object, rotation: i8) -> SchematicTile {
+        SchematicTile {
+            block: block.to_string(),
+            position: PackedPoint2 { x, y },
+            config,
+            rotation,
+        }
+    }
+
+    fn linked_schematic() -> Schematic {
+        Schematic::new()
+            .add_tile(tile(
+                "logic-processor",
+                0,
+                0,
+                ProcessorConfig {
+                    code: "print 1".to_string(),
+                    links: vec![ProcessorLinkConfig::unnamed(2, 0)],
+                }
+                .to_object()
+                .unwrap(),
+                0,
+            ))
+            .add_tile(tile("switch", 2, 0, Object::Null, 0))
+            .add_tile(tile("conveyor", 3, 0, Object::Null, 0))
+            .to_owned()
+    }
+
+    fn processor_links(schem: &Schematic) -> Vec<(i16, i16)> {
+        ProcessorConfig::parse(&schem.tiles()[0].config)
+            .unwrap()
+            .links
+            .iter()
+            .map(|link| (link.x, link.y))
+            .collect()
+    }
+
+    fn positions(schem: &Schematic) -> Vec<(i16, i16, i8)> {
+        schem
+            .tiles()
+            .iter()
+            .map(|tile| (tile.position.x, tile.position.y, tile.rotation))
+            .collect()
+    }
+
+    #[test]
+    fn test_size() {
+        let mut schem = Schematic::new()
+            .add_tile(tile("hyper-processor", 1, 1, Object::Null, 0))
+            .add_tile(tile("switch", 3, 0, Object::Null, 0))
+            .to_owned();
+        assert_eq!(schem.size(), (4, 3));
+        assert_eq!(schem.dimensions(), (4, 3));
+
+        schem.remove_tile(0);
+        assert_eq!(schem.size(), (1, 1));
+        assert_eq!(schem.remove_tile(1), None);
+    }
+
+    #[test]
+    fn test_palette_gc() -> TestResult {
+        let mut schem = linked_schematic();
+        schem.remove_tile(0);
+        schem
+            .tiles_mut()
+            .push(tile("sorter", 5, 0, Object::Null, 0));
+
+        let got = Schematic::read_base64(schem.write_base64()?)?;
+        assert_eq!(got.tiles(), schem.tiles());
+        assert_eq!(got.dimensions(), (4, 1));
+        Ok(())
+    }
+
+    #[test]
+    fn test_write_large() -> TestResult {
+        let schem = Schematic::new()
+            .add_tile(tile("switch", 0, 0, Object::Null, 0))
+            .add_tile(tile("switch", 200, 300, Object::Null, 0))
+            .to_owned();
+        let got = Schematic::read_base64(schem.write_base64()?)?;
+        assert_eq!(got.dimensions(), (201, 301));
+        Ok(())
+    }
+
+    #[test]
+    fn test_move_tile() {
+        let mut schem = linked_schematic();
+
+        assert!(schem.move_tile(1, PackedPoint2 { x: 5, y: 5 }));
+        assert_eq!(processor_links(&schem), vec![(5, 5)]);
+
+        assert!(schem.move_tile(0, PackedPoint2 { x: 4, y: 3 }));
+        assert_eq!(processor_links(&schem), vec![(1, 2)]);
+
+        assert!(!schem.move_tile(3, PackedPoint2 { x: 0, y: 0 }));
+    }
+
+    #[test]
+    fn test_translate() {
+        let mut schem = linked_schematic();
+        schem.translate(3, 4);
+        assert_eq!(positions(&schem), vec![(3, 4, 0), (5, 4, 0), (6, 4, 0)]);
+        assert_eq!(processor_links(&schem), vec![(2, 0)]);
+
+        schem.normalize();
+        assert_eq!(positions(&schem), vec![(0, 0, 0), (2, 0, 0), (3, 0, 0)]);
+    }
+
+    #[test]
+    fn test_rotate() {
+        let mut schem = linked_schematic();
+
+        schem.rotate(false);
+        assert_eq!(positions(&schem), vec![(0, 0, 1), (1, 2, 1), (1, 3, 1)]);
+        assert_eq!(processor_links(&schem), vec![(1, 2)]);
+        assert_eq!(schem.dimensions(), (2, 4));
+
+        schem.rotate(true);
+        assert_eq!(positions(&schem), vec![(0, 0, 0), (2, 0, 0), (3, 0, 0)]);
+        assert_eq!(processor_links(&schem), vec![(2, 0)]);
+    }
+
+    #[test]
+    fn test_mirror() {
+        let mut schem = linked_schematic();
+
+        schem.mirror(true);
+        assert_eq!(positions(&schem), vec![(2, 0, 2), (1, 0, 2), (0, 0, 2)]);
+        assert_eq!(processor_links(&schem), vec![(-1, 0)]);
+
+        schem.mirror(false);
+        assert_eq!(positions(&schem), vec![(2, 0, 2), (1, 1, 2), (0, 1, 2)]);
+        assert_eq!(processor_links(&schem), vec![(-1, 1)]);
+    }
+
+    #[test]
+    fn test_rotate_power_links() {
+        let mut schem = Schematic::new()
+            .add_tile(tile(
+                "power-node",
+                0,
+                0,
+                vec![PackedPoint2 { x: 0, y: 3 }].into(),
+                0,
+            ))
+            .add_tile(tile("battery", 0, 3, Object::Null, 0))
+            .add_tile(tile("bridge-conveyor", 1, 0, Point2::new(0, 3).into(), 0))
+            .to_owned();
+
+        schem.rotate(true);
+        assert_eq!(positions(&schem), vec![(0, 1, 3), (3, 1, 3), (0, 0, 3)]);
+        assert_eq!(
+            schem.tiles()[0].config,
+            Object::Point2Array {
+                values: vec![PackedPoint2 { x: 3, y: 0 }]
+            }
+        );
+        assert_eq!(schem.tiles()[2].config, Point2::new(3, 0).into());
+    }
 }
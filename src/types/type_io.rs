@@ -4,7 +4,6 @@ use alloc::{boxed::Box, string::String, vec::Vec};
 use core::hash::Hash;
 
 use binrw::prelude::*;
-use itertools::Itertools;
 
 use crate::types::{
     ContentType, JavaString, LAccess, PackedPoint2, Point2, Team, UnitCommand, Vec2,
@@ -90,9 +89,9 @@ pub enum Object {
     BoolArray {
         #[bw(try_calc = values.len().try_into())]
         count: i32,
-        // TODO: this seems inefficent
-        #[br(count = count, map = |v: Vec<i8>| v.iter().map(|&b| b != 0).collect())]
-        #[bw(map = |v| v.iter().map(|&b| b as i8).collect_vec())]
+        // collecting from a Vec<u8> into a Vec<bool> reuses the allocation
+        #[br(count = count, map = |v: Vec<u8>| v.into_iter().map(|b| b != 0).collect())]
+        #[bw(write_with = write_bools)]
         values: Vec<bool>,
     },
 
@@ -190,6 +189,14 @@ fn write_string(value: &Option<JavaString>) -> BinResult<()> {
     }
 }
 
+#[allow(clippy::ptr_arg)]
+#[binrw::writer(writer)]
+fn write_bools(values: &Vec<bool>) -> BinResult<()> {
+    let bytes: Vec<u8> = values.iter().map(|&b| b as u8).collect();
+    writer.write_all(&bytes)?;
+    Ok(())
+}
+
 #[binrw]
 #[brw(big)]
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
@@ -197,3 +204,131 @@ pub struct ContentID {
     pub type_: ContentType,
     pub id: i16,
 }
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use std::{collections::BTreeSet, io::Cursor, string::ToString, vec};
+
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    /// Hand-written encodings of every TypeIO object type, following the format of Mindustry's `TypeIO.writeObject`.
+    fn encodings() -> Vec<(&'static str, Object)> {
+        vec![
+            ("00", Object::Null),
+            ("01 ff ff ff fe", Object::Int(-2)),
+            ("02 00 00 00 01 00 00 00 02", Object::Long(0x1_0000_0002)),
+            ("03 3f c0 00 00", Object::Float(1.5)),
+            ("04 00", Object::String(None)),
+            ("04 01 00 02 68 69", "hi".to_string().into()),
+            (
+                // modified UTF-8 encodes nulls with two bytes and supplementary characters as surrogate pairs
+                "04 01 00 09 61 c0 80 ed a0 bd ed b8 80",
+                "a\0\u{1f600}".to_string().into(),
+            ),
+            (
+                "05 00 00 05",
+                Object::Content(ContentID {
+                    type_: ContentType::Item,
+                    id: 5,
+                }),
+            ),
+            (
+                "06 00 02 00 00 00 01 ff ff ff ff",
+                Object::IntSeq {
+                    values: vec![1, -1],
+                },
+            ),
+            ("07 00 00 00 03 ff ff ff fc", Point2::new(3, -4).into()),
+            (
+                "08 02 00 01 00 02 ff ff 00 00",
+                vec![PackedPoint2::new(1, 2), PackedPoint2::new(-1, 0)].into(),
+            ),
+            (
+                "09 01 00 0a",
+                Object::TechNode(ContentID {
+                    type_: ContentType::Block,
+                    id: 10,
+                }),
+            ),
+            ("0a 01", true.into()),
+            ("0a 00", false.into()),
+            ("0b 3f f8 00 00 00 00 00 00", 1.5f64.into()),
+            ("0c 00 05 00 07", Object::Building(0x0005_0007)),
+            ("0d 00 3b", LAccess::Enabled.into()),
+            ("0e 00 00 00 03 01 02 ff", vec![1u8, 2, 255].into()),
+            ("0f 02", Object::LegacyUnitCommand { value: 2 }),
+            ("10 00 00 00 03 01 00 01", vec![true, false, true].into()),
+            ("10 00 00 00 00", Vec::<bool>::new().into()),
+            ("11 00 00 00 63", Object::Unit { id: 99 }),
+            (
+                "12 00 01 3f 80 00 00 c0 00 00 00",
+                vec![Vec2 { x: 1., y: -2. }].into(),
+            ),
+            ("13 00 00 00 00 3f 00 00 00", Vec2 { x: 0., y: 0.5 }.into()),
+            ("14 02", Team::CRUX.into()),
+            ("14 ff", Team(255).into()),
+            (
+                "15 00 02 00 00 00 07 00 00 00 08",
+                Object::IntArray { values: vec![7, 8] },
+            ),
+            (
+                "16 00 00 00 02 01 00 00 00 01 00",
+                vec![Object::Int(1), Object::Null].into(),
+            ),
+            ("17 00 04", UnitCommand::Mine.into()),
+        ]
+    }
+
+    fn decode_hex(hex: &str) -> Vec<u8> {
+        hex.split_whitespace()
+            .map(|b| u8::from_str_radix(b, 16).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn test_encodings_read() {
+        for (hex, want) in encodings() {
+            let got = Object::read(&mut Cursor::new(decode_hex(hex)))
+                .unwrap_or_else(|e| panic!("failed to read {hex}: {e}"));
+            assert_eq!(got, want, "{hex}");
+        }
+    }
+
+    #[test]
+    fn test_encodings_write() {
+        for (hex, value) in encodings() {
+            let mut cur = Cursor::new(Vec::new());
+            value.write(&mut cur).unwrap();
+            assert_eq!(cur.into_inner(), decode_hex(hex), "{value:?}");
+        }
+    }
+
+    #[test]
+    fn test_encodings_coverage() {
+        let tags: BTreeSet<_> = encodings()
+            .into_iter()
+            .map(|(hex, _)| decode_hex(hex)[0])
+            .collect();
+        assert_eq!(tags, (0..=23).collect());
+    }
+
+    #[test]
+    fn test_read_invalid() {
+        for hex in [
+            // unknown type
+            "18",
+            // truncated
+            "01 00 00",
+            "10 00 00 00 02 01",
+            // negative length
+            "0e ff ff ff ff",
+        ] {
+            assert!(
+                Object::read(&mut Cursor::new(decode_hex(hex))).is_err(),
+                "{hex}"
+            );
+        }
+    }
+}
@@ -6,16 +6,18 @@ use itertools::Itertools;
 use strum::IntoStaticStr;
 use widestring::U16String;
 
+#[cfg(feature = "std")]
+use super::{ProcessorLimits, VMExportError};
 use super::{
-    DrawCommand, InstructionResult, LObject, LValue, LogicVM, Processor, ProcessorBuilder,
-    VMLoadError, VMLoadResult,
+    Content, DrawCommand, InstructionResult, ItemModule, LObject, LValue, LiquidModule, LogicVM,
+    PowerModule, Processor, ProcessorBuilder, TurretData, VMLoadError, VMLoadResult, default_power,
 };
+#[cfg(feature = "std")]
+use crate::types::{ContentID, MapBuilding, ProcessorConfig, ProcessorLinkConfig, SchematicTile};
 use crate::types::{
-    LAccess, Object, PackedPoint2,
-    content::{self, Block},
+    LAccess, Object, PackedPoint2, Team,
+    content::{Block, ContentVersion, Item},
 };
-#[cfg(feature = "std")]
-use crate::types::{ProcessorConfig, SchematicTile};
 
 pub const MICRO_PROCESSOR: &str = "micro-processor";
 pub const LOGIC_PROCESSOR: &str = "logic-processor";
@@ -32,35 +34,122 @@ pub const WORLD_MESSAGE: &str = "world-message";
 pub const SWITCH: &str = "switch";
 pub const WORLD_SWITCH: &str = "world-switch";
 
+pub const SORTER: &str = "sorter";
+pub const INVERTED_SORTER: &str = "inverted-sorter";
+pub const UNLOADER: &str = "unloader";
+
+pub const DOOR: &str = "door";
+pub const DOOR_LARGE: &str = "door-large";
+
+pub const ILLUMINATOR: &str = "illuminator";
+
+pub const CONVEYORS: &[&str] = &[
+    "conveyor",
+    "titanium-conveyor",
+    "plastanium-conveyor",
+    "armored-conveyor",
+    "duct",
+    "armored-duct",
+];
+
+pub const DRILLS: &[&str] = &[
+    "mechanical-drill",
+    "pneumatic-drill",
+    "laser-drill",
+    "blast-drill",
+    "impact-drill",
+    "eruption-drill",
+];
+
+/// Turrets that can be controlled with `control shoot` and `control shootp`.
+pub const TURRETS: &[&str] = &[
+    "duo",
+    "scatter",
+    "scorch",
+    "hail",
+    "wave",
+    "lancer",
+    "arc",
+    "swarmer",
+    "salvo",
+    "tsunami",
+    "fuse",
+    "ripple",
+    "cyclone",
+    "foreshadow",
+    "spectre",
+    "meltdown",
+    "breach",
+    "diffuse",
+    "sublimate",
+    "titan",
+    "disperse",
+    "afflict",
+    "lustre",
+    "scathe",
+    "smite",
+    "malign",
+];
+
+/// The default illuminator color, `Pal.accent`.
+const ILLUMINATOR_DEFAULT_COLOR: u32 = 0xffd37fff;
+
 const MESSAGE_MAX_LEN: usize = 220;
 const MESSAGE_MAX_LINES: usize = 24;
 
+/// The number of ticks until a building disabled by `control enabled` is re-enabled, equivalent to Mindustry's `Building.timeToUncontrol`.
+pub(super) const TIME_TO_UNCONTROL: f64 = 60. * 6.;
+
+/// Mindustry's default health per tile, used for blocks that don't override it.
+///
+/// The content data doesn't include block health, so this is used for every block. Most blocks override it in Mindustry, so set [`BuildingProps::max_health`] manually if the exact value matters.
+pub const DEFAULT_SCALED_HEALTH: f64 = 40.;
+
 #[derive(Debug, Clone)]
 pub struct Building {
     pub block: &'static Block,
     pub position: PackedPoint2,
     pub data: Rc<RefCell<BuildingData>>,
+    pub props: Rc<RefCell<BuildingProps>>,
 }
 
 impl Building {
+    /// Creates a building whose inventories use the items and liquids of the default [`ContentVersion`].
     pub fn new(block: &'static Block, position: PackedPoint2, data: BuildingData) -> Self {
+        Self::with_content(block, position, data, ContentVersion::default())
+    }
+
+    /// Creates a building whose inventories use the items and liquids of `content`, which should be the version that `block` was taken from.
+    pub fn with_content(
+        block: &'static Block,
+        position: PackedPoint2,
+        data: BuildingData,
+        content: ContentVersion,
+    ) -> Self {
         Self {
             block,
             position,
             data: Rc::new(RefCell::new(data)),
+            props: Rc::new(RefCell::new(BuildingProps::new(block, content))),
         }
     }
 
+    /// Creates a building using the block with the given name in the default [`ContentVersion`].
     pub fn from_name(name: &str, position: PackedPoint2, data: BuildingData) -> VMLoadResult<Self> {
-        Ok(Self::new(Self::get_block(name)?, position, data))
+        Ok(Self::new(
+            Self::get_block(name, ContentVersion::default())?,
+            position,
+            data,
+        ))
     }
 
     pub fn from_config(
         name: &str,
         position: PackedPoint2,
         config: &Object,
-        _vm: impl AsRef<LogicVM>,
+        vm: impl AsRef<LogicVM>,
     ) -> VMLoadResult<Self> {
+        let content = vm.as_ref().content();
         let data = match name {
             MICRO_PROCESSOR | LOGIC_PROCESSOR | HYPER_PROCESSOR | WORLD_PROCESSOR => {
                 #[cfg(feature = "std")]
@@ -68,7 +157,7 @@ impl Building {
                     name,
                     position,
                     &ProcessorConfig::parse(config)?,
-                    _vm,
+                    vm,
                 );
                 #[cfg(not(feature = "std"))]
                 panic!("processor config parsing is not supported on no_std");
@@ -101,17 +190,58 @@ impl Building {
                 _ => false,
             }),
 
+            SORTER | INVERTED_SORTER => BuildingData::Sorter(Self::item_config(config, content)),
+            UNLOADER => BuildingData::Unloader(Self::item_config(config, content)),
+
+            DOOR | DOOR_LARGE => BuildingData::Door(match config {
+                &Object::Bool(value) => value,
+                _ => false,
+            }),
+
+            ILLUMINATOR => BuildingData::Illuminator(match config {
+                &Object::Int(value) => value as u32,
+                _ => ILLUMINATOR_DEFAULT_COLOR,
+            }),
+
+            _ if CONVEYORS.contains(&name) => BuildingData::Conveyor {
+                enabled: true,
+                enabled_control_time: 0.,
+            },
+            _ if DRILLS.contains(&name) => BuildingData::Drill {
+                enabled: true,
+                enabled_control_time: 0.,
+            },
+            _ if TURRETS.contains(&name) => {
+                BuildingData::Turret(TurretData::new(Self::get_block(name, content)?).into())
+            }
+
             _ => BuildingData::Unknown {
                 senseable_config: match *config {
-                    Object::Content(content) => {
-                        content.try_into().map(|v| LObject::Content(v).into()).ok()
-                    }
+                    Object::Content(id) => Content::from_id(id, content)
+                        .map(|v| LObject::Content(v).into())
+                        .ok(),
                     _ => None,
                 },
+                config: config.clone(),
             },
         };
 
-        Self::from_name(name, position, data)
+        let building = Self::with_content(Self::get_block(name, content)?, position, data, content);
+
+        // power node links are relative to the node
+        if let Object::Point2Array { values } = config
+            && let Some(power) = &mut building.props.borrow_mut().power
+        {
+            power.links = values
+                .iter()
+                .map(|link| PackedPoint2 {
+                    x: position.x + link.x,
+                    y: position.y + link.y,
+                })
+                .collect();
+        }
+
+        Ok(building)
     }
 
     #[cfg(feature = "std")]
@@ -120,6 +250,20 @@ impl Building {
         position: PackedPoint2,
         config: &ProcessorConfig,
         vm: impl AsRef<LogicVM>,
+    ) -> VMLoadResult<Self> {
+        Self::from_processor_config_with_limits(name, position, config, None, vm)
+    }
+
+    /// Like [`Self::from_processor_config`], but also fails if the processor's code or links exceed `limits`.
+    ///
+    /// The instruction, link, and variable limits are checked when the VM is built.
+    #[cfg(feature = "std")]
+    pub fn from_processor_config_with_limits(
+        name: &str,
+        position: PackedPoint2,
+        config: &ProcessorConfig,
+        limits: Option<ProcessorLimits>,
+        vm: impl AsRef<LogicVM>,
     ) -> VMLoadResult<Self> {
         let code = ProcessorBuilder::parse_code(&config.code)?;
 
@@ -128,29 +272,37 @@ impl Building {
                 ipt: 2.,
                 privileged: false,
                 code,
+                source: Some(&config.code),
                 links: &config.links,
                 instruction_hook: None,
+                limits,
             },
             LOGIC_PROCESSOR => ProcessorBuilder {
                 ipt: 8.,
                 privileged: false,
                 code,
+                source: Some(&config.code),
                 links: &config.links,
                 instruction_hook: None,
+                limits,
             },
             HYPER_PROCESSOR => ProcessorBuilder {
                 ipt: 25.,
                 privileged: false,
                 code,
+                source: Some(&config.code),
                 links: &config.links,
                 instruction_hook: None,
+                limits,
             },
             WORLD_PROCESSOR => ProcessorBuilder {
                 ipt: 8.,
                 privileged: true,
                 code,
+                source: Some(&config.code),
                 links: &config.links,
                 instruction_hook: None,
+                limits,
             },
             _ => {
                 return Err(VMLoadError::BadBlockType {
@@ -160,12 +312,8 @@ impl Building {
             }
         };
 
-        Ok(Self::from_processor_builder(
-            Self::get_block(name)?,
-            position,
-            data,
-            vm,
-        ))
+        let block = Self::get_block(name, vm.as_ref().content())?;
+        Self::from_processor_builder(block, position, data, vm)
     }
 
     pub fn from_processor_builder(
@@ -173,25 +321,231 @@ impl Building {
         position: PackedPoint2,
         config: ProcessorBuilder,
         vm: impl AsRef<LogicVM>,
-    ) -> Self {
-        Self::new(
+    ) -> VMLoadResult<Self> {
+        let content = vm.as_ref().content();
+        Ok(Self::with_content(
             block,
             position,
-            BuildingData::Processor(config.build(position, vm)),
-        )
+            BuildingData::Processor(config.build(position, vm)?),
+            content,
+        ))
     }
 
+    /// Creates a building from a schematic tile, treating its position and links as if they refer to the bottom left corner of the block.
+    ///
+    /// Schematics actually store the center of each block, so most callers should use [`Self::from_schematic_tile_center`] instead. This only differs for blocks larger than 2x2.
     #[cfg(feature = "std")]
     pub fn from_schematic_tile(
         SchematicTile {
             block: name,
             position,
             config,
-            ..
+            rotation,
+        }: &SchematicTile,
+        vm: impl AsRef<LogicVM>,
+    ) -> VMLoadResult<Self> {
+        let building = Self::from_config(name, *position, config, vm)?;
+        building.props.borrow_mut().rotation = *rotation;
+        Ok(building)
+    }
+
+    /// Creates a building from a schematic tile, converting its position and links like [`Self::from_center_config`].
+    #[cfg(feature = "std")]
+    pub fn from_schematic_tile_center(
+        SchematicTile {
+            block: name,
+            position,
+            config,
+            rotation,
         }: &SchematicTile,
         vm: impl AsRef<LogicVM>,
     ) -> VMLoadResult<Self> {
-        Self::from_config(name, *position, config, vm)
+        let building = Self::from_center_config(name, *position, config, vm)?;
+        building.props.borrow_mut().rotation = *rotation;
+        Ok(building)
+    }
+
+    /// Like [`Self::from_config`], but `center` and any links in `config` refer to the center of the block, like in schematics and maps.
+    ///
+    /// These are converted to the bottom left corner to make the building cover the same tiles as in Mindustry.
+    #[cfg(feature = "std")]
+    pub fn from_center_config(
+        name: &str,
+        center: PackedPoint2,
+        config: &Object,
+        vm: impl AsRef<LogicVM>,
+    ) -> VMLoadResult<Self> {
+        let offset = Self::get_block(name, vm.as_ref().content())?.center_offset();
+        let position = PackedPoint2 {
+            x: center.x - offset,
+            y: center.y - offset,
+        };
+
+        if let MICRO_PROCESSOR | LOGIC_PROCESSOR | HYPER_PROCESSOR | WORLD_PROCESSOR = name {
+            let mut config = ProcessorConfig::parse(config)?;
+            for link in &mut config.links {
+                link.x += offset;
+                link.y += offset;
+            }
+            return Self::from_processor_config(name, position, &config, vm);
+        }
+
+        let building = Self::from_config(name, position, config, vm)?;
+        if let Some(power) = &mut building.props.borrow_mut().power {
+            for link in &mut power.links {
+                link.x += offset;
+                link.y += offset;
+            }
+        }
+        Ok(building)
+    }
+
+    /// Creates a building from a map, including its team, health, items, liquids, power and memory.
+    ///
+    /// The position and links are converted like [`Self::from_center_config`].
+    #[cfg(feature = "std")]
+    pub fn from_map_building(
+        building: &MapBuilding,
+        vm: impl AsRef<LogicVM>,
+    ) -> VMLoadResult<Self> {
+        let content = vm.as_ref().content();
+
+        // map configs use the IDs of the default content version
+        let config = match building.config {
+            Object::Content(id) => ContentVersion::default()
+                .convert_id(id, content)
+                .map_or(Object::Null, Object::Content),
+            ref config => config.clone(),
+        };
+        let result = Self::from_center_config(&building.block, building.position, &config, vm)?;
+
+        {
+            let mut props = result.props.borrow_mut();
+            props.team = building.team;
+            props.rotation = building.rotation;
+            props.set_health(building.health as f64);
+
+            if let Some(items) = &mut props.items {
+                for (name, amount) in &building.items {
+                    if let Some(item) = content.items().from_name(name) {
+                        items.set(item, *amount);
+                    }
+                }
+            }
+
+            if let Some(liquids) = &mut props.liquids {
+                for (name, amount) in &building.liquids {
+                    if let Some(liquid) = content.liquids().from_name(name) {
+                        liquids.set(liquid, *amount);
+                    }
+                }
+            }
+
+            // power links in maps are absolute
+            if let (Some(power), Some(saved)) = (&mut props.power, &building.power) {
+                power.links = saved.links.clone();
+                power.status = saved.status as f64;
+            }
+        }
+
+        match &mut *result.data.borrow_mut() {
+            BuildingData::Memory(memory) => {
+                for (cell, &value) in memory.iter_mut().zip(building.memory.iter().flatten()) {
+                    *cell = value;
+                }
+            }
+            BuildingData::Conveyor { enabled, .. } | BuildingData::Drill { enabled, .. } => {
+                *enabled = building.enabled;
+            }
+            _ => {}
+        }
+
+        Ok(result)
+    }
+
+    /// Converts this building back into a schematic tile, including its current configuration.
+    ///
+    /// Processors must have [source code](Processor::source) to be exported.
+    #[cfg(feature = "std")]
+    pub fn to_schematic_tile(&self) -> Result<SchematicTile, VMExportError> {
+        // schematic positions and links refer to the center of the block
+        let center = self.center();
+        let config = match &*self.data.borrow() {
+            BuildingData::Processor(processor) => ProcessorConfig {
+                code: processor
+                    .source()
+                    .ok_or(VMExportError::MissingProcessorCode(self.position))?
+                    .to_string(),
+                links: processor
+                    .state
+                    .links()
+                    .iter()
+                    .map(|link| {
+                        let target = link.building.center();
+                        ProcessorLinkConfig {
+                            name: link.name.as_str().into(),
+                            x: target.x - center.x,
+                            y: target.y - center.y,
+                        }
+                    })
+                    .collect(),
+            }
+            .to_object()?,
+
+            BuildingData::Message(message) => message.to_string_lossy().into(),
+            &BuildingData::Switch(value) | &BuildingData::Door(value) => value.into(),
+            &BuildingData::Sorter(item) | &BuildingData::Unloader(item) => match item {
+                Some(item) => ContentID::from(Content::Item(item)).into(),
+                None => Object::Null,
+            },
+            &BuildingData::Illuminator(color) => (color as i32).into(),
+
+            BuildingData::Unknown { config, .. } => match &self.props.borrow().power {
+                // the host may have changed the links since the building was created
+                Some(power) if !power.links.is_empty() => power
+                    .links
+                    .iter()
+                    .map(|link| PackedPoint2 {
+                        x: link.x - center.x,
+                        y: link.y - center.y,
+                    })
+                    .collect_vec()
+                    .into(),
+                _ => config.clone(),
+            },
+
+            BuildingData::Custom(custom) => custom.config(self),
+
+            BuildingData::Memory(_)
+            | BuildingData::Conveyor { .. }
+            | BuildingData::Drill { .. }
+            | BuildingData::Turret(_) => Object::Null,
+        };
+
+        Ok(SchematicTile {
+            block: self.block.name.to_string(),
+            position: center,
+            config,
+            rotation: self.props.borrow().rotation,
+        })
+    }
+
+    /// Returns the position of this building's center tile, which Mindustry uses as the building's position in schematics and maps.
+    pub fn center(&self) -> PackedPoint2 {
+        let offset = self.block.center_offset();
+        PackedPoint2 {
+            x: self.position.x + offset,
+            y: self.position.y + offset,
+        }
+    }
+
+    /// Returns the position of this building's center in tiles, equivalent to Mindustry's `Building.x` and `Building.y` divided by `tilesize`.
+    ///
+    /// Unlike [`Self::center`], this is halfway between two tiles for blocks with an even size.
+    pub fn world_center(&self) -> (f64, f64) {
+        let center = self.center();
+        let offset = ((self.block.size + 1) % 2) as f64 / 2.;
+        (center.x as f64 + offset, center.y as f64 + offset)
     }
 
     /// Returns an iterator over all of the points contained within this building.
@@ -209,10 +563,25 @@ impl Building {
             .map(PackedPoint2::from)
     }
 
-    fn get_block(name: &str) -> VMLoadResult<&'static Block> {
-        content::blocks::FROM_NAME
-            .get(name)
-            .copied()
+    /// Returns the team that owns this building.
+    pub fn team(&self) -> Team {
+        self.props.borrow().team
+    }
+
+    fn item_config(config: &Object, content: ContentVersion) -> Option<&'static Item> {
+        match *config {
+            Object::Content(id) => match Content::from_id(id, content) {
+                Ok(Content::Item(item)) => Some(item),
+                _ => None,
+            },
+            _ => None,
+        }
+    }
+
+    fn get_block(name: &str, content: ContentVersion) -> VMLoadResult<&'static Block> {
+        content
+            .blocks()
+            .from_name(name)
             .ok_or_else(|| VMLoadError::UnknownBlockType(name.to_string()))
     }
 }
@@ -223,6 +592,54 @@ impl PartialEq for Building {
     }
 }
 
+/// Properties shared by all buildings, which may be changed by the host or by `setprop`.
+///
+/// If you change the team of a building that has already been added to a VM, you must call [`LogicVM::invalidate_team_indexes`] afterwards.
+#[derive(Debug, Clone, PartialEq)]
+pub struct BuildingProps {
+    pub team: Team,
+    /// The direction that the building is facing, from 0 (right) to 3 (down).
+    pub rotation: i8,
+    pub health: f64,
+    /// The building's maximum health, returned by `sensor @maxHealth` and used to clamp `setprop @health`.
+    ///
+    /// This defaults to an approximation based on [`DEFAULT_SCALED_HEALTH`].
+    pub max_health: f64,
+    pub items: Option<ItemModule>,
+    pub liquids: Option<LiquidModule>,
+    pub power: Option<PowerModule>,
+}
+
+impl BuildingProps {
+    /// Creates the default properties for a block from a content version.
+    ///
+    /// The building starts at full health, where `max_health` is approximated using [`DEFAULT_SCALED_HEALTH`].
+    ///
+    /// Item, liquid and power modules are only created for blocks whose content data sets `hasItems`, `hasLiquids` or `hasPower`. Power is configured using [`default_power`].
+    pub fn new(block: &Block, content: ContentVersion) -> Self {
+        let size = block.size as f64;
+        let max_health = size * size * DEFAULT_SCALED_HEALTH;
+        Self {
+            team: Team::SHARDED,
+            rotation: 0,
+            health: max_health,
+            max_health,
+            items: block.has_items.then(|| ItemModule::new(content)),
+            liquids: block.has_liquids.then(|| LiquidModule::new(content)),
+            power: block.has_power.then(|| default_power(block)).flatten(),
+        }
+    }
+
+    pub fn dead(&self) -> bool {
+        self.health <= 0.
+    }
+
+    /// Sets this building's health, clamped to `[0, max_health]`.
+    pub fn set_health(&mut self, health: f64) {
+        self.health = health.clamp(0., self.max_health);
+    }
+}
+
 macro_rules! borrow_data {
     (
         mut $ref:expr,
@@ -304,7 +721,30 @@ pub enum BuildingData {
     Memory(Box<[f64]>),
     Message(U16String),
     Switch(bool),
-    Unknown { senseable_config: Option<LValue> },
+    /// A sorter or inverted sorter, configured with `control config`.
+    Sorter(Option<&'static Item>),
+    /// An unloader, configured with `control config`.
+    Unloader(Option<&'static Item>),
+    /// A door, opened and closed with `control enabled`.
+    Door(bool),
+    /// An illuminator, with a color in RGBA8888 format set by `control color`.
+    Illuminator(u32),
+    Conveyor {
+        enabled: bool,
+        /// The number of ticks until the conveyor is re-enabled, set by `control enabled`.
+        enabled_control_time: f64,
+    },
+    Drill {
+        enabled: bool,
+        /// The number of ticks until the drill is re-enabled, set by `control enabled`.
+        enabled_control_time: f64,
+    },
+    Turret(Box<TurretData>),
+    Unknown {
+        senseable_config: Option<LValue>,
+        /// The config that this building was created with.
+        config: Object,
+    },
     Custom(#[derivative(Debug = "ignore")] Box<dyn CustomBuildingData>),
 }
 
@@ -418,4 +858,23 @@ pub trait CustomBuildingData {
     fn sensor(&mut self, building: &Building, vm: &LogicVM, sensor: LAccess) -> Option<LValue> {
         None
     }
+
+    /// Returns the config to use when exporting this building to a schematic.
+    fn config(&self, building: &Building) -> Object {
+        Object::Null
+    }
+
+    /// Called by `setprop` after the building's [`BuildingProps`] have been updated.
+    ///
+    /// `prop` is usually a sensor (eg. `@health`) or a content object (eg. `@copper`).
+    #[must_use]
+    fn setprop(
+        &mut self,
+        building: &Building,
+        vm: &LogicVM,
+        prop: Cow<'_, LValue>,
+        value: Cow<'_, LValue>,
+    ) -> InstructionResult {
+        InstructionResult::Ok
+    }
 }
@@ -1,5 +1,6 @@
-use alloc::{borrow::Cow, format, rc::Rc, string::String};
+use alloc::{borrow::Cow, boxed::Box, rc::Rc, string::String, vec::Vec};
 
+use derivative::Derivative;
 use enum_dispatch::enum_dispatch;
 #[cfg(feature = "std")]
 use lazy_static::lazy_static;
@@ -11,25 +12,31 @@ use num_traits::float::FloatCore;
 use widestring::{U16Str, u16str};
 
 use super::{
-    BuildingData, Content, DrawCommand, LObject, LString, LValue, LVar, LogicVM, ProcessorState,
-    TextAlignment, VMLoadError, VMLoadResult,
-    buildings::borrow_data,
+    Building, BuildingData, Content, CustomInstructionHandler, DrawCommand, InstructionError,
+    LObject, LString, LValue, LVar, LogicVM, ProcessorState, TextAlignment, UnitData, UnitEntity,
+    VMLoadError, VMLoadResult,
+    buildings::{TIME_TO_UNCONTROL, borrow_data},
     processor::{MAX_DRAW_BUFFER, MAX_TEXT_BUFFER},
     variables::{Constants, F64_DEG_RAD, F64_RAD_DEG, RAD_DEG},
+    world,
 };
 use crate::{
-    parser::ast::{self, ConditionOp, DrawOp, LogicOp, TileLayer},
+    parser::ast::{
+        self, ConditionOp, DrawOp, FetchType, LogicOp, MessageType, RadarSort, RadarTarget,
+        TileLayer,
+    },
     types::{
         ContentType, LAccess, PackedPoint2, Team,
-        colors::{self, f32_to_double_bits, f64_from_double_bits, from_double_bits},
-        content,
+        colors::{
+            f32_to_double_bits, f64_from_double_bits, from_double_bits, rgba8888_to_double_bits,
+        },
     },
     utils::{RapidHashMap, u16format},
     vm::variables::VariableIndex,
 };
 
-const MAX_IPT: i32 = 1000;
-const EQUALITY_EPSILON: f64 = 0.000001;
+pub(super) const MAX_IPT: i32 = 1000;
+pub(crate) const EQUALITY_EPSILON: f64 = 0.000001;
 const PRINT_EPSILON: f64 = 0.00001;
 
 #[cfg(feature = "std")]
@@ -78,6 +85,7 @@ pub enum Instruction {
     GetLink,
     Control,
     Sensor,
+    Radar,
     // operations
     Set,
     Op,
@@ -94,6 +102,12 @@ pub enum Instruction {
     // privileged
     GetBlock,
     SetRate,
+    Message,
+    LocalePrint,
+    Fetch,
+    SetProp,
+    // custom
+    Custom,
 }
 
 impl Default for Instruction {
@@ -105,12 +119,17 @@ impl Default for Instruction {
 #[derive(Debug)]
 pub struct InstructionBuilder {
     pub(super) instruction: ast::Instruction,
+    pub(super) span: ast::Span,
+    /// Any extra arguments after the instruction's own arguments. These are only used by custom instructions.
+    pub(super) args: Vec<ast::Value>,
     pub(super) labels: Rc<RapidHashMap<String, usize>>,
 }
 
 impl InstructionBuilder {
     pub(super) fn late_init(
         self,
+        vm: &LogicVM,
+        building: &Building,
         globals: &Constants,
         state: &mut ProcessorState,
     ) -> VMLoadResult<Instruction> {
@@ -147,26 +166,27 @@ impl InstructionBuilder {
             ast::Value::None => LVar::Constant(LValue::NULL),
         };
 
-        let jump_target =
-            |value| match value {
-                ast::Value::Variable(name) => self.labels.get(&name).copied().ok_or_else(|| {
-                    VMLoadError::BadProcessorCode(format!("label not found: {name}"))
-                }),
-
-                ast::Value::Number(address) => {
-                    let counter = address as usize;
-                    if (0..num_instructions).contains(&counter) {
-                        Ok(counter)
-                    } else {
-                        Err(VMLoadError::BadProcessorCode(format!(
-                            "jump out of range: {}",
-                            address.trunc()
-                        )))
-                    }
+        let span = self.span;
+        let error = |error| VMLoadError::BadInstruction { span, error };
+
+        let jump_target = |value| match value {
+            ast::Value::Variable(name) => self
+                .labels
+                .get(&name)
+                .copied()
+                .ok_or_else(|| error(InstructionError::LabelNotFound(name))),
+
+            ast::Value::Number(address) => {
+                let counter = address as usize;
+                if (0..num_instructions).contains(&counter) {
+                    Ok(counter)
+                } else {
+                    Err(error(InstructionError::JumpOutOfRange(address.trunc())))
                 }
+            }
 
-                _ => unreachable!(),
-            };
+            _ => unreachable!(),
+        };
 
         // map AST instructions to handlers
 
@@ -253,6 +273,23 @@ impl InstructionBuilder {
                 sensor: lvar(sensor),
             }
             .into(),
+            ast::Instruction::Radar {
+                target1,
+                target2,
+                target3,
+                sort,
+                radar,
+                order,
+                result,
+            } => Radar {
+                targets: [target1, target2, target3],
+                sort,
+                radar: lvar(radar),
+                order: lvar(order),
+                result: lvar(result),
+                position: building.position,
+            }
+            .into(),
 
             // operations
             ast::Instruction::Set { to, from } => Set {
@@ -325,11 +362,26 @@ impl InstructionBuilder {
 
             // unknown
             // do this here so it isn't ignored for unprivileged procs
-            ast::Instruction::Unknown(name) => {
-                return Err(VMLoadError::BadProcessorCode(format!(
-                    "unknown instruction: {name}"
-                )));
-            }
+            ast::Instruction::Unknown(name) => match vm.custom_instructions.get(&name) {
+                Some(custom) if self.args.len() >= custom.arity => Custom {
+                    args: self
+                        .args
+                        .into_iter()
+                        .take(custom.arity)
+                        .map(&mut lvar)
+                        .collect(),
+                    handler: custom.handler.clone(),
+                }
+                .into(),
+                Some(custom) => {
+                    return Err(error(InstructionError::NotEnoughArguments {
+                        name,
+                        want: custom.arity,
+                        got: self.args.len(),
+                    }));
+                }
+                None => return Err(error(InstructionError::UnknownInstruction(name))),
+            },
 
             // convert privileged instructions to noops if the proc is unprivileged
             _ if !privileged => Noop.into(),
@@ -348,6 +400,44 @@ impl InstructionBuilder {
             }
             .into(),
             ast::Instruction::SetRate { value } => SetRate { value: lvar(value) }.into(),
+            ast::Instruction::Message {
+                message_type,
+                duration,
+                success,
+            } => Message {
+                message_type,
+                duration: lvar(duration),
+                success: match success {
+                    ast::Value::Variable(name) if name == "@wait" => None,
+                    success => Some(lvar(success)),
+                },
+            }
+            .into(),
+            ast::Instruction::LocalePrint { value } => LocalePrint { value: lvar(value) }.into(),
+            ast::Instruction::Fetch {
+                fetch_type,
+                result,
+                team,
+                index,
+                extra,
+            } => Fetch {
+                fetch_type,
+                result: lvar(result),
+                team: lvar(team),
+                index: lvar(index),
+                extra: lvar(extra),
+            }
+            .into(),
+            ast::Instruction::SetProp {
+                prop,
+                target,
+                value,
+            } => SetProp {
+                prop: lvar(prop),
+                target: lvar(target),
+                value: lvar(value),
+            }
+            .into(),
         })
     }
 }
@@ -647,6 +737,7 @@ impl Print {
             Some(LObject::Content(content)) => Cow::Borrowed(content.name()),
             Some(LObject::Team(team)) => Cow::from(team.name_u16()),
             Some(LObject::Building(building)) => Cow::Borrowed(building.block.name.as_u16str()),
+            Some(LObject::Unit(unit)) => Cow::Borrowed(unit.unit_type.name.as_u16str()),
             Some(LObject::Sensor(sensor)) => Cow::from(sensor.name_u16()),
         }
     }
@@ -825,10 +916,59 @@ impl InstructionTrait for Control {
                     }
                 },
                 data => match data {
-                    BuildingData::Switch(value) if self.control == LAccess::Enabled => {
+                    BuildingData::Switch(value) | BuildingData::Door(value)
+                        if self.control == LAccess::Enabled =>
+                    {
+                        let enabled = self.p1.get(state);
+                        if enabled.isnum() {
+                            *value = enabled.numf() != 0.;
+                        }
+                    }
+
+                    BuildingData::Conveyor {
+                        enabled: value,
+                        enabled_control_time,
+                    }
+                    | BuildingData::Drill {
+                        enabled: value,
+                        enabled_control_time,
+                    } if self.control == LAccess::Enabled => {
                         let enabled = self.p1.get(state);
                         if enabled.isnum() {
                             *value = enabled.numf() != 0.;
+                            *enabled_control_time = TIME_TO_UNCONTROL;
+                        }
+                    }
+
+                    BuildingData::Sorter(item) | BuildingData::Unloader(item)
+                        if self.control == LAccess::Config =>
+                    {
+                        match self.p1.get(state).obj() {
+                            Some(LObject::Content(Content::Item(value))) => *item = Some(value),
+                            Some(LObject::Null) => *item = None,
+                            _ => {}
+                        }
+                    }
+
+                    BuildingData::Turret(turret) => match self.control {
+                        LAccess::Shoot => turret.shoot(
+                            self.p1.get(state).num(),
+                            self.p2.get(state).num(),
+                            self.p3.get(state).bool(),
+                        ),
+                        LAccess::Shootp => {
+                            if let Some(target) = self.p1.get(state).obj() {
+                                turret.shoot_at(target, self.p2.get(state).bool());
+                            }
+                        }
+                        _ => {}
+                    },
+
+                    BuildingData::Illuminator(color) if self.control == LAccess::Color => {
+                        let value = self.p1.get(state);
+                        if value.isnum() {
+                            let (r, g, b, a) = from_double_bits(value.num());
+                            *color = u32::from_be_bytes([r, g, b, a]);
                         }
                     }
 
@@ -933,27 +1073,78 @@ impl SimpleInstructionTrait for Sensor {
                     _ => setnull!(),
                 },
 
-                // TODO: solid, health, maxHealth, powerCapacity
+                // TODO: solid
                 Some(LObject::Building(building)) => match sensor {
                     X => building.position.x as f64,
                     Y => building.position.y as f64,
-                    Color => colors::TEAM_SHARDED_F64,
-                    Dead => false.into(),
-                    Team => crate::types::Team::SHARDED.0 as f64,
+                    Color => match building.data.try_borrow().as_deref() {
+                        Ok(&BuildingData::Illuminator(color)) => rgba8888_to_double_bits(color),
+                        _ => building.team().color(),
+                    },
+                    Dead => building.props.borrow().dead().into(),
+                    Team => building.team().0 as f64,
+                    Health => building.props.borrow().health,
+                    MaxHealth => building.props.borrow().max_health,
                     Efficiency => 1.,
                     Timescale => 1.,
                     Range => building.block.range,
-                    Rotation => 0.,
-                    TotalItems | TotalLiquids | TotalPower => 0.,
+                    Rotation => match building.data.try_borrow().as_deref() {
+                        Ok(BuildingData::Turret(turret)) => turret.rotation,
+                        _ => building.props.borrow().rotation as f64,
+                    },
+                    TotalItems => building
+                        .props
+                        .borrow()
+                        .items
+                        .as_ref()
+                        .map_or(0, |i| i.total()) as f64,
+                    TotalLiquids => building
+                        .props
+                        .borrow()
+                        .liquids
+                        .as_ref()
+                        .map_or(0., |l| l.current_amount())
+                        as f64,
+                    TotalPower => building
+                        .props
+                        .borrow()
+                        .power
+                        .as_ref()
+                        .map_or(0., |p| p.total()),
+                    PowerCapacity => building
+                        .props
+                        .borrow()
+                        .power
+                        .as_ref()
+                        .map_or(0., |p| p.capacity),
                     ItemCapacity => building.block.item_capacity as f64,
                     LiquidCapacity => building.block.liquid_capacity as f64,
-                    PowerNetIn | PowerNetOut | PowerNetStored | PowerNetCapacity => 0.,
+                    PowerNetIn | PowerNetOut | PowerNetStored | PowerNetCapacity => {
+                        let graphs = vm.power_graphs();
+                        graphs.graph_of(building).map_or(0., |graph| match sensor {
+                            PowerNetIn => graph.power_in(),
+                            PowerNetOut => graph.power_out(),
+                            PowerNetStored => graph.last_power_stored,
+                            _ => graph.last_capacity,
+                        })
+                    }
                     Controlled => false.into(),
                     PayloadCount => 0.,
                     Size => building.block.size as f64,
                     CameraX | CameraY | CameraWidth | CameraHeight => 0.,
                     Type => setobj!(Content::Block(building.block)),
-                    FirstItem => setnull!(),
+                    FirstItem => {
+                        let first = building
+                            .props
+                            .borrow()
+                            .items
+                            .as_ref()
+                            .and_then(|i| i.first());
+                        match first {
+                            Some(item) => setobj!(Content::Item(item)),
+                            None => setnull!(),
+                        }
+                    }
                     PayloadType => setnull!(),
 
                     _ => borrow_data!(
@@ -975,11 +1166,38 @@ impl SimpleInstructionTrait for Sensor {
                                 _ => setnull!(),
                             },
 
-                            BuildingData::Switch(enabled) => match sensor {
+                            BuildingData::Switch(enabled)
+                            | BuildingData::Door(enabled)
+                            | BuildingData::Conveyor { enabled, .. }
+                            | BuildingData::Drill { enabled, .. } => match sensor {
                                 Enabled => (*enabled).into(),
                                 _ => setnull!(),
                             },
 
+                            BuildingData::Sorter(item) | BuildingData::Unloader(item) => {
+                                match sensor {
+                                    Config => match *item {
+                                        Some(item) => setobj!(Content::Item(item)),
+                                        None => setnull!(),
+                                    },
+                                    Enabled => true.into(),
+                                    _ => setnull!(),
+                                }
+                            }
+
+                            BuildingData::Turret(turret) => match sensor {
+                                ShootX => turret.shoot_x,
+                                ShootY => turret.shoot_y,
+                                Shooting => turret.is_shooting().into(),
+                                Enabled => true.into(),
+                                _ => setnull!(),
+                            },
+
+                            BuildingData::Illuminator(_) => match sensor {
+                                Enabled => true.into(),
+                                _ => setnull!(),
+                            },
+
                             BuildingData::Unknown {
                                 senseable_config, ..
                             } => match sensor {
@@ -1010,6 +1228,34 @@ impl SimpleInstructionTrait for Sensor {
                     ),
                 },
 
+                // TODO: velocityX, velocityY, shootX, shootY, shooting, boosting, flag, speed
+                Some(LObject::Unit(unit)) => {
+                    let data = unit.data.borrow().clone();
+                    match sensor {
+                        X => data.x,
+                        Y => data.y,
+                        Rotation => data.rotation,
+                        Health => data.health,
+                        MaxHealth => data.max_health,
+                        Shield => data.shield,
+                        Armor => data.armor,
+                        Dead => data.dead.into(),
+                        Team => data.team.0 as f64,
+                        Color => data.team.color(),
+                        Controlled => {
+                            if data.player {
+                                2.
+                            } else {
+                                0.
+                            }
+                        }
+                        Type => setobj!(Content::Unit(unit.unit_type)),
+                        Name => setobj!(LString::Static(unit.unit_type.name.as_u16str())),
+                        Id => unit.id as f64,
+                        _ => setnull!(),
+                    }
+                }
+
                 // string length
                 Some(LObject::String(string)) if matches!(sensor, BufferSize | Size) => {
                     string.len() as f64
@@ -1021,14 +1267,37 @@ impl SimpleInstructionTrait for Sensor {
             // if target doesn't implement Senseable, write null
             _ if !matches!(
                 target.obj(),
-                Some(LObject::Content(_) | LObject::Team(_) | LObject::Building(_))
+                Some(
+                    LObject::Content(_)
+                        | LObject::Team(_)
+                        | LObject::Building(_)
+                        | LObject::Unit(_)
+                )
             ) =>
             {
                 setnull!()
             }
 
-            // items/liquids aren't implemented, so always write null if sensing content
-            Some(LObject::Content(_)) => setnull!(),
+            // item/liquid amounts
+            Some(LObject::Content(content)) => {
+                let amount = match target.obj() {
+                    Some(LObject::Building(building)) => {
+                        let props = building.props.borrow();
+                        match (content, &props.items, &props.liquids) {
+                            (Content::Item(item), Some(items), _) => Some(items.get(item) as f64),
+                            (Content::Liquid(liquid), _, Some(liquids)) => {
+                                Some(liquids.get(liquid) as f64)
+                            }
+                            _ => None,
+                        }
+                    }
+                    _ => None,
+                };
+                match amount {
+                    Some(amount) => amount,
+                    None => setnull!(),
+                }
+            }
 
             // if target is Senseable and sensor isn't Content or LAccess, do not write to result
             _ => return,
@@ -1038,6 +1307,98 @@ impl SimpleInstructionTrait for Sensor {
     }
 }
 
+#[derive(Debug)]
+#[non_exhaustive]
+pub struct Radar {
+    pub targets: [RadarTarget; 3],
+    pub sort: RadarSort,
+    pub radar: LVar,
+    pub order: LVar,
+    pub result: LVar,
+    /// The position of the processor executing this instruction.
+    pub position: PackedPoint2,
+}
+
+impl Radar {
+    fn matches(target: RadarTarget, team: Team, unit: &UnitData) -> bool {
+        match target {
+            RadarTarget::Any => true,
+            RadarTarget::Enemy => unit.team != team,
+            RadarTarget::Ally => unit.team == team,
+            RadarTarget::Player => unit.player,
+            RadarTarget::Attacker => unit.can_shoot,
+            RadarTarget::Flying => unit.flying,
+            RadarTarget::Boss => unit.boss,
+            RadarTarget::Ground => !unit.flying,
+        }
+    }
+
+    fn sort_value(&self, x: f64, y: f64, unit: &UnitData) -> f64 {
+        match self.sort {
+            RadarSort::Distance => -unit.dst2(x, y),
+            RadarSort::Health => unit.health,
+            RadarSort::Shield => unit.shield,
+            RadarSort::Armor => unit.armor,
+            RadarSort::MaxHealth => unit.max_health,
+        }
+    }
+}
+
+impl SimpleInstructionTrait for Radar {
+    fn execute(&self, state: &mut ProcessorState, vm: &LogicVM) {
+        // only buildings can be used as radars, since units are not controllable
+        // like Mindustry, unprivileged processors can only use linked buildings on their own team
+        let this = vm.building(self.position);
+        let team = match self.radar.get(state).obj() {
+            Some(LObject::Building(building))
+                if state.privileged()
+                    || (this.is_some_and(|this| building.team() == this.team())
+                        && state.linked_positions().contains(&building.position)) =>
+            {
+                Some((building.clone(), building.team()))
+            }
+            _ => None,
+        };
+
+        let Some((building, team)) = team else {
+            self.result.set(state, LValue::NULL);
+            return;
+        };
+
+        let (x, y) = building.world_center();
+        let range = building.block.range;
+
+        let sort_dir = if self.order.get(state).bool() {
+            1.
+        } else {
+            -1.
+        };
+        let enemies = self.targets.contains(&RadarTarget::Enemy);
+
+        // unlike Mindustry, the target is recalculated every time instead of every 30 ticks
+        let mut best = None;
+        let mut best_value = 0.;
+        for unit in vm.units() {
+            let data = unit.data.borrow();
+            if data.dead
+                || (data.team != team) != enemies
+                || data.dst2(x, y) > range * range
+                || !self.targets.iter().all(|&t| Self::matches(t, team, &data))
+            {
+                continue;
+            }
+
+            let value = self.sort_value(x, y, &data) * sort_dir;
+            if best.is_none() || value > best_value {
+                best_value = value;
+                best = Some(unit);
+            }
+        }
+
+        self.result.set(state, best.cloned().into());
+    }
+}
+
 // operations
 
 #[derive(Debug)]
@@ -1203,29 +1564,26 @@ pub struct Lookup {
 }
 
 impl SimpleInstructionTrait for Lookup {
-    fn execute(&self, state: &mut ProcessorState, _: &LogicVM) {
+    fn execute(&self, state: &mut ProcessorState, vm: &LogicVM) {
         let id = self.id.get(state).numi();
+        let content = vm.content();
 
         let result = match self.content_type {
-            ContentType::Block => content::blocks::FROM_LOGIC_ID
-                .get(&id)
-                .map(|v| Content::Block(v))
+            ContentType::Block => content
+                .blocks()
+                .from_logic_id(id)
+                .map(Content::Block)
                 .into(),
 
-            ContentType::Item => content::items::FROM_LOGIC_ID
-                .get(&id)
-                .map(|v| Content::Item(v))
-                .into(),
+            ContentType::Item => content.items().from_logic_id(id).map(Content::Item).into(),
 
-            ContentType::Liquid => content::liquids::FROM_LOGIC_ID
-                .get(&id)
-                .map(|v| Content::Liquid(v))
+            ContentType::Liquid => content
+                .liquids()
+                .from_logic_id(id)
+                .map(Content::Liquid)
                 .into(),
 
-            ContentType::Unit => content::units::FROM_LOGIC_ID
-                .get(&id)
-                .map(|v| Content::Unit(v))
-                .into(),
+            ContentType::Unit => content.units().from_logic_id(id).map(Content::Unit).into(),
 
             ContentType::Team => id.try_into().ok().map(Team).into(),
 
@@ -1395,17 +1753,35 @@ pub struct GetBlock {
 
 impl SimpleInstructionTrait for GetBlock {
     fn execute(&self, state: &mut ProcessorState, vm: &LogicVM) {
-        let result = match vm.building(PackedPoint2 {
+        let position = PackedPoint2 {
             x: self.x.get(state).numf().round() as i16,
             y: self.y.get(state).numf().round() as i16,
-        }) {
-            Some(building) => match self.layer {
-                TileLayer::Floor => Content::Block(&content::blocks::STONE).into(),
-                TileLayer::Ore => Content::Block(&content::blocks::AIR).into(),
-                TileLayer::Block => Content::Block(building.block).into(),
-                TileLayer::Building => building.clone().into(),
+        };
+        let building = vm.building(position);
+        let result = match vm.world() {
+            Some(world) => match world.tile(position) {
+                Some(tile) => match self.layer {
+                    TileLayer::Floor => Content::Block(tile.floor).into(),
+                    TileLayer::Ore => Content::Block(tile.overlay).into(),
+                    TileLayer::Block => {
+                        Content::Block(building.map_or(tile.block, |b| b.block)).into()
+                    }
+                    TileLayer::Building => {
+                        building.cloned().map_or(LObject::Null, LObject::Building)
+                    }
+                },
+                None => LObject::Null,
+            },
+            // without a world, only tiles containing buildings exist
+            None => match building {
+                Some(building) => match self.layer {
+                    TileLayer::Floor => Content::Block(world::stone(vm.content())).into(),
+                    TileLayer::Ore => Content::Block(world::air(vm.content())).into(),
+                    TileLayer::Block => Content::Block(building.block).into(),
+                    TileLayer::Building => building.clone().into(),
+                },
+                None => LObject::Null,
             },
-            None => LObject::Null,
         };
         self.result.setobj(state, result);
     }
@@ -1422,3 +1798,269 @@ impl SimpleInstructionTrait for SetRate {
         state.ipt = self.value.get(state).numi().clamp(1, MAX_IPT) as f64;
     }
 }
+
+#[derive(Debug)]
+#[non_exhaustive]
+pub struct Message {
+    pub message_type: MessageType,
+    pub duration: LVar,
+    /// `None` if the output variable is `@wait`, meaning the processor should retry until the message is shown.
+    pub success: Option<LVar>,
+}
+
+impl InstructionTrait for Message {
+    fn execute(&self, state: &mut ProcessorState, vm: &LogicVM) -> InstructionResult {
+        // if there's no handler, behave like a headless server and discard the message
+        let shown = match vm.message_handler.try_borrow_mut() {
+            Ok(mut handler) => match &mut *handler {
+                Some(handler) => {
+                    let text = LocalePrint::resolve_message(&state.printbuffer, vm);
+                    handler(self.message_type, self.duration.get(state).num(), text)
+                }
+                None => true,
+            },
+            // the handler is already running (eg. a re-entrant call), so the message can't be shown yet
+            Err(_) => false,
+        };
+
+        if shown {
+            state.printbuffer.clear();
+        }
+
+        match &self.success {
+            Some(success) => {
+                success.setnum(state, shown.into());
+                InstructionResult::Ok
+            }
+            // jump back to this instruction and try again next tick
+            None if !shown => {
+                state.counter -= 1;
+                InstructionResult::Yield
+            }
+            None => InstructionResult::Ok,
+        }
+    }
+}
+
+#[derive(Debug)]
+#[non_exhaustive]
+pub struct LocalePrint {
+    pub value: LVar,
+}
+
+impl LocalePrint {
+    /// If the text starts with `@` and the rest is a key in the VM's locale bundle, returns the localized string.
+    fn resolve_message<'a>(text: &'a U16Str, vm: &'a LogicVM) -> &'a U16Str {
+        if let Some((&first, key)) = text.as_slice().split_first()
+            && first == '@' as u16
+            && let Some(value) = vm.locales.get(U16Str::from_slice(key))
+        {
+            value
+        } else {
+            text
+        }
+    }
+}
+
+impl SimpleInstructionTrait for LocalePrint {
+    fn execute(&self, state: &mut ProcessorState, vm: &LogicVM) {
+        if state.printbuffer.len() >= MAX_TEXT_BUFFER {
+            return;
+        }
+
+        let value = self.value.get_inner(state, &state.variables);
+        state.printbuffer += vm
+            .locales
+            .get_or_placeholder(&Print::to_string(&value))
+            .as_ustr();
+    }
+}
+
+#[derive(Debug)]
+#[non_exhaustive]
+pub struct Fetch {
+    pub fetch_type: FetchType,
+    pub result: LVar,
+    pub team: LVar,
+    pub index: LVar,
+    pub extra: LVar,
+}
+
+impl SimpleInstructionTrait for Fetch {
+    fn execute(&self, state: &mut ProcessorState, vm: &LogicVM) {
+        let Some(team) = self.team.get(state).team() else {
+            return;
+        };
+        let index = self.index.get(state).num_usize().ok();
+        let extra = self.extra.get(state);
+        let block = match extra.obj() {
+            Some(LObject::Content(Content::Block(block))) => Some(*block),
+            _ => None,
+        };
+        let unit_type = match extra.obj() {
+            Some(LObject::Content(Content::Unit(unit_type))) => Some(unit_type.id),
+            _ => None,
+        };
+
+        let team_index = vm.team_index(team);
+        let get = |buildings: &[Building]| index.and_then(|i| buildings.get(i)).cloned();
+
+        let units = || {
+            vm.team_units(team)
+                .filter(move |u| unit_type.is_none_or(|id| u.unit_type.id == id))
+        };
+        let players = || vm.team_units(team).filter(|u| u.data.borrow().player);
+
+        match self.fetch_type {
+            FetchType::Unit => {
+                let result = index.and_then(|i| units().nth(i)).cloned();
+                self.result.set(state, result.into());
+            }
+            FetchType::UnitCount => {
+                let result = units().count();
+                self.result.setnum(state, result as f64);
+            }
+
+            FetchType::Player => {
+                let result = index.and_then(|i| players().nth(i)).cloned();
+                self.result.set(state, result.into());
+            }
+            FetchType::PlayerCount => {
+                let result = players().count();
+                self.result.setnum(state, result as f64);
+            }
+
+            FetchType::Core => {
+                let result = team_index.and_then(|t| get(&t.cores));
+                self.result.set(state, result.into());
+            }
+            FetchType::CoreCount => {
+                let result = team_index.map_or(0, |t| t.cores.len());
+                self.result.setnum(state, result as f64);
+            }
+
+            FetchType::Build => {
+                let result =
+                    block.and_then(|block| team_index.and_then(|t| get(t.buildings_of(block))));
+                self.result.set(state, result.into());
+            }
+            FetchType::BuildCount => {
+                let result = team_index.map_or(0, |t| match block {
+                    Some(block) => t.buildings_of(block).len(),
+                    None => t.buildings.len(),
+                });
+                self.result.setnum(state, result as f64);
+            }
+        }
+    }
+}
+
+#[derive(Debug)]
+#[non_exhaustive]
+pub struct SetProp {
+    pub prop: LVar,
+    pub target: LVar,
+    pub value: LVar,
+}
+
+impl SetProp {
+    fn set_building(&self, building: &Building, vm: &LogicVM, prop: &LValue, value: &LValue) {
+        match prop.obj() {
+            Some(LObject::Sensor(LAccess::Health)) if value.isnum() => {
+                building.props.borrow_mut().set_health(value.num());
+            }
+            Some(LObject::Sensor(LAccess::Team)) => {
+                if let Some(team) = value.team()
+                    && team != building.team()
+                {
+                    building.props.borrow_mut().team = team;
+                    vm.invalidate_team_indexes();
+                    vm.invalidate_power_graphs();
+                }
+            }
+            Some(LObject::Sensor(LAccess::TotalPower)) => {
+                if let Some(power) = &mut building.props.borrow_mut().power {
+                    power.set_stored(value.num());
+                }
+            }
+            Some(LObject::Content(Content::Item(item))) => {
+                if let Some(items) = &mut building.props.borrow_mut().items {
+                    items.set_clamped(building.block, item, value.numi());
+                }
+            }
+            Some(LObject::Content(Content::Liquid(liquid))) => {
+                if let Some(liquids) = &mut building.props.borrow_mut().liquids {
+                    liquids.set_clamped(building.block, liquid, value.numf());
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn set_unit(&self, unit: &UnitEntity, prop: &LValue, value: &LValue) {
+        let mut data = unit.data.borrow_mut();
+        match (prop.obj(), value.try_num()) {
+            (Some(LObject::Sensor(LAccess::Team)), _) => {
+                if let Some(team) = value.team() {
+                    data.team = team;
+                }
+            }
+            (&Some(LObject::Sensor(sensor)), Some(value)) => match sensor {
+                LAccess::X => data.x = value,
+                LAccess::Y => data.y = value,
+                LAccess::Rotation => data.rotation = value,
+                LAccess::Health => data.health = value.clamp(0., data.max_health),
+                LAccess::Shield => data.shield = value.max(0.),
+                LAccess::Armor => data.armor = value.max(0.),
+                _ => {}
+            },
+            _ => {}
+        }
+    }
+}
+
+impl InstructionTrait for SetProp {
+    fn execute(&self, state: &mut ProcessorState, vm: &LogicVM) -> InstructionResult {
+        let prop = self.prop.get(state);
+        let value = self.value.get(state);
+
+        match self.target.get(state).obj() {
+            Some(LObject::Building(building)) => {
+                self.set_building(building, vm, &prop, &value);
+
+                if let Ok(mut data) = building.data.try_borrow_mut()
+                    && let BuildingData::Custom(custom) = &mut *data
+                {
+                    return custom.setprop(building, vm, prop, value);
+                }
+            }
+            Some(LObject::Unit(unit)) => self.set_unit(unit, &prop, &value),
+            _ => {}
+        }
+
+        InstructionResult::Ok
+    }
+}
+
+// custom
+
+/// A custom instruction registered with [`LogicVMBuilder::add_custom_instruction`](super::LogicVMBuilder::add_custom_instruction).
+pub(super) struct CustomInstruction {
+    pub arity: usize,
+    pub handler: Rc<CustomInstructionHandler>,
+}
+
+#[derive(Derivative)]
+#[derivative(Debug)]
+#[non_exhaustive]
+pub struct Custom {
+    pub args: Box<[LVar]>,
+    #[derivative(Debug = "ignore")]
+    pub handler: Rc<CustomInstructionHandler>,
+}
+
+impl InstructionTrait for Custom {
+    fn execute(&self, state: &mut ProcessorState, vm: &LogicVM) -> InstructionResult {
+        (self.handler)(&self.args, state, vm)
+    }
+}
@@ -0,0 +1,306 @@
+use alloc::{boxed::Box, vec, vec::Vec};
+
+use crate::types::{
+    PackedPoint2,
+    content::{Block, ContentVersion, Item, Liquid},
+};
+
+/// The items stored in a building, equivalent to Mindustry's `ItemModule`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ItemModule {
+    /// Item amounts, indexed by item id.
+    amounts: Box<[i32]>,
+    total: i32,
+    content: ContentVersion,
+}
+
+impl ItemModule {
+    /// Creates an empty module for the items of a content version.
+    pub fn new(content: ContentVersion) -> Self {
+        Self {
+            amounts: vec![0; content.items().values().len()].into(),
+            total: 0,
+            content,
+        }
+    }
+
+    pub fn get(&self, item: &Item) -> i32 {
+        self.amounts.get(item.id as usize).copied().unwrap_or(0)
+    }
+
+    /// Sets the amount of an item, ignoring the building's item capacity.
+    pub fn set(&mut self, item: &Item, amount: i32) {
+        if let Some(current) = self.amounts.get_mut(item.id as usize) {
+            self.total += amount - *current;
+            *current = amount;
+        }
+    }
+
+    /// Sets the amount of an item like Mindustry's `Building.setProp`.
+    ///
+    /// Increases are limited by the block's item capacity, and negative amounts are ignored.
+    pub fn set_clamped(&mut self, block: &Block, item: &Item, amount: i32) {
+        let current = self.get(item);
+        if amount > current {
+            self.set(item, amount.min(block.item_capacity.max(current)));
+        } else if amount >= 0 {
+            self.set(item, amount);
+        }
+    }
+
+    pub fn total(&self) -> i32 {
+        self.total
+    }
+
+    /// Returns the item with the lowest id that has a nonzero amount.
+    pub fn first(&self) -> Option<&'static Item> {
+        if self.total == 0 {
+            return None;
+        }
+        self.content
+            .items()
+            .values()
+            .iter()
+            .find(|item| self.get(item) > 0)
+    }
+
+    pub fn clear(&mut self) {
+        self.amounts.fill(0);
+        self.total = 0;
+    }
+}
+
+impl Default for ItemModule {
+    fn default() -> Self {
+        Self::new(ContentVersion::default())
+    }
+}
+
+/// The liquids stored in a building, equivalent to Mindustry's `LiquidModule`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LiquidModule {
+    /// Liquid amounts, indexed by liquid id.
+    amounts: Box<[f32]>,
+    /// The id of the most recently added liquid.
+    current: i32,
+    content: ContentVersion,
+}
+
+impl LiquidModule {
+    /// Creates an empty module for the liquids of a content version.
+    pub fn new(content: ContentVersion) -> Self {
+        Self {
+            amounts: vec![0.; content.liquids().values().len()].into(),
+            current: 0,
+            content,
+        }
+    }
+
+    pub fn get(&self, liquid: &Liquid) -> f32 {
+        self.amounts.get(liquid.id as usize).copied().unwrap_or(0.)
+    }
+
+    /// Sets the amount of a liquid, ignoring the building's liquid capacity.
+    ///
+    /// Adding a liquid makes it the current liquid.
+    pub fn set(&mut self, liquid: &Liquid, amount: f32) {
+        if let Some(current) = self.amounts.get_mut(liquid.id as usize) {
+            if amount > *current {
+                self.current = liquid.id;
+            }
+            *current = amount;
+        }
+    }
+
+    /// Sets the amount of a liquid like Mindustry's `Building.setProp`.
+    ///
+    /// The amount is clamped to the block's liquid capacity. Increases are only allowed if this is the current liquid, or if the current liquid is (almost) empty.
+    pub fn set_clamped(&mut self, block: &Block, liquid: &Liquid, amount: f32) {
+        let amount = amount.clamp(0., block.liquid_capacity);
+        if amount < self.get(liquid) || self.current == liquid.id || self.current_amount() <= 0.1 {
+            self.set(liquid, amount);
+        }
+    }
+
+    pub fn current(&self) -> Option<&'static Liquid> {
+        self.content
+            .liquids()
+            .values()
+            .iter()
+            .find(|liquid| liquid.id == self.current)
+    }
+
+    pub fn current_amount(&self) -> f32 {
+        self.amounts
+            .get(self.current as usize)
+            .copied()
+            .unwrap_or(0.)
+    }
+
+    pub fn clear(&mut self) {
+        self.amounts.fill(0.);
+    }
+}
+
+impl Default for LiquidModule {
+    fn default() -> Self {
+        Self::new(ContentVersion::default())
+    }
+}
+
+/// The power state of a building, equivalent to Mindustry's `PowerModule`.
+///
+/// All rates are in power units per tick (ie. 1/60 of the per-second values shown in-game).
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct PowerModule {
+    /// For batteries, the fraction of the buffer that is filled. For consumers, the fraction of the requested power that was received in the last update. Ranges from 0 to 1.
+    pub status: f64,
+    /// The size of this building's power buffer, or 0 if it has no buffer.
+    pub capacity: f64,
+    pub production: f64,
+    pub consumption: f64,
+    /// The positions of the buildings that this power node is linked to.
+    pub links: Vec<PackedPoint2>,
+}
+
+impl PowerModule {
+    /// A building with no production, consumption or buffer, eg. a power node.
+    pub fn node() -> Self {
+        Self::default()
+    }
+
+    pub fn battery(capacity: f64) -> Self {
+        Self {
+            capacity,
+            ..Default::default()
+        }
+    }
+
+    pub fn producer(production: f64) -> Self {
+        Self {
+            production,
+            ..Default::default()
+        }
+    }
+
+    pub fn consumer(consumption: f64) -> Self {
+        Self {
+            consumption,
+            ..Default::default()
+        }
+    }
+
+    /// Returns true if this building only consumes power. Adjacent consumers do not share power.
+    pub fn is_consumer_only(&self) -> bool {
+        self.consumption > 0. && self.production == 0. && self.capacity == 0.
+    }
+
+    pub fn stored(&self) -> f64 {
+        self.status * self.capacity
+    }
+
+    /// Sets the amount of stored power, clamped to the buffer's capacity.
+    pub fn set_stored(&mut self, amount: f64) {
+        self.status = if self.capacity > 0. {
+            (amount / self.capacity).clamp(0., 1.)
+        } else {
+            0.
+        };
+    }
+
+    /// Returns the value of `sensor @totalPower`, which is the stored power for batteries, or the satisfaction for consumers.
+    pub fn total(&self) -> f64 {
+        if self.capacity > 0. {
+            self.stored()
+        } else if self.consumption > 0. {
+            self.status
+        } else {
+            0.
+        }
+    }
+}
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+    use crate::types::content;
+
+    #[test]
+    fn test_items() {
+        let container = content::blocks::FROM_NAME["container"];
+        let copper = content::items::FROM_NAME["copper"];
+        let lead = content::items::FROM_NAME["lead"];
+
+        let mut items = ItemModule::new(ContentVersion::default());
+        assert_eq!(items.first(), None);
+
+        items.set_clamped(container, lead, 5);
+        items.set_clamped(container, copper, 100_000);
+        assert_eq!(items.get(lead), 5);
+        assert_eq!(items.get(copper), container.item_capacity);
+        assert_eq!(items.total(), container.item_capacity + 5);
+        assert_eq!(items.first(), Some(copper));
+
+        items.set_clamped(container, copper, -1);
+        assert_eq!(items.get(copper), container.item_capacity);
+
+        items.set_clamped(container, copper, 0);
+        assert_eq!(items.first(), Some(lead));
+
+        items.clear();
+        assert_eq!(items.total(), 0);
+        assert_eq!(items.first(), None);
+    }
+
+    #[test]
+    fn test_liquids() {
+        let tank = content::blocks::FROM_NAME["liquid-tank"];
+        let water = content::liquids::FROM_NAME["water"];
+        let slag = content::liquids::FROM_NAME["slag"];
+
+        let mut liquids = LiquidModule::new(ContentVersion::default());
+        liquids.set_clamped(tank, slag, 100.);
+        assert_eq!(liquids.current(), Some(slag));
+        assert_eq!(liquids.current_amount(), 100.);
+
+        // can't add a different liquid until the current one is empty
+        liquids.set_clamped(tank, water, 50.);
+        assert_eq!(liquids.get(water), 0.);
+
+        liquids.set_clamped(tank, slag, 0.);
+        liquids.set_clamped(tank, water, 1e9);
+        assert_eq!(liquids.current(), Some(water));
+        assert_eq!(liquids.get(water), tank.liquid_capacity);
+    }
+
+    #[test]
+    fn test_content_version() {
+        let v7 = ContentVersion::V7;
+        let copper = v7.items().from_name("copper").unwrap();
+        let water = v7.liquids().from_name("water").unwrap();
+
+        let mut items = ItemModule::new(v7);
+        items.set(copper, 1);
+        assert!(core::ptr::eq(items.first().unwrap(), copper));
+
+        let mut liquids = LiquidModule::new(v7);
+        liquids.set(water, 1.);
+        assert!(core::ptr::eq(liquids.current().unwrap(), water));
+    }
+
+    #[test]
+    fn test_power() {
+        let mut power = PowerModule::battery(1000.);
+        power.set_stored(250.);
+        assert_eq!(power.status, 0.25);
+        assert_eq!(power.stored(), 250.);
+
+        power.set_stored(5000.);
+        assert_eq!(power.stored(), 1000.);
+
+        power.set_stored(-1.);
+        assert_eq!(power.stored(), 0.);
+    }
+}
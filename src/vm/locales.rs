@@ -0,0 +1,292 @@
+use alloc::borrow::ToOwned;
+#[cfg(feature = "std")]
+use std::{collections::HashMap, string::String};
+
+use widestring::{U16Str, U16String};
+
+use crate::utils::{RapidHashMap, u16format};
+
+/// The locale used as a fallback if a key is missing from the selected locale.
+pub const DEFAULT_LOCALE: &str = "en";
+
+/// A set of localized strings, used by the `localeprint` and `message` instructions.
+///
+/// This is equivalent to the currently selected locale of Mindustry's `MapLocales`, with the default locale merged in as a fallback.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct LocaleBundle {
+    properties: RapidHashMap<U16String, U16String>,
+}
+
+impl LocaleBundle {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Loads a bundle from the value of a map's `locales` tag, which is a JSON object mapping locale names to objects of properties.
+    ///
+    /// Keys missing from `locale` fall back to [`DEFAULT_LOCALE`].
+    #[cfg(feature = "std")]
+    pub fn from_locales_tag(tag: &str, locale: &str) -> serde_json::Result<Self> {
+        let locales: HashMap<String, HashMap<String, String>> = serde_json::from_str(tag)?;
+
+        let mut bundle = Self::new();
+        for name in [DEFAULT_LOCALE, locale] {
+            if let Some(properties) = locales.get(name) {
+                bundle.extend(
+                    properties
+                        .iter()
+                        .map(|(k, v)| (U16String::from_str(k), U16String::from_str(v))),
+                );
+            }
+        }
+        Ok(bundle)
+    }
+
+    /// Loads a bundle from a Java properties file, eg. `bundle_en.properties`.
+    ///
+    /// Supports `=`, `:` and whitespace separators, `#` and `!` comments, line continuations, and the standard escape sequences (including `\uXXXX`).
+    pub fn from_properties(text: &str) -> Self {
+        let mut bundle = Self::new();
+
+        let mut lines = text.lines();
+        while let Some(line) = lines.next() {
+            let line = line.trim_start();
+            if line.is_empty() || line.starts_with(['#', '!']) {
+                continue;
+            }
+
+            // join continuation lines, ie. lines ending with an odd number of backslashes
+            let mut logical = U16String::new();
+            let mut line = line;
+            loop {
+                let trailing = line.chars().rev().take_while(|&c| c == '\\').count();
+                if trailing % 2 == 0 {
+                    logical.push_str(line);
+                    break;
+                }
+                logical.push_str(&line[..line.len() - 1]);
+                match lines.next() {
+                    Some(next) => line = next.trim_start(),
+                    None => break,
+                }
+            }
+
+            let (key, value) = split_property(logical.as_slice());
+            bundle.insert(unescape(key), unescape(value));
+        }
+
+        bundle
+    }
+
+    pub fn insert(&mut self, key: U16String, value: U16String) -> Option<U16String> {
+        self.properties.insert(key, value)
+    }
+
+    pub fn get(&self, key: &U16Str) -> Option<&U16Str> {
+        self.properties.get(key).map(|v| v.as_ustr())
+    }
+
+    pub fn contains_key(&self, key: &U16Str) -> bool {
+        self.properties.contains_key(key)
+    }
+
+    /// Looks up a key, returning `???key???` if it does not exist (like Mindustry).
+    pub fn get_or_placeholder(&self, key: &U16Str) -> U16String {
+        match self.get(key) {
+            Some(value) => value.to_owned(),
+            None => u16format!("???{}???", key.display()),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.properties.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.properties.is_empty()
+    }
+}
+
+impl Extend<(U16String, U16String)> for LocaleBundle {
+    fn extend<T: IntoIterator<Item = (U16String, U16String)>>(&mut self, iter: T) {
+        self.properties.extend(iter);
+    }
+}
+
+impl FromIterator<(U16String, U16String)> for LocaleBundle {
+    fn from_iter<T: IntoIterator<Item = (U16String, U16String)>>(iter: T) -> Self {
+        let mut bundle = Self::new();
+        bundle.extend(iter);
+        bundle
+    }
+}
+
+const BACKSLASH: u16 = b'\\' as u16;
+
+fn is_whitespace(c: u16) -> bool {
+    matches!(c, 0x20 | 0x09 | 0x0c)
+}
+
+fn is_separator(c: u16) -> bool {
+    c == b'=' as u16 || c == b':' as u16
+}
+
+/// Splits a logical line into its (still escaped) key and value.
+fn split_property(line: &[u16]) -> (&[u16], &[u16]) {
+    let mut i = 0;
+    while i < line.len() {
+        match line[i] {
+            BACKSLASH => i += 2,
+            c if is_whitespace(c) || is_separator(c) => break,
+            _ => i += 1,
+        }
+    }
+    let key = &line[..i.min(line.len())];
+
+    let mut rest = &line[i.min(line.len())..];
+    while let [c, tail @ ..] = rest
+        && is_whitespace(*c)
+    {
+        rest = tail;
+    }
+    if let [c, tail @ ..] = rest
+        && is_separator(*c)
+    {
+        rest = tail;
+    }
+    while let [c, tail @ ..] = rest
+        && is_whitespace(*c)
+    {
+        rest = tail;
+    }
+
+    (key, rest)
+}
+
+fn unescape(value: &[u16]) -> U16String {
+    let mut result = U16String::new();
+    let mut iter = value.iter().copied();
+    while let Some(c) = iter.next() {
+        if c != BACKSLASH {
+            result.push_slice([c]);
+            continue;
+        }
+        let Some(c) = iter.next() else {
+            break;
+        };
+        result.push_slice([match char::from_u32(c as u32) {
+            Some('n') => b'\n' as u16,
+            Some('t') => b'\t' as u16,
+            Some('r') => b'\r' as u16,
+            Some('f') => 0x0c,
+            Some('u') => {
+                let mut digits = iter.clone();
+                let value = (0..4).try_fold(0u16, |acc, _| {
+                    digits
+                        .next()
+                        .and_then(|d| char::from_u32(d as u32))
+                        .and_then(|d| d.to_digit(16))
+                        .map(|d| (acc << 4) | d as u16)
+                });
+                match value {
+                    Some(c) => {
+                        iter = digits;
+                        c
+                    }
+                    // keep malformed escapes verbatim
+                    None => {
+                        result.push_slice([BACKSLASH]);
+                        c
+                    }
+                }
+            }
+            _ => c,
+        }]);
+    }
+    result
+}
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use std::prelude::rust_2024::*;
+
+    use pretty_assertions::assert_eq;
+    use widestring::u16str;
+
+    use super::*;
+
+    #[test]
+    fn test_from_properties() {
+        let bundle = LocaleBundle::from_properties(
+            r"
+            # comment
+            ! also a comment
+            simple = Hello, world!
+            colon:value
+            space value with spaces
+            escaped\ key = a\tb\nc é
+            multi = first \
+                    second
+            empty =
+            ",
+        );
+
+        assert_eq!(bundle.len(), 6);
+        assert_eq!(
+            bundle.get(u16str!("simple")),
+            Some(u16str!("Hello, world!"))
+        );
+        assert_eq!(bundle.get(u16str!("colon")), Some(u16str!("value")));
+        assert_eq!(
+            bundle.get(u16str!("space")),
+            Some(u16str!("value with spaces"))
+        );
+        assert_eq!(
+            bundle.get(u16str!("escaped key")),
+            Some(u16str!("a\tb\nc é"))
+        );
+        assert_eq!(bundle.get(u16str!("multi")), Some(u16str!("first second")));
+        assert_eq!(bundle.get(u16str!("empty")), Some(u16str!("")));
+    }
+
+    #[test]
+    fn test_unicode_escapes() {
+        let bundle = LocaleBundle::from_properties(
+            r"
+            valid = \u0041\u00e9
+            short = a\u12
+            invalid = \u12g4\n
+            ",
+        );
+
+        assert_eq!(bundle.get(u16str!("valid")), Some(u16str!("Aé")));
+        assert_eq!(bundle.get(u16str!("short")), Some(u16str!("a\\u12")));
+        assert_eq!(bundle.get(u16str!("invalid")), Some(u16str!("\\u12g4\n")));
+    }
+
+    #[test]
+    fn test_from_locales_tag() {
+        let tag = r#"{
+            "en": {"greeting": "Hello", "farewell": "Bye"},
+            "fr": {"greeting": "Bonjour"}
+        }"#;
+
+        let bundle = LocaleBundle::from_locales_tag(tag, "fr").unwrap();
+        assert_eq!(bundle.get(u16str!("greeting")), Some(u16str!("Bonjour")));
+        assert_eq!(bundle.get(u16str!("farewell")), Some(u16str!("Bye")));
+
+        let bundle = LocaleBundle::from_locales_tag(tag, "de").unwrap();
+        assert_eq!(bundle.get(u16str!("greeting")), Some(u16str!("Hello")));
+
+        assert!(LocaleBundle::from_locales_tag("[]", "en").is_err());
+    }
+
+    #[test]
+    fn test_placeholder() {
+        let bundle = LocaleBundle::new();
+        assert_eq!(
+            bundle.get_or_placeholder(u16str!("missing")),
+            u16str!("???missing???")
+        );
+    }
+}
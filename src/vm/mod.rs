@@ -1,43 +1,93 @@
-use alloc::{rc::Rc, string::String, vec::Vec};
-use core::{cell::Cell, time::Duration};
+use alloc::{boxed::Box, rc::Rc, string::String, vec::Vec};
+use core::{
+    cell::{Cell, Ref, RefCell, RefMut},
+    time::Duration,
+};
 #[cfg(feature = "std")]
 use std::time::Instant;
 
 use thiserror::Error;
+use widestring::U16Str;
 
 #[cfg(feature = "embedded_graphics")]
 pub use self::draw::embedded::{EmbeddedDisplayData, EmbeddedDisplayInitError};
-use self::variables::Constants;
+pub(crate) use self::processor::link_name_prefix;
 pub use self::{
-    buildings::{Building, BuildingData, CustomBuildingData},
+    buildings::{Building, BuildingData, BuildingProps, CustomBuildingData},
     draw::{DrawCommand, TextAlignment},
     instructions::InstructionResult,
-    processor::{InstructionHook, Processor, ProcessorBuilder, ProcessorState},
+    inventory::{ItemModule, LiquidModule, PowerModule},
+    locales::LocaleBundle,
+    power::{PowerGraph, PowerGraphs, default_power},
+    processor::{InstructionHook, Processor, ProcessorBuilder, ProcessorLimits, ProcessorState},
+    teams::TeamIndex,
+    turret::TurretData,
+    units::{UnitData, UnitEntity},
     variables::{Content, LObject, LString, LValue, LVar},
+    world::{World, WorldTile},
 };
+use self::{instructions::CustomInstruction, variables::Constants};
 #[cfg(feature = "std")]
-use crate::types::{Schematic, SchematicTile};
 use crate::{
-    types::{PackedPoint2, content::Block},
+    parser::SyntaxError,
+    types::{Map, MapProcessorState, Schematic, SchematicTile},
+};
+use crate::{
+    parser::ast::{MessageType, Span},
+    types::{
+        PackedPoint2, Team,
+        content::{self, Block, ContentVersion},
+    },
     utils::RapidHashMap,
 };
 
 pub mod buildings;
 mod draw;
 pub mod instructions;
+mod inventory;
+pub mod locales;
+mod power;
 mod processor;
+mod teams;
+mod turret;
+mod units;
 pub mod variables;
+mod world;
 
 const MILLIS_PER_SEC: u64 = 1_000;
 const NANOS_PER_MILLI: u32 = 1_000_000;
 
+/// Called by the `message` instruction with the message type, the duration in seconds, and the text to display.
+///
+/// Returns `false` if the message could not be shown right now (eg. because another announcement is still visible), in which case the processor's printbuffer is kept so that the message can be retried.
+pub type MessageHandler = dyn FnMut(MessageType, f64, &U16Str) -> bool;
+
+/// Executes a custom instruction registered with [`LogicVMBuilder::add_custom_instruction`].
+///
+/// The arguments are resolved like those of built-in instructions, so they can be read with [`LVar::get`] and written with [`LVar::set`].
+pub type CustomInstructionHandler =
+    dyn Fn(&[LVar], &mut ProcessorState, &LogicVM) -> InstructionResult;
+
 pub struct LogicVM {
     /// Sorted with all processors in update order first, then all other buildings in arbitrary order.
     buildings: Vec<Building>,
     buildings_map: RapidHashMap<PackedPoint2, usize>,
+    /// Sorted in the order that units were added.
+    units: Vec<UnitEntity>,
+    next_unit_id: u32,
     total_processors: usize,
     running_processors: Rc<Cell<usize>>,
     time: Rc<Cell<f64>>,
+    message_handler: RefCell<Option<Box<MessageHandler>>>,
+    locales: LocaleBundle,
+    /// Lazily built by [`Self::team_index`], and cleared whenever buildings are added or removed.
+    team_indexes: RefCell<Option<RapidHashMap<Team, TeamIndex>>>,
+    /// Lazily built by [`Self::power_graphs`], and cleared whenever buildings are added or removed.
+    power_graphs: RefCell<Option<PowerGraphs>>,
+    world: Option<World>,
+    content: ContentVersion,
+    lenient: bool,
+    custom_instructions: RapidHashMap<String, CustomInstruction>,
 }
 
 impl LogicVM {
@@ -48,9 +98,19 @@ impl LogicVM {
         Self {
             buildings: Vec::new(),
             buildings_map: RapidHashMap::default(),
+            units: Vec::new(),
+            next_unit_id: 0,
             total_processors: 0,
             running_processors: Rc::new(Cell::new(0)),
             time: Rc::new(Cell::new(0.)),
+            message_handler: RefCell::new(None),
+            locales: LocaleBundle::new(),
+            team_indexes: RefCell::new(None),
+            power_graphs: RefCell::new(None),
+            world: None,
+            content: ContentVersion::default(),
+            lenient: false,
+            custom_instructions: RapidHashMap::default(),
         }
     }
 
@@ -66,12 +126,39 @@ impl LogicVM {
         builder.build()
     }
 
+    /// Exports every building in this VM to a new schematic, which can be loaded with [`Self::from_schematic`] or pasted into Mindustry.
+    ///
+    /// Tiles are placed at the same positions as the buildings, without normalizing the schematic.
+    #[cfg(feature = "std")]
+    pub fn to_schematic(&self) -> Result<Schematic, VMExportError> {
+        let mut schematic = Schematic::new();
+        for building in &self.buildings {
+            schematic.add_tile(building.to_schematic_tile()?);
+        }
+        Ok(schematic)
+    }
+
+    /// Creates a VM from the buildings, tiles, rules and tags of a map.
+    ///
+    /// See [`LogicVMBuilder::add_map`].
+    #[cfg(feature = "std")]
+    pub fn from_map(map: &Map) -> VMLoadResult<Self> {
+        let mut builder = LogicVMBuilder::new();
+        builder.add_map(map)?;
+        builder.build()
+    }
+
     pub fn from_buildings(buildings: impl IntoIterator<Item = Building>) -> VMLoadResult<Self> {
         let mut builder = LogicVMBuilder::new();
         builder.add_buildings(buildings);
         builder.build()
     }
 
+    /// Returns every building in this VM.
+    pub fn buildings(&self) -> &[Building] {
+        &self.buildings
+    }
+
     pub fn building(&self, position: PackedPoint2) -> Option<&Building> {
         self.buildings_map
             .get(&position)
@@ -127,6 +214,9 @@ impl LogicVM {
             self.buildings_map.insert(position, index);
         }
 
+        self.invalidate_team_indexes();
+        self.invalidate_power_graphs();
+
         Ok(())
     }
 
@@ -181,9 +271,48 @@ impl LogicVM {
             self.buildings_map.remove(&position);
         }
 
+        self.invalidate_team_indexes();
+        self.invalidate_power_graphs();
+
         Some(building)
     }
 
+    pub fn units(&self) -> &[UnitEntity] {
+        &self.units
+    }
+
+    pub fn unit(&self, id: u32) -> Option<&UnitEntity> {
+        self.units.iter().find(|u| u.id == id)
+    }
+
+    /// Returns an iterator over all living units owned by a team, in the order they were added.
+    pub fn team_units(&self, team: Team) -> impl Iterator<Item = &UnitEntity> {
+        self.units.iter().filter(move |u| {
+            let data = u.data.borrow();
+            data.team == team && !data.dead
+        })
+    }
+
+    /// Add a new unit to the VM, returning a handle that can be used to update its state.
+    pub fn add_unit(&mut self, unit_type: &'static content::Unit, data: UnitData) -> UnitEntity {
+        let unit = UnitEntity {
+            unit_type,
+            id: self.next_unit_id,
+            data: Rc::new(RefCell::new(data)),
+        };
+        self.next_unit_id += 1;
+        self.units.push(unit.clone());
+        unit
+    }
+
+    /// Remove a unit from the VM, marking it as dead.
+    pub fn remove_unit(&mut self, id: u32) -> Option<UnitEntity> {
+        let index = self.units.iter().position(|u| u.id == id)?;
+        let unit = self.units.remove(index);
+        unit.data.borrow_mut().dead = true;
+        Some(unit)
+    }
+
     /// Run the simulation until all processors halt, or until a number of ticks are finished.
     /// Returns true if all processors halted, or false if the tick limit was reached.
     #[cfg(feature = "std")]
@@ -232,6 +361,31 @@ impl LogicVM {
         let time = duration_millis_f64(time);
         self.time.set(time);
 
+        self.power_graphs_mut().update(delta);
+
+        for building in self.buildings.iter().skip(self.total_processors) {
+            match &mut *building.data.borrow_mut() {
+                BuildingData::Turret(turret) => turret.update(building, delta),
+
+                // like Building.update, re-enable the building once no processor has controlled it for a while
+                BuildingData::Conveyor {
+                    enabled,
+                    enabled_control_time,
+                }
+                | BuildingData::Drill {
+                    enabled,
+                    enabled_control_time,
+                } if *enabled_control_time > 0. => {
+                    *enabled_control_time -= delta;
+                    if *enabled_control_time <= 0. {
+                        *enabled = true;
+                    }
+                }
+
+                _ => {}
+            }
+        }
+
         for processor in self.iter_processors() {
             processor
                 .data
@@ -245,6 +399,43 @@ impl LogicVM {
         self.buildings.iter().take(self.total_processors)
     }
 
+    /// Returns the buildings owned by a team, or `None` if the team has no buildings.
+    pub fn team_index(&self, team: Team) -> Option<Ref<'_, TeamIndex>> {
+        if self.team_indexes.borrow().is_none() {
+            self.team_indexes
+                .replace(Some(TeamIndex::build_all(&self.buildings)));
+        }
+        Ref::filter_map(self.team_indexes.borrow(), |indexes| {
+            indexes.as_ref().and_then(|indexes| indexes.get(&team))
+        })
+        .ok()
+    }
+
+    /// Clears the cached team indexes. This must be called after changing the team of a building without using `setprop`.
+    pub fn invalidate_team_indexes(&self) {
+        self.team_indexes.replace(None);
+    }
+
+    /// Returns the power graphs of all buildings in this VM.
+    pub fn power_graphs(&self) -> Ref<'_, PowerGraphs> {
+        self.power_graphs_mut();
+        Ref::map(self.power_graphs.borrow(), |graphs| {
+            graphs.as_ref().unwrap()
+        })
+    }
+
+    fn power_graphs_mut(&self) -> RefMut<'_, PowerGraphs> {
+        RefMut::map(self.power_graphs.borrow_mut(), |graphs| {
+            graphs
+                .get_or_insert_with(|| PowerGraphs::build_all(&self.buildings, &self.buildings_map))
+        })
+    }
+
+    /// Clears the cached power graphs. This must be called after changing the power links or team of a building without using `setprop`.
+    pub fn invalidate_power_graphs(&self) {
+        self.power_graphs.replace(None);
+    }
+
     pub fn running_processors(&self) -> usize {
         self.running_processors.get()
     }
@@ -256,6 +447,43 @@ impl LogicVM {
     pub fn time(&self) -> Duration {
         Duration::from_secs_f64(self.time.get() / 1000.)
     }
+
+    /// Sets the callback used to display messages from the `message` instruction.
+    ///
+    /// If no handler is set, messages are discarded, like on a headless server.
+    pub fn set_message_handler(&mut self, handler: Option<Box<MessageHandler>>) {
+        self.message_handler = RefCell::new(handler);
+    }
+
+    pub fn locales(&self) -> &LocaleBundle {
+        &self.locales
+    }
+
+    /// Sets the locale bundle used by the `localeprint` and `message` instructions.
+    pub fn set_locales(&mut self, locales: LocaleBundle) {
+        self.locales = locales;
+    }
+
+    /// Returns the world that this VM's buildings are placed in, or `None` if the VM was not loaded from a map.
+    pub fn world(&self) -> Option<&World> {
+        self.world.as_ref()
+    }
+
+    pub fn world_mut(&mut self) -> Option<&mut World> {
+        self.world.as_mut()
+    }
+
+    /// Returns the version of Mindustry's content used by this VM.
+    pub fn content(&self) -> ContentVersion {
+        self.content
+    }
+
+    /// Returns true if unknown instructions are loaded as no-ops instead of causing an error.
+    ///
+    /// See [`LogicVMBuilder::set_lenient`].
+    pub fn lenient(&self) -> bool {
+        self.lenient
+    }
 }
 
 impl Default for LogicVM {
@@ -274,6 +502,9 @@ pub struct LogicVMBuilder {
     vm: LogicVM,
     processors: Vec<Building>,
     other_buildings: Vec<Building>,
+    /// Processor states from a map, which can only be restored after the processors are initialized.
+    #[cfg(feature = "std")]
+    processor_states: Vec<(Building, MapProcessorState)>,
 }
 
 impl LogicVMBuilder {
@@ -282,6 +513,8 @@ impl LogicVMBuilder {
             vm: LogicVM::new(),
             processors: Vec::new(),
             other_buildings: Vec::new(),
+            #[cfg(feature = "std")]
+            processor_states: Vec::new(),
         }
     }
 
@@ -301,7 +534,7 @@ impl LogicVMBuilder {
 
     #[cfg(feature = "std")]
     pub fn add_schematic_tile(&mut self, tile: &SchematicTile) -> VMLoadResult<()> {
-        let building = Building::from_schematic_tile(tile, &*self)?;
+        let building = Building::from_schematic_tile_center(tile, &*self)?;
         self.add_building(building);
         Ok(())
     }
@@ -314,12 +547,86 @@ impl LogicVMBuilder {
         Ok(())
     }
 
+    /// Adds the buildings from a map, and sets the world to the map's tiles, rules and tags.
+    ///
+    /// Processors keep the variables that were saved in the map, like in Mindustry.
+    #[cfg(feature = "std")]
+    pub fn add_map(&mut self, map: &Map) -> VMLoadResult<()> {
+        for map_building in &map.buildings {
+            let building = Building::from_map_building(map_building, &*self)?;
+            if let Some(state) = &map_building.processor {
+                self.processor_states
+                    .push((building.clone(), state.clone()));
+            }
+            self.add_building(building);
+        }
+        self.set_world(Some(World::from_map(map, self.vm.content)));
+        Ok(())
+    }
+
     pub fn vm(&self) -> &LogicVM {
         &self.vm
     }
 
+    /// Sets the world that the VM's buildings are placed in.
+    pub fn set_world(&mut self, world: Option<World>) {
+        self.vm.world = world;
+    }
+
+    /// Sets the version of Mindustry's content used by the VM, eg. for block names, `lookup` and `@blockCount`.
+    ///
+    /// This should be called before adding any buildings, since buildings look up their blocks when they're created.
+    pub fn set_content_version(&mut self, content: ContentVersion) {
+        self.vm.content = content;
+    }
+
+    /// If enabled, unknown instructions are loaded as no-ops instead of causing an error, like in Mindustry.
+    ///
+    /// This allows code written for newer versions of the game to run. The skipped instructions are listed in [`Processor::warnings`].
+    pub fn set_lenient(&mut self, lenient: bool) {
+        self.vm.lenient = lenient;
+    }
+
+    /// Registers a custom instruction, eg. for host I/O. When executed, `handler` is called with the first `arity` arguments of the instruction.
+    ///
+    /// Custom instructions are parsed like any other unknown instruction, so they can't replace built-in instructions. Extra arguments are ignored, and missing arguments cause a load error.
+    pub fn add_custom_instruction<F>(&mut self, name: impl Into<String>, arity: usize, handler: F)
+    where
+        F: Fn(&[LVar], &mut ProcessorState, &LogicVM) -> InstructionResult + 'static,
+    {
+        self.vm.custom_instructions.insert(
+            name.into(),
+            CustomInstruction {
+                arity,
+                handler: Rc::new(handler),
+            },
+        );
+    }
+
+    /// Returns the name and arity of each custom instruction registered with [`Self::add_custom_instruction`], eg. for [`Linter::custom_instructions`](crate::lint::Linter::custom_instructions).
+    pub fn custom_instructions(&self) -> impl Iterator<Item = (&str, usize)> {
+        self.vm
+            .custom_instructions
+            .iter()
+            .map(|(name, custom)| (name.as_str(), custom.arity))
+    }
+
+    /// See [`LogicVM::set_message_handler`].
+    pub fn set_message_handler(&mut self, handler: Option<Box<MessageHandler>>) {
+        self.vm.set_message_handler(handler);
+    }
+
+    /// See [`LogicVM::set_locales`].
+    pub fn set_locales(&mut self, locales: LocaleBundle) {
+        self.vm.set_locales(locales);
+    }
+
     pub fn build(self) -> VMLoadResult<LogicVM> {
-        self.build_with_globals(&LVar::create_global_constants())
+        let mut globals = LVar::create_global_constants_for(self.vm.content);
+        if let Some(world) = &self.vm.world {
+            LVar::add_world_constants(&mut globals, world);
+        }
+        self.build_with_globals(&globals)
     }
 
     pub fn build_with_globals(mut self, globals: &Constants) -> VMLoadResult<LogicVM> {
@@ -350,6 +657,9 @@ impl LogicVMBuilder {
             }
         }
 
+        vm.invalidate_team_indexes();
+        vm.invalidate_power_graphs();
+
         for processor in vm.iter_processors() {
             processor
                 .data
@@ -358,6 +668,27 @@ impl LogicVMBuilder {
                 .late_init(&vm, processor, globals)?;
         }
 
+        #[cfg(feature = "std")]
+        for (building, state) in &self.processor_states {
+            building
+                .data
+                .borrow_mut()
+                .unwrap_processor_mut()
+                .restore_map_state(state, &vm);
+        }
+
+        if let Some(world) = &vm.world
+            && world.rules.disable_world_processors
+        {
+            for processor in vm.iter_processors() {
+                let mut data = processor.data.borrow_mut();
+                let state = &mut data.unwrap_processor_mut().state;
+                if state.privileged() {
+                    state.set_enabled(false);
+                }
+            }
+        }
+
         Ok(vm)
     }
 }
@@ -394,8 +725,12 @@ pub enum VMLoadError {
     #[error("failed to decode processor config")]
     BadProcessorConfig(#[from] binrw::Error),
 
+    #[cfg(feature = "std")]
     #[error("failed to parse processor code: {0}")]
-    BadProcessorCode(String),
+    BadProcessorCode(#[from] SyntaxError),
+
+    #[error("invalid instruction at {span}: {error}")]
+    BadInstruction { span: Span, error: InstructionError },
 
     #[error("attempted to call late_init on an already-initialized instruction")]
     AlreadyInitialized,
@@ -405,6 +740,73 @@ pub enum VMLoadError {
         position: PackedPoint2,
         current: &'static Block,
     },
+
+    #[error("too many instructions: {count} (max {max})")]
+    TooManyInstructions {
+        /// The location of the first instruction over the limit.
+        span: Span,
+        count: usize,
+        max: usize,
+    },
+
+    #[error("code is too long: {bytes} bytes (max {max})")]
+    CodeTooLong { bytes: usize, max: usize },
+
+    #[error("too many tokens at {span}: {count} (max {max})")]
+    TooManyTokens { span: Span, count: usize, max: usize },
+
+    #[error("can't check the code length and token limits without the processor's source code")]
+    UncheckedLimits,
+
+    #[error("too many links: {count} (max {max})")]
+    TooManyLinks { count: usize, max: usize },
+
+    #[error("too many variables: {count} (max {max})")]
+    TooManyVariables { count: usize, max: usize },
+}
+
+impl VMLoadError {
+    /// Returns the location in the processor code that caused this error, if any.
+    pub fn span(&self) -> Option<Span> {
+        match self {
+            #[cfg(feature = "std")]
+            Self::BadProcessorCode(error) => Some(error.span),
+            Self::BadInstruction { span, .. }
+            | Self::TooManyInstructions { span, .. }
+            | Self::TooManyTokens { span, .. } => Some(*span),
+            _ => None,
+        }
+    }
+}
+
+/// An error encountered while initializing a parsed instruction.
+#[derive(Error, Debug, Clone, PartialEq)]
+pub enum InstructionError {
+    #[error("label not found: {0}")]
+    LabelNotFound(String),
+
+    #[error("jump out of range: {0}")]
+    JumpOutOfRange(f64),
+
+    #[error("unknown instruction: {0}")]
+    UnknownInstruction(String),
+
+    #[error("not enough arguments for {name}: expected {want}, got {got}")]
+    NotEnoughArguments {
+        name: String,
+        want: usize,
+        got: usize,
+    },
+}
+
+#[cfg(feature = "std")]
+#[derive(Error, Debug)]
+pub enum VMExportError {
+    #[error("processor at {0} has no source code")]
+    MissingProcessorCode(PackedPoint2),
+
+    #[error("failed to encode processor config")]
+    BadProcessorConfig(#[from] binrw::Error),
 }
 
 #[cfg(all(test, not(feature = "std"), feature = "no_std"))]
@@ -428,6 +830,10 @@ mod tests {
             block: &content::blocks::AIR,
             position: PackedPoint2 { x: 1, y: 0 },
             data: gpio_data.clone(),
+            props: Rc::new(RefCell::new(BuildingProps::new(
+                &content::blocks::AIR,
+                ContentVersion::default(),
+            ))),
         };
 
         let mut globals = LVar::create_global_constants();
@@ -450,6 +856,7 @@ mod tests {
                             from: ast::Value::Variable("@ipt".into()),
                         },
                         vec![],
+                        ast::Span::default(),
                     ),
                     ast::Statement::Instruction(
                         ast::Instruction::Set {
@@ -457,6 +864,7 @@ mod tests {
                             from: ast::Value::Variable("@this".into()),
                         },
                         vec![],
+                        ast::Span::default(),
                     ),
                     ast::Statement::Instruction(
                         ast::Instruction::Write {
@@ -465,14 +873,22 @@ mod tests {
                             address: ast::Value::Number(25.),
                         },
                         vec![],
+                        ast::Span::default(),
+                    ),
+                    ast::Statement::Instruction(
+                        ast::Instruction::Stop,
+                        vec![],
+                        ast::Span::default(),
                     ),
-                    ast::Statement::Instruction(ast::Instruction::Stop, vec![]),
                 ]),
+                source: None,
                 links: &[],
                 instruction_hook: None,
+                limits: None,
             },
             &builder,
-        )]);
+        )
+        .unwrap()]);
         let mut vm = builder.build_with_globals(&globals).unwrap();
 
         vm.do_tick(Duration::ZERO);
@@ -506,7 +922,7 @@ mod tests {
 
 #[cfg(all(test, feature = "std"))]
 mod tests {
-    use std::{format, io::Cursor, prelude::rust_2024::*, thread_local, vec};
+    use std::{borrow::Cow, format, io::Cursor, prelude::rust_2024::*, thread_local, vec};
 
     use binrw::{BinRead, BinWrite};
     use itertools::Itertools;
@@ -516,17 +932,20 @@ mod tests {
 
     use super::{
         buildings::{
-            HYPER_PROCESSOR, LOGIC_PROCESSOR, MEMORY_BANK, MEMORY_CELL, MESSAGE, MICRO_PROCESSOR,
-            SWITCH, WORLD_CELL, WORLD_PROCESSOR,
+            DOOR, HYPER_PROCESSOR, ILLUMINATOR, LOGIC_PROCESSOR, MEMORY_BANK, MEMORY_CELL, MESSAGE,
+            MICRO_PROCESSOR, SORTER, SWITCH, UNLOADER, WORLD_CELL, WORLD_PROCESSOR,
         },
         instructions::Instruction,
         variables::Constants,
         *,
     };
     use crate::{
+        parser::{self, ast::Location},
         types::{
-            ContentID, ContentType, Object, PackedPoint2, ProcessorConfig, ProcessorLinkConfig,
-            Team, colors::COLORS, content,
+            ContentID, ContentType, LAccess, MapBuilding, MapTile, Object, PackedPoint2,
+            ProcessorConfig, ProcessorLinkConfig, Team,
+            colors::{COLORS, rgba8888_to_double_bits},
+            content::{self, ContentVersion},
         },
         utils::u16format,
     };
@@ -589,6 +1008,7 @@ mod tests {
             .data
             .replace(BuildingData::Unknown {
                 senseable_config: None,
+                config: Object::Null,
             })
             .into_processor()
     }
@@ -759,48 +1179,304 @@ mod tests {
         assert!(processor.state.stopped());
     }
 
+    #[test]
+    fn test_to_schematic() {
+        let code = r#"
+            control enabled switch1 true
+            control config sorter1 @copper
+            print "hello"
+            printflush message1
+            fetch build conveyor @sharded 0 @conveyor
+            sensor rotation conveyor @rotation
+            stop
+        "#;
+
+        let tile = |block: &str, x, config: Object, rotation| SchematicTile {
+            block: block.into(),
+            position: PackedPoint2 { x, y: 0 },
+            config,
+            rotation,
+        };
+
+        let mut vm = LogicVM::from_schematic_tiles(&[
+            tile(
+                WORLD_PROCESSOR,
+                0,
+                ProcessorConfig {
+                    code: code.into(),
+                    links: vec![
+                        ProcessorLinkConfig::unnamed(2, 0),
+                        ProcessorLinkConfig::unnamed(3, 0),
+                        ProcessorLinkConfig::unnamed(4, 0),
+                    ],
+                }
+                .to_object()
+                .unwrap(),
+                0,
+            ),
+            tile(SWITCH, 2, false.into(), 0),
+            tile(MESSAGE, 3, "".to_string().into(), 0),
+            tile(SORTER, 4, Object::Null, 0),
+            tile(
+                "power-node",
+                5,
+                vec![PackedPoint2 { x: 1, y: 0 }].into(),
+                0,
+            ),
+            tile("battery", 6, Object::Null, 0),
+            tile("conveyor", 7, Object::Null, 1),
+        ])
+        .unwrap();
+
+        run(&mut vm, 2, true);
+
+        let schematic = vm.to_schematic().unwrap();
+        assert_eq!(schematic.tiles(), &vec![
+            tile(
+                WORLD_PROCESSOR,
+                0,
+                ProcessorConfig {
+                    code: code.into(),
+                    links: vec![
+                        ProcessorLinkConfig {
+                            name: "switch1".into(),
+                            x: 2,
+                            y: 0,
+                        },
+                        ProcessorLinkConfig {
+                            name: "message1".into(),
+                            x: 3,
+                            y: 0,
+                        },
+                        ProcessorLinkConfig {
+                            name: "sorter1".into(),
+                            x: 4,
+                            y: 0,
+                        },
+                    ],
+                }
+                .to_object()
+                .unwrap(),
+                0,
+            ),
+            tile(SWITCH, 2, true.into(), 0),
+            tile(MESSAGE, 3, "hello".to_string().into(), 0),
+            tile(
+                SORTER,
+                4,
+                ContentID {
+                    type_: ContentType::Item,
+                    id: content::items::FROM_NAME["copper"].id as i16,
+                }
+                .into(),
+                0,
+            ),
+            tile(
+                "power-node",
+                5,
+                vec![PackedPoint2 { x: 1, y: 0 }].into(),
+                0,
+            ),
+            tile("battery", 6, Object::Null, 0),
+            tile("conveyor", 7, Object::Null, 1),
+        ]);
+
+        // the exported schematic can be loaded again
+        let mut cur = Cursor::new(Vec::new());
+        schematic.write(&mut cur).unwrap();
+        cur.set_position(0);
+        let mut vm2 = LogicVM::from_schematic(&Schematic::read(&mut cur).unwrap()).unwrap();
+        run(&mut vm2, 2, true);
+        assert_eq!(vm2.to_schematic().unwrap().tiles(), schematic.tiles());
+
+        vm.building(PackedPoint2 { x: 0, y: 0 })
+            .unwrap()
+            .data
+            .borrow_mut()
+            .unwrap_processor_mut()
+            .set_source(None);
+        assert!(matches!(
+            vm.to_schematic(),
+            Err(VMExportError::MissingProcessorCode(PackedPoint2 {
+                x: 0,
+                y: 0
+            }))
+        ));
+
+        let processor = take_processor(&mut vm2, (0, 0));
+        assert_variables(&processor, map_iter! {
+            u16str!("rotation"): LValue::from(1),
+        });
+    }
+
+    #[test]
+    fn test_from_map() {
+        let building = |block: &str, x, y, config: Object| MapBuilding {
+            block: block.into(),
+            position: PackedPoint2 { x, y },
+            team: Team::SHARDED,
+            rotation: 0,
+            enabled: true,
+            health: 1000.,
+            items: vec![],
+            liquids: vec![],
+            power: None,
+            config,
+            processor: None,
+            memory: None,
+            data: vec![],
+        };
+
+        let mut processor = building(
+            WORLD_PROCESSOR,
+            0,
+            3,
+            ProcessorConfig {
+                code: "
+                getblock floor floor 5 0
+                getblock ore ore 5 0
+                getblock block block 5 3
+                getblock building core 2 0
+                getblock floor outside 6 0
+                sensor copper core @copper
+                sensor team core @team
+                read cell cell1 1
+                set wave @waveNumber
+                set mapw @mapw
+                set ipt @ipt
+                set restored x
+                set restored_core saved
+                stop
+                "
+                .into(),
+                links: vec![ProcessorLinkConfig::unnamed(1, 0)],
+            }
+            .to_object()
+            .unwrap(),
+        );
+        processor.processor = Some(MapProcessorState {
+            variables: vec![
+                ("x".into(), Object::Double(4.)),
+                ("saved".into(), Object::Building((3 << 16) | 1)),
+                ("removed".into(), Object::Double(1.)),
+            ],
+            ipt: Some(100),
+        });
+
+        let mut cell = building(MEMORY_CELL, 1, 3, Object::Null);
+        cell.memory = Some(vec![1.5, 2.5]);
+
+        // a 3x3 block centered at (3, 1)
+        let mut core = building("core-shard", 3, 1, Object::Null);
+        core.team = Team::CRUX;
+        core.items = vec![("copper".into(), 100)];
+
+        let mut map = Map {
+            version: 7,
+            tags: [("wave", "5"), ("rules", "{waves:true}")]
+                .into_iter()
+                .map(|(k, v)| (k.into(), v.into()))
+                .collect(),
+            width: 6,
+            height: 4,
+            blocks: vec!["air".into(), "stone".into(), "sand-floor".into(), "ore-copper".into()],
+            tiles: vec![
+                MapTile {
+                    floor: 1,
+                    overlay: 0,
+                    block: 0,
+                };
+                24
+            ],
+            buildings: vec![processor, cell, core],
+        };
+        map.tiles[5] = MapTile {
+            floor: 2,
+            overlay: 3,
+            block: 0,
+        };
+
+        let mut vm = LogicVM::from_map(&map).unwrap();
+        assert!(vm.world().unwrap().rules.waves);
+
+        run(&mut vm, 2, true);
+
+        let processor = take_processor(&mut vm, (0, 3));
+        assert_variables(&processor, map_iter! {
+            u16str!("floor"): LValue::from(Content::Block(content::blocks::FROM_NAME["sand-floor"])),
+            u16str!("ore"): LValue::from(Content::Block(content::blocks::FROM_NAME["ore-copper"])),
+            u16str!("block"): LValue::from(Content::Block(&content::blocks::AIR)),
+            u16str!("outside"): LValue::NULL,
+            u16str!("copper"): LValue::from(100),
+            u16str!("team"): LValue::from(Team::CRUX.0),
+            u16str!("cell"): LValue::from(2.5),
+            u16str!("wave"): LValue::from(5),
+            u16str!("mapw"): LValue::from(6),
+            u16str!("ipt"): LValue::from(100),
+            u16str!("x"): LValue::from(4),
+            u16str!("restored"): LValue::from(4),
+        });
+        assert_variables_buildings(&processor, map_iter! {
+            u16str!("core"): PackedPoint2 { x: 2, y: 0 },
+            u16str!("saved"): PackedPoint2 { x: 2, y: 0 },
+            u16str!("restored_core"): PackedPoint2 { x: 2, y: 0 },
+        });
+
+        map.tags
+            .insert("rules".into(), "{disableWorldProcessors:true}".into());
+        let vm = LogicVM::from_map(&map).unwrap();
+        assert_eq!(vm.running_processors(), 0);
+    }
+
     #[test]
     fn test_instruction_hook() {
         let hits = Rc::new(Cell::new(0));
 
         let mut builder = LogicVMBuilder::new();
-        builder.add_building(Building::from_processor_builder(
-            content::blocks::FROM_NAME[HYPER_PROCESSOR],
-            PackedPoint2 { x: 0, y: 0 },
-            ProcessorBuilder {
-                ipt: 25.,
-                privileged: false,
-                code: ProcessorBuilder::parse_code(
-                    "
+        builder.add_building(
+            Building::from_processor_builder(
+                content::blocks::FROM_NAME[HYPER_PROCESSOR],
+                PackedPoint2 { x: 0, y: 0 },
+                ProcessorBuilder {
+                    ipt: 25.,
+                    privileged: false,
+                    code: ProcessorBuilder::parse_code(
+                        "
                     op add a 1 0
                     set b 2
                     op add c 3 0
                     set d 4
                     op add e 5 0
                     ",
-                )
-                .unwrap(),
-                links: &[],
-                instruction_hook: {
-                    let hits = hits.clone();
-                    Some(Box::new(move |instruction, state, _| {
-                        if let Instruction::Set(instructions::Set { to, from, .. }) = instruction {
-                            hits.update(|v| v + 1);
-                            let value = from.get(state).num();
-                            to.set(state, (value * 10.).into());
-                            Some(if value == 4. {
-                                InstructionResult::Yield
+                    )
+                    .unwrap(),
+                    source: None,
+                    links: &[],
+                    instruction_hook: {
+                        let hits = hits.clone();
+                        Some(Box::new(move |instruction, state, _| {
+                            if let Instruction::Set(instructions::Set { to, from, .. }) =
+                                instruction
+                            {
+                                hits.update(|v| v + 1);
+                                let value = from.get(state).num();
+                                to.set(state, (value * 10.).into());
+                                Some(if value == 4. {
+                                    InstructionResult::Yield
+                                } else {
+                                    InstructionResult::Ok
+                                })
                             } else {
-                                InstructionResult::Ok
-                            })
-                        } else {
-                            None
-                        }
-                    }))
+                                None
+                            }
+                        }))
+                    },
+                    limits: None,
                 },
-            },
-            &builder,
-        ));
+                &builder,
+            )
+            .unwrap(),
+        );
         let mut vm = builder.build().unwrap();
 
         vm.do_tick(Duration::ZERO);
@@ -1393,39 +2069,1281 @@ mod tests {
         });
     }
 
-    thread_local! {
-        static SENSOR_TESTS: Vec<(U16String, &'static str, &'static str, LValue)> = map_iter_from![
-            ("null", "@dead"): true,
-
-            ("@hyper-processor", "@name"): u16str!("hyper-processor"),
-            ("@hyper-processor", "@id"): 141,
-            ("@hyper-processor", "@size"): 3,
-
-            ("@titanium", "@name"): u16str!("titanium"),
-            ("@titanium", "@id"): 6,
-
-            ("@cryofluid", "@name"): u16str!("cryofluid"),
-            ("@cryofluid", "@id"): 3,
-
-            ("@flare", "@name"): u16str!("flare"),
-            ("@flare", "@id"): 15,
-
-            (r#""123456789""#, "@size"): 9,
-
-            ("@sharded", "@id"): 1,
-
-            ("1", "@dead"): LValue::NULL,
-
-            ("@this", "@enabled"): true,
-            ("@this", "@config"): LValue::NULL,
-            ("@this", "@dead"): false,
-            ("@this", "@x"): 0,
-            ("@this", "@y"): 0,
-            ("@this", "@size"): 1,
-            ("@this", "@type"): Content::Block(content::blocks::FROM_NAME[WORLD_PROCESSOR]),
+    #[test]
+    fn test_fetch() {
+        let mut builder = LogicVMBuilder::new();
+        builder.add_buildings(
+            [
+                Building::from_processor_config(
+                    WORLD_PROCESSOR,
+                    PackedPoint2 { x: 0, y: 0 },
+                    &ProcessorConfig::from_code(
+                        "
+                        fetch buildCount cells @sharded 0 @memory-cell
+                        fetch build cell0 @sharded 0 @memory-cell
+                        fetch build cell1 @sharded 1 @memory-cell
+                        fetch build cell2 @sharded 2 @memory-cell
+                        fetch build cell3 @sharded -1 @memory-cell
+                        fetch build nocell @sharded 0 null
+
+                        fetch buildCount total @sharded 0 null
+                        fetch buildCount total_id 1 0 null
+                        fetch buildCount total_wrapped 257 0 null
+                        set derelict -1
+                        fetch buildCount derelict 256 0 null
+                        fetch buildCount switches @sharded 0 @switch
+
+                        fetch coreCount cores @sharded 0 null
+                        fetch core core0 @sharded 0 null
+                        fetch core core1 @sharded 1 null
+
+                        fetch buildCount crux @crux 0 null
+                        fetch build crux0 @crux 0 @memory-cell
+                        fetch coreCount crux_cores @crux 0 null
+
+                        fetch unitCount units @sharded 0 null
+                        fetch unit unit0 @sharded 0 null
+                        fetch playerCount players @sharded 0 null
+                        fetch player player0 @sharded 0 null
+
+                        set invalid_team 1
+                        fetch buildCount invalid_team @memory-cell 0 null
 
-            ("processor1", "@enabled"): false,
-            ("processor1", "@config"): LValue::NULL,
+                        stop
+                        ",
+                    ),
+                    &builder,
+                ),
+                Building::from_config(
+                    MEMORY_CELL,
+                    PackedPoint2 { x: 5, y: 1 },
+                    &Object::Null,
+                    &builder,
+                ),
+                Building::from_config(
+                    MEMORY_CELL,
+                    PackedPoint2 { x: 1, y: 1 },
+                    &Object::Null,
+                    &builder,
+                ),
+                Building::from_config(
+                    "core-shard",
+                    PackedPoint2 { x: 10, y: 10 },
+                    &Object::Null,
+                    &builder,
+                ),
+                Building::from_config(
+                    MEMORY_CELL,
+                    PackedPoint2 { x: 3, y: 0 },
+                    &Object::Null,
+                    &builder,
+                ),
+            ]
+            .map(|v| v.unwrap()),
+        );
+        let mut vm = builder.build().unwrap();
+
+        run(&mut vm, 4, true);
+
+        let processor = take_processor(&mut vm, (0, 0));
+        assert_variables(&processor, map_iter! {
+            u16str!("cells"): LValue::from(3),
+            u16str!("cell3"): LValue::NULL,
+            u16str!("nocell"): LValue::NULL,
+
+            u16str!("total"): LValue::from(5),
+            u16str!("total_id"): LValue::from(5),
+            u16str!("total_wrapped"): LValue::from(5),
+            u16str!("derelict"): LValue::from(0),
+            u16str!("switches"): LValue::from(0),
+
+            u16str!("cores"): LValue::from(1),
+            u16str!("core1"): LValue::NULL,
+
+            u16str!("crux"): LValue::from(0),
+            u16str!("crux0"): LValue::NULL,
+            u16str!("crux_cores"): LValue::from(0),
+
+            u16str!("units"): LValue::from(0),
+            u16str!("unit0"): LValue::NULL,
+            u16str!("players"): LValue::from(0),
+            u16str!("player0"): LValue::NULL,
+
+            u16str!("invalid_team"): LValue::from(1),
+        });
+        assert_variables_buildings(&processor, map_iter! {
+            u16str!("cell0"): PackedPoint2 { x: 3, y: 0 },
+            u16str!("cell1"): PackedPoint2 { x: 1, y: 1 },
+            u16str!("cell2"): PackedPoint2 { x: 5, y: 1 },
+            u16str!("core0"): PackedPoint2 { x: 10, y: 10 },
+        });
+    }
+
+    #[test]
+    fn test_fetch_center_order() {
+        let mut builder = LogicVMBuilder::new();
+        builder.add_buildings(
+            [
+                Building::from_config(
+                    "core-shard",
+                    PackedPoint2 { x: 0, y: 0 },
+                    &Object::Null,
+                    &builder,
+                ),
+                Building::from_config(
+                    MEMORY_CELL,
+                    PackedPoint2 { x: 5, y: 2 },
+                    &Object::Null,
+                    &builder,
+                ),
+                Building::from_config(
+                    MEMORY_CELL,
+                    PackedPoint2 { x: 5, y: 0 },
+                    &Object::Null,
+                    &builder,
+                ),
+            ]
+            .map(|v| v.unwrap()),
+        );
+        let vm = builder.build().unwrap();
+
+        let index = vm.team_index(Team::SHARDED).unwrap();
+        assert_eq!(
+            index
+                .buildings
+                .iter()
+                .map(|b| b.position)
+                .collect::<Vec<_>>(),
+            vec![
+                PackedPoint2 { x: 5, y: 0 },
+                PackedPoint2 { x: 0, y: 0 },
+                PackedPoint2 { x: 5, y: 2 },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_fetch_add_remove() {
+        let mut vm = single_processor_vm(
+            WORLD_PROCESSOR,
+            "
+            fetch buildCount cells @sharded 0 @memory-cell
+            stop
+            ",
+        );
+        let globals = LVar::create_global_constants();
+
+        let check = |vm: &mut LogicVM, want: usize| {
+            with_processor(vm, (0, 0), |p| {
+                p.state.counter = 0;
+                p.state.set_stopped(false);
+            });
+            run(vm, 1, true);
+            with_processor(vm, (0, 0), |p| {
+                assert_variables(p, map_iter! {
+                    u16str!("cells"): LValue::from(want),
+                });
+            });
+        };
+
+        check(&mut vm, 0);
+
+        vm.add_building(
+            Building::from_config(MEMORY_CELL, PackedPoint2 { x: 2, y: 0 }, &Object::Null, &vm)
+                .unwrap(),
+            &globals,
+        )
+        .unwrap();
+        check(&mut vm, 1);
+
+        vm.remove_building(PackedPoint2 { x: 2, y: 0 });
+        check(&mut vm, 0);
+    }
+
+    fn units_vm(code: &str) -> LogicVM {
+        let mut builder = LogicVMBuilder::new();
+        builder.add_buildings(
+            [
+                Building::from_processor_config(
+                    WORLD_PROCESSOR,
+                    PackedPoint2 { x: 0, y: 0 },
+                    &ProcessorConfig::from_code(code),
+                    &builder,
+                ),
+                Building::from_config(
+                    "duo",
+                    PackedPoint2 { x: 10, y: 10 },
+                    &Object::Null,
+                    &builder,
+                ),
+            ]
+            .map(|v| v.unwrap()),
+        );
+        builder.build().unwrap()
+    }
+
+    #[test]
+    fn test_radar() {
+        let mut vm = units_vm(
+            "
+            fetch build turret @sharded 0 @duo
+            radar enemy any any distance turret 1 closest
+            radar enemy any any distance turret 0 farthest
+            radar enemy flying any distance turret 1 flying
+            radar enemy ground any distance turret 0 ground
+            radar ally any any distance turret 1 ally
+            radar enemy any any health turret 1 healthiest
+            radar enemy any any health turret 0 weakest
+            radar enemy boss any distance turret 1 boss
+            radar enemy player any distance turret 1 player
+            radar enemy any any distance null 1 no_radar
+            stop
+            ",
+        );
+
+        let dagger = content::units::FROM_NAME["dagger"];
+        let mace = content::units::FROM_NAME["mace"];
+
+        let near = vm.add_unit(
+            dagger,
+            UnitData {
+                health: 50.,
+                ..UnitData::new(Team::CRUX, 12., 10.5)
+            },
+        );
+        let far = vm.add_unit(
+            mace,
+            UnitData {
+                health: 200.,
+                flying: true,
+                ..UnitData::new(Team::CRUX, 15., 10.5)
+            },
+        );
+        let ally = vm.add_unit(dagger, UnitData::new(Team::SHARDED, 11., 10.5));
+        vm.add_unit(dagger, UnitData::new(Team::CRUX, 1000., 1000.));
+        let dead = vm.add_unit(dagger, UnitData::new(Team::CRUX, 10.5, 10.5));
+        vm.remove_unit(dead.id);
+
+        run(&mut vm, 2, true);
+
+        let processor = take_processor(&mut vm, (0, 0));
+        assert_variables(&processor, map_iter! {
+            u16str!("closest"): LValue::from(near.clone()),
+            u16str!("farthest"): LValue::from(far.clone()),
+            u16str!("flying"): LValue::from(far.clone()),
+            u16str!("ground"): LValue::from(near.clone()),
+            u16str!("ally"): LValue::from(ally),
+            u16str!("healthiest"): LValue::from(far),
+            u16str!("weakest"): LValue::from(near),
+            u16str!("boss"): LValue::NULL,
+            u16str!("player"): LValue::NULL,
+            u16str!("no_radar"): LValue::NULL,
+        });
+    }
+
+    #[test]
+    fn test_radar_linked() {
+        let mut builder = LogicVMBuilder::new();
+        builder.add_buildings(
+            [
+                Building::from_processor_config(
+                    MICRO_PROCESSOR,
+                    PackedPoint2 { x: 9, y: 10 },
+                    &ProcessorConfig {
+                        code: "radar enemy any any distance duo1 1 result; stop".into(),
+                        links: vec![ProcessorLinkConfig::unnamed(1, 0)],
+                    },
+                    &builder,
+                ),
+                Building::from_config(
+                    "duo",
+                    PackedPoint2 { x: 10, y: 10 },
+                    &Object::Null,
+                    &builder,
+                ),
+            ]
+            .map(|v| v.unwrap()),
+        );
+        let mut vm = builder.build().unwrap();
+
+        let unit = vm.add_unit(
+            content::units::FROM_NAME["dagger"],
+            UnitData::new(Team::CRUX, 12., 10.5),
+        );
+
+        run(&mut vm, 1, true);
+
+        let processor = take_processor(&mut vm, (9, 10));
+        assert_variables(&processor, map_iter! {
+            u16str!("result"): LValue::from(unit),
+        });
+    }
+
+    #[test]
+    fn test_radar_range() {
+        let mut vm = units_vm(
+            "
+            fetch build turret @sharded 0 @duo
+            radar enemy any any distance turret 0 farthest
+            stop
+            ",
+        );
+
+        // the duo's center is at (10, 10), so only the first unit is in range
+        let range = content::blocks::FROM_NAME["duo"].range;
+        let dagger = content::units::FROM_NAME["dagger"];
+        let inside = vm.add_unit(dagger, UnitData::new(Team::CRUX, 10. + range - 0.25, 10.));
+        vm.add_unit(dagger, UnitData::new(Team::CRUX, 10. + range + 0.25, 10.));
+
+        run(&mut vm, 2, true);
+
+        let processor = take_processor(&mut vm, (0, 0));
+        assert_variables(&processor, map_iter! {
+            u16str!("farthest"): LValue::from(inside),
+        });
+    }
+
+    #[test]
+    fn test_radar_unlinked() {
+        let mut builder = LogicVMBuilder::new();
+        builder.add_buildings(
+            [
+                Building::from_processor_config(
+                    MICRO_PROCESSOR,
+                    PackedPoint2 { x: 9, y: 10 },
+                    &ProcessorConfig::from_code(
+                        "radar enemy any any distance turret 1 result; stop",
+                    ),
+                    &builder,
+                ),
+                Building::from_config(
+                    "duo",
+                    PackedPoint2 { x: 10, y: 10 },
+                    &Object::Null,
+                    &builder,
+                ),
+            ]
+            .map(|v| v.unwrap()),
+        );
+        let mut vm = builder.build().unwrap();
+
+        vm.add_unit(
+            content::units::FROM_NAME["dagger"],
+            UnitData::new(Team::CRUX, 12., 10.5),
+        );
+
+        // the turret is on the same team, but isn't linked to the processor
+        let turret = vm.building(PackedPoint2 { x: 10, y: 10 }).unwrap().clone();
+        with_processor(&mut vm, (9, 10), |p| {
+            p.state.variables[u16str!("turret")] = turret.into();
+        });
+
+        run(&mut vm, 1, true);
+
+        let processor = take_processor(&mut vm, (9, 10));
+        assert_variables(&processor, map_iter! {
+            u16str!("result"): LValue::NULL,
+        });
+    }
+
+    #[test]
+    fn test_fetch_units() {
+        let mut vm = units_vm(
+            "
+            fetch unitCount units @crux 0 null
+            fetch unitCount daggers @crux 0 @dagger
+            fetch unit unit0 @crux 0 null
+            fetch unit unit1 @crux 1 null
+            fetch unit mace0 @crux 0 @mace
+            fetch unit unit3 @crux 3 null
+            fetch unitCount sharded @sharded 0 null
+            fetch playerCount players @crux 0 null
+            fetch player player0 @crux 0 null
+            stop
+            ",
+        );
+
+        let dagger = content::units::FROM_NAME["dagger"];
+        let mace = content::units::FROM_NAME["mace"];
+
+        let dagger0 = vm.add_unit(dagger, UnitData::new(Team::CRUX, 0., 0.));
+        let dead = vm.add_unit(dagger, UnitData::new(Team::CRUX, 0., 0.));
+        let mace0 = vm.add_unit(
+            mace,
+            UnitData {
+                player: true,
+                ..UnitData::new(Team::CRUX, 0., 0.)
+            },
+        );
+        vm.add_unit(dagger, UnitData::new(Team::CRUX, 0., 0.));
+        vm.remove_unit(dead.id);
+
+        run(&mut vm, 2, true);
+
+        let processor = take_processor(&mut vm, (0, 0));
+        assert_variables(&processor, map_iter! {
+            u16str!("units"): LValue::from(3),
+            u16str!("daggers"): LValue::from(2),
+            u16str!("unit0"): LValue::from(dagger0),
+            u16str!("unit1"): LValue::from(mace0.clone()),
+            u16str!("mace0"): LValue::from(mace0.clone()),
+            u16str!("unit3"): LValue::NULL,
+            u16str!("sharded"): LValue::from(0),
+            u16str!("players"): LValue::from(1),
+            u16str!("player0"): LValue::from(mace0),
+        });
+    }
+
+    #[test]
+    fn test_sensor_unit() {
+        let mut vm = units_vm(
+            "
+            fetch unit unit @crux 0 null
+            sensor x unit @x
+            sensor y unit @y
+            sensor health unit @health
+            sensor maxHealth unit @maxHealth
+            sensor team unit @team
+            sensor type unit @type
+            sensor name unit @name
+            sensor dead unit @dead
+            sensor controlled unit @controlled
+            sensor ammo unit @ammo
+            print unit
+            stop
+            ",
+        );
+
+        let mace = content::units::FROM_NAME["mace"];
+        vm.add_unit(
+            mace,
+            UnitData {
+                health: 300.,
+                max_health: 550.,
+                player: true,
+                ..UnitData::new(Team::CRUX, 4.5, 6.)
+            },
+        );
+
+        run(&mut vm, 2, true);
+
+        let processor = take_processor(&mut vm, (0, 0));
+        assert_variables(&processor, map_iter! {
+            u16str!("x"): LValue::from(4.5),
+            u16str!("y"): LValue::from(6),
+            u16str!("health"): LValue::from(300),
+            u16str!("maxHealth"): LValue::from(550),
+            u16str!("team"): LValue::from(2),
+            u16str!("type"): LValue::from(Content::Unit(mace)),
+            u16str!("name"): LValue::from(u16str!("mace")),
+            u16str!("dead"): LValue::from(false),
+            u16str!("controlled"): LValue::from(2),
+            u16str!("ammo"): LValue::NULL,
+        });
+        assert_eq!(processor.state.printbuffer, u16str!("mace"));
+    }
+
+    #[test]
+    fn test_turret() {
+        let mut vm = units_vm(
+            "
+            setrate 1000
+            fetch build turret @sharded 0 @duo
+            fetch unit unit @sharded 0 @dagger
+            sensor initial_rotation turret @rotation
+            sensor initial_shooting turret @shooting
+
+            control shoot turret 20.5 4 1
+            sensor x turret @shootX
+            sensor y turret @shootY
+            sensor shooting turret @shooting
+
+            control shootp turret unit 0
+            sensor unit_x turret @shootX
+            sensor unit_y turret @shootY
+            sensor unit_shooting turret @shooting
+
+            control shootp turret @copper 1
+            sensor copper_shooting turret @shooting
+            stop
+            ",
+        );
+
+        let dagger = content::units::FROM_NAME["dagger"];
+        vm.add_unit(dagger, UnitData::new(Team::SHARDED, 10., 30.));
+
+        run(&mut vm, 2, true);
+
+        let turret = vm.building(PackedPoint2 { x: 10, y: 10 }).unwrap().clone();
+        let rotation = |turret: &Building| match &*turret.data.borrow() {
+            BuildingData::Turret(turret) => turret.rotation,
+            _ => unreachable!(),
+        };
+
+        // the turret started turning towards the first target, then turns back up towards the unit
+        assert_eq!(rotation(&turret), 85.);
+        vm.do_tick(Duration::ZERO);
+        assert_eq!(rotation(&turret), 90.);
+
+        if let BuildingData::Turret(data) = &mut *turret.data.borrow_mut() {
+            data.shoot(0.5, 10., true);
+        }
+
+        vm.do_tick(Duration::ZERO);
+        assert_eq!(rotation(&turret), 95.);
+
+        for _ in 0..20 {
+            vm.do_tick(Duration::ZERO);
+        }
+        assert_eq!(rotation(&turret), 180.);
+
+        let processor = take_processor(&mut vm, (0, 0));
+        assert_variables(&processor, map_iter! {
+            u16str!("initial_rotation"): LValue::from(90),
+            u16str!("initial_shooting"): LValue::from(false),
+            u16str!("x"): LValue::from(20.5),
+            u16str!("y"): LValue::from(4),
+            u16str!("shooting"): LValue::from(true),
+            u16str!("unit_x"): LValue::from(10),
+            u16str!("unit_y"): LValue::from(30),
+            u16str!("unit_shooting"): LValue::from(false),
+            u16str!("copper_shooting"): LValue::from(false),
+        });
+    }
+
+    #[test]
+    fn test_turret_shootp_building() {
+        let mut builder = LogicVMBuilder::new();
+        builder.add_buildings(
+            [
+                Building::from_processor_config(
+                    WORLD_PROCESSOR,
+                    PackedPoint2 { x: 0, y: 0 },
+                    &ProcessorConfig::from_code(
+                        "
+                        fetch build turret @sharded 0 @duo
+                        fetch build bank @sharded 0 @memory-bank
+
+                        control shootp turret @this 1
+                        sensor this_x turret @shootX
+                        sensor this_y turret @shootY
+
+                        control shootp turret bank 1
+                        sensor bank_x turret @shootX
+                        sensor bank_y turret @shootY
+                        stop
+                        ",
+                    ),
+                    &builder,
+                ),
+                Building::from_config(
+                    "duo",
+                    PackedPoint2 { x: 10, y: 10 },
+                    &Object::Null,
+                    &builder,
+                ),
+                Building::from_config(
+                    MEMORY_BANK,
+                    PackedPoint2 { x: 4, y: 4 },
+                    &Object::Null,
+                    &builder,
+                ),
+            ]
+            .map(|v| v.unwrap()),
+        );
+        let mut vm = builder.build().unwrap();
+
+        run(&mut vm, 2, true);
+
+        // buildings are aimed at their center, which is between tiles for even sizes
+        let processor = take_processor(&mut vm, (0, 0));
+        assert_variables(&processor, map_iter! {
+            u16str!("this_x"): LValue::from(0),
+            u16str!("this_y"): LValue::from(0),
+            u16str!("bank_x"): LValue::from(4.5),
+            u16str!("bank_y"): LValue::from(4.5),
+        });
+    }
+
+    #[test]
+    fn test_turret_control_timeout() {
+        let mut vm = units_vm(
+            "
+            fetch build turret @sharded 0 @duo
+            control shoot turret 20.5 4 1
+            sensor shooting turret @shooting
+            wait 1
+            sensor still_shooting turret @shooting
+            wait 1.5
+            sensor released turret @shooting
+            stop
+            ",
+        );
+
+        let mut time = Duration::ZERO;
+        for _ in 0..200 {
+            vm.do_tick(time);
+            time += Duration::from_secs_f64(1. / 60.);
+        }
+
+        let processor = take_processor(&mut vm, (0, 0));
+        assert_variables(&processor, map_iter! {
+            u16str!("shooting"): LValue::from(true),
+            u16str!("still_shooting"): LValue::from(true),
+            u16str!("released"): LValue::from(false),
+        });
+    }
+
+    #[test]
+    fn test_setprop() {
+        let mut builder = LogicVMBuilder::new();
+        builder.add_buildings(
+            [
+                Building::from_processor_config(
+                    WORLD_PROCESSOR,
+                    PackedPoint2 { x: 0, y: 0 },
+                    &ProcessorConfig::from_code(
+                        "
+                        setrate 1000
+                        fetch build cell @sharded 0 @memory-cell
+                        fetch build container @sharded 0 @container
+
+                        setprop @health cell 10
+                        sensor health1 cell @health
+                        setprop @health cell 1000
+                        sensor health2 cell @health
+                        setprop @health cell -5
+                        sensor health3 cell @health
+                        sensor dead cell @dead
+                        setprop @health cell @copper
+                        sensor health4 cell @health
+
+                        setprop @copper container 5
+                        sensor copper1 container @copper
+                        setprop @copper container 1000
+                        sensor copper2 container @copper
+                        setprop @copper container -1
+                        sensor copper3 container @copper
+                        setprop @copper container 0
+                        sensor copper4 container @copper
+                        sensor lead container @lead
+
+                        setprop @x cell 10
+                        sensor x cell @x
+
+                        fetch buildCount sharded1 @sharded 0 @memory-cell
+                        setprop @team cell @crux
+                        sensor team1 cell @team
+                        fetch buildCount sharded2 @sharded 0 @memory-cell
+                        fetch buildCount crux @crux 0 @memory-cell
+                        setprop @team cell 3
+                        sensor team2 cell @team
+                        setprop @team cell null
+                        sensor team3 cell @team
+
+                        stop
+                        ",
+                    ),
+                    &builder,
+                ),
+                Building::from_config(
+                    MEMORY_CELL,
+                    PackedPoint2 { x: 1, y: 0 },
+                    &Object::Null,
+                    &builder,
+                ),
+                Building::from_config(
+                    "container",
+                    PackedPoint2 { x: 2, y: 0 },
+                    &Object::Null,
+                    &builder,
+                ),
+            ]
+            .map(|v| v.unwrap()),
+        );
+        let mut vm = builder.build().unwrap();
+
+        run(&mut vm, 2, true);
+
+        let processor = take_processor(&mut vm, (0, 0));
+        assert_variables(&processor, map_iter! {
+            u16str!("health1"): LValue::from(10),
+            u16str!("health2"): LValue::from(40),
+            u16str!("health3"): LValue::from(0),
+            u16str!("dead"): LValue::from(true),
+            u16str!("health4"): LValue::from(0),
+
+            u16str!("copper1"): LValue::from(5),
+            u16str!("copper2"): LValue::from(300),
+            u16str!("copper3"): LValue::from(300),
+            u16str!("copper4"): LValue::from(0),
+            u16str!("lead"): LValue::from(0),
+
+            u16str!("x"): LValue::from(1),
+
+            u16str!("sharded1"): LValue::from(1),
+            u16str!("team1"): LValue::from(2),
+            u16str!("sharded2"): LValue::from(0),
+            u16str!("crux"): LValue::from(1),
+            u16str!("team2"): LValue::from(3),
+            u16str!("team3"): LValue::from(3),
+        });
+
+        let cell = vm.building(PackedPoint2 { x: 1, y: 0 }).unwrap();
+        assert_eq!(cell.team(), Team::MALIS);
+        assert_eq!(cell.props.borrow().health, 0.);
+    }
+
+    #[test]
+    fn test_setprop_unpriv() {
+        let mut builder = LogicVMBuilder::new();
+        builder.add_buildings(
+            [
+                Building::from_processor_config(
+                    MICRO_PROCESSOR,
+                    PackedPoint2 { x: 0, y: 0 },
+                    &ProcessorConfig {
+                        code: "setprop @health cell1 10; sensor health cell1 @health; stop".into(),
+                        links: vec![ProcessorLinkConfig::unnamed(1, 0)],
+                    },
+                    &builder,
+                ),
+                Building::from_config(
+                    MEMORY_CELL,
+                    PackedPoint2 { x: 1, y: 0 },
+                    &Object::Null,
+                    &builder,
+                ),
+            ]
+            .map(|v| v.unwrap()),
+        );
+        let mut vm = builder.build().unwrap();
+
+        run(&mut vm, 2, true);
+
+        let processor = take_processor(&mut vm, (0, 0));
+        assert_variables(&processor, map_iter! {
+            u16str!("health"): LValue::from(40),
+        });
+    }
+
+    #[test]
+    fn test_setprop_unit() {
+        let mut vm = units_vm(
+            "
+            fetch unit unit @crux 0 null
+            setprop @x unit 5
+            setprop @y unit 6
+            setprop @rotation unit 90
+            setprop @health unit 1000
+            setprop @shield unit -1
+            setprop @team unit @sharded
+            stop
+            ",
+        );
+
+        let unit = vm.add_unit(
+            content::units::FROM_NAME["dagger"],
+            UnitData::new(Team::CRUX, 0., 0.),
+        );
+
+        run(&mut vm, 2, true);
+
+        assert_eq!(
+            *unit.data.borrow(),
+            UnitData {
+                rotation: 90.,
+                ..UnitData::new(Team::SHARDED, 5., 6.)
+            }
+        );
+    }
+
+    #[test]
+    fn test_setprop_custom() {
+        type Calls = Rc<RefCell<Vec<(LValue, LValue)>>>;
+
+        struct Recorder(Calls);
+
+        impl CustomBuildingData for Recorder {
+            fn setprop(
+                &mut self,
+                building: &Building,
+                _: &LogicVM,
+                prop: Cow<'_, LValue>,
+                value: Cow<'_, LValue>,
+            ) -> InstructionResult {
+                // built-in properties should already be updated
+                assert_eq!(building.props.borrow().health, 5.);
+                self.0
+                    .borrow_mut()
+                    .push((prop.into_owned(), value.into_owned()));
+                InstructionResult::Ok
+            }
+        }
+
+        let calls = Calls::default();
+
+        let mut builder = LogicVMBuilder::new();
+        builder.add_buildings([
+            Building::from_processor_config(
+                WORLD_PROCESSOR,
+                PackedPoint2 { x: 0, y: 0 },
+                &ProcessorConfig {
+                    code: "setprop @health sorter1 5; setprop @config sorter1 @copper; stop".into(),
+                    links: vec![ProcessorLinkConfig::unnamed(1, 0)],
+                },
+                &builder,
+            )
+            .unwrap(),
+            Building::from_name(
+                "sorter",
+                PackedPoint2 { x: 1, y: 0 },
+                Recorder(calls.clone()).into(),
+            )
+            .unwrap(),
+        ]);
+        let mut vm = builder.build().unwrap();
+
+        run(&mut vm, 1, true);
+
+        assert_eq!(*calls.borrow(), vec![
+            (LValue::from(LAccess::Health), LValue::from(5)),
+            (
+                LValue::from(LAccess::Config),
+                LValue::from(Content::Item(content::items::FROM_NAME["copper"])),
+            ),
+        ]);
+    }
+
+    #[test]
+    fn test_inventory() {
+        let mut builder = LogicVMBuilder::new();
+        builder.add_buildings(
+            [
+                Building::from_processor_config(
+                    WORLD_PROCESSOR,
+                    PackedPoint2 { x: 0, y: 0 },
+                    &ProcessorConfig::from_code(
+                        "
+                        setrate 1000
+                        fetch build container @sharded 0 @container
+                        fetch build tank @sharded 0 @liquid-tank
+                        fetch build battery @sharded 0 @battery
+                        fetch build cell @sharded 0 @memory-cell
+
+                        sensor empty_total container @totalItems
+                        sensor empty_first container @firstItem
+                        setprop @lead container 20
+                        setprop @copper container 10
+                        sensor total container @totalItems
+                        sensor first container @firstItem
+                        sensor copper container @copper
+                        sensor water container @water
+
+                        setprop @water tank 100
+                        setprop @slag tank 50
+                        sensor tank_water tank @water
+                        sensor tank_slag tank @slag
+                        sensor tank_total tank @totalLiquids
+                        sensor tank_copper tank @copper
+
+                        setprop @totalPower battery 300
+                        sensor battery_power battery @totalPower
+                        sensor battery_capacity battery @powerCapacity
+                        setprop @totalPower container 300
+                        sensor container_power container @totalPower
+
+                        setprop @copper cell 5
+                        sensor cell_copper cell @copper
+                        sensor cell_total cell @totalItems
+
+                        stop
+                        ",
+                    ),
+                    &builder,
+                ),
+                Building::from_config(
+                    "container",
+                    PackedPoint2 { x: 1, y: 0 },
+                    &Object::Null,
+                    &builder,
+                ),
+                Building::from_config(
+                    "liquid-tank",
+                    PackedPoint2 { x: 3, y: 0 },
+                    &Object::Null,
+                    &builder,
+                ),
+                Building::from_config(
+                    "battery",
+                    PackedPoint2 { x: 6, y: 0 },
+                    &Object::Null,
+                    &builder,
+                ),
+                Building::from_config(
+                    MEMORY_CELL,
+                    PackedPoint2 { x: 7, y: 0 },
+                    &Object::Null,
+                    &builder,
+                ),
+            ]
+            .map(|v| v.unwrap()),
+        );
+        let mut vm = builder.build().unwrap();
+
+        // the host decides which buildings have power buffers
+        vm.building(PackedPoint2 { x: 6, y: 0 })
+            .unwrap()
+            .props
+            .borrow_mut()
+            .power = Some(PowerModule::battery(1000.));
+
+        // blocks without hasItems/hasLiquids have no inventory to sense
+        for (position, items, liquids) in [
+            ((1, 0), true, false),
+            ((3, 0), false, true),
+            ((7, 0), false, false),
+        ] {
+            let props = vm.building(position.into()).unwrap().props.borrow();
+            assert_eq!(props.items.is_some(), items);
+            assert_eq!(props.liquids.is_some(), liquids);
+        }
+
+        run(&mut vm, 2, true);
+
+        let copper = content::items::FROM_NAME["copper"];
+
+        let processor = take_processor(&mut vm, (0, 0));
+        assert_variables(&processor, map_iter! {
+            u16str!("empty_total"): LValue::from(0),
+            u16str!("empty_first"): LValue::NULL,
+            u16str!("total"): LValue::from(30),
+            u16str!("first"): LValue::from(Content::Item(copper)),
+            u16str!("copper"): LValue::from(10),
+            u16str!("water"): LValue::NULL,
+
+            u16str!("tank_water"): LValue::from(100),
+            u16str!("tank_slag"): LValue::from(0),
+            u16str!("tank_total"): LValue::from(100),
+            u16str!("tank_copper"): LValue::NULL,
+
+            u16str!("battery_power"): LValue::from(300),
+            u16str!("battery_capacity"): LValue::from(1000),
+            u16str!("container_power"): LValue::from(0),
+
+            u16str!("cell_copper"): LValue::NULL,
+            u16str!("cell_total"): LValue::from(0),
+        });
+
+        let container = vm.building(PackedPoint2 { x: 1, y: 0 }).unwrap();
+        assert_eq!(
+            container.props.borrow().items.as_ref().unwrap().get(copper),
+            10
+        );
+    }
+
+    #[test]
+    fn test_power() {
+        let mut builder = LogicVMBuilder::new();
+        builder.add_buildings(
+            [
+                Building::from_processor_config(
+                    WORLD_PROCESSOR,
+                    PackedPoint2 { x: 0, y: 0 },
+                    &ProcessorConfig::from_code(
+                        "
+                        setrate 1000
+                        fetch build node @sharded 0 @power-node
+                        fetch build battery @sharded 0 @battery
+                        fetch build consumer @sharded 0 @sorter
+                        fetch build lone @sharded 0 @inverted-sorter
+                        fetch build solar @sharded 0 @solar-panel
+                        fetch build cell @sharded 0 @memory-cell
+
+                        sensor in node @powerNetIn
+                        sensor out battery @powerNetOut
+                        sensor stored consumer @powerNetStored
+                        sensor capacity node @powerNetCapacity
+                        sensor satisfaction consumer @totalPower
+                        sensor lone_satisfaction lone @totalPower
+                        sensor solar_out solar @powerNetOut
+                        sensor cell_in cell @powerNetIn
+                        stop
+                        ",
+                    ),
+                    &builder,
+                ),
+                Building::from_config(
+                    "power-node",
+                    PackedPoint2 { x: 0, y: 5 },
+                    &Object::Point2Array {
+                        values: vec![PackedPoint2 { x: 3, y: 0 }, PackedPoint2 { x: 0, y: 3 }],
+                    },
+                    &builder,
+                ),
+                Building::from_config(
+                    "battery",
+                    PackedPoint2 { x: 3, y: 5 },
+                    &Object::Null,
+                    &builder,
+                ),
+                Building::from_config(
+                    "combustion-generator",
+                    PackedPoint2 { x: 0, y: 8 },
+                    &Object::Null,
+                    &builder,
+                ),
+                // adjacent to the battery
+                Building::from_config(
+                    "sorter",
+                    PackedPoint2 { x: 4, y: 5 },
+                    &Object::Null,
+                    &builder,
+                ),
+                // adjacent consumers don't share power
+                Building::from_config(
+                    "inverted-sorter",
+                    PackedPoint2 { x: 5, y: 5 },
+                    &Object::Null,
+                    &builder,
+                ),
+                Building::from_config(
+                    "solar-panel",
+                    PackedPoint2 { x: 10, y: 10 },
+                    &Object::Null,
+                    &builder,
+                ),
+                Building::from_config(
+                    MEMORY_CELL,
+                    PackedPoint2 { x: 12, y: 10 },
+                    &Object::Null,
+                    &builder,
+                ),
+            ]
+            .map(|v| v.unwrap()),
+        );
+        let mut vm = builder.build().unwrap();
+
+        for (x, consumption) in [(4, 3.), (5, 1.)] {
+            vm.building(PackedPoint2 { x, y: 5 })
+                .unwrap()
+                .props
+                .borrow_mut()
+                .power = Some(PowerModule::consumer(consumption));
+        }
+        vm.invalidate_power_graphs();
+
+        run(&mut vm, 2, true);
+
+        assert_eq!(vm.power_graphs().graphs.len(), 3);
+
+        // the battery covers the deficit once it has some power stored
+        let battery = vm.building(PackedPoint2 { x: 3, y: 5 }).unwrap().clone();
+        battery
+            .props
+            .borrow_mut()
+            .power
+            .as_mut()
+            .unwrap()
+            .set_stored(500.);
+
+        vm.do_tick(Duration::ZERO);
+
+        {
+            let graphs = vm.power_graphs();
+            let graph = graphs.graph_of(&battery).unwrap();
+            assert_eq!(graph.last_power_stored, 500.);
+            assert_eq!(graph.power_in(), 180.);
+        }
+        assert_eq!(
+            battery.props.borrow().power.as_ref().unwrap().stored(),
+            498.
+        );
+
+        let consumer = vm.building(PackedPoint2 { x: 4, y: 5 }).unwrap().clone();
+        assert_eq!(consumer.props.borrow().power.as_ref().unwrap().status, 1.);
+
+        let processor = take_processor(&mut vm, (0, 0));
+        assert_variables(&processor, map_iter! {
+            u16str!("in"): LValue::from(60),
+            u16str!("out"): LValue::from(180),
+            u16str!("stored"): LValue::from(0),
+            u16str!("capacity"): LValue::from(1000),
+            u16str!("satisfaction"): LValue::from(1. / 3.),
+            u16str!("lone_satisfaction"): LValue::from(0),
+            u16str!("solar_out"): LValue::from(0),
+            u16str!("cell_in"): LValue::from(0),
+        });
+    }
+
+    #[test]
+    fn test_actuators() {
+        let mut builder = LogicVMBuilder::new();
+        builder.add_buildings(
+            [
+                Building::from_processor_config(
+                    WORLD_PROCESSOR,
+                    PackedPoint2 { x: 0, y: 0 },
+                    &ProcessorConfig::from_code(
+                        "
+                        setrate 1000
+                        fetch build sorter @sharded 0 @sorter
+                        fetch build unloader @sharded 0 @unloader
+                        fetch build door @sharded 0 @door
+                        fetch build light @sharded 0 @illuminator
+                        fetch build conveyor @sharded 0 @conveyor
+                        fetch build drill @sharded 0 @mechanical-drill
+
+                        sensor sorter_initial sorter @config
+                        control config sorter @copper
+                        sensor sorter_copper sorter @config
+                        control config sorter @water
+                        sensor sorter_water sorter @config
+
+                        sensor unloader_initial unloader @config
+                        control config unloader null
+                        sensor unloader_null unloader @config
+
+                        sensor door_initial door @enabled
+                        control enabled door true
+                        sensor door_open door @enabled
+
+                        sensor light_initial light @color
+                        packcolor color 1 0 0.5 1
+                        control color light color
+                        sensor light_color light @color
+                        op equal light_ok light_color color
+                        sensor light_enabled light @enabled
+
+                        sensor conveyor_initial conveyor @enabled
+                        control enabled conveyor false
+                        sensor conveyor_enabled conveyor @enabled
+                        control enabled drill 0
+                        sensor drill_enabled drill @enabled
+                        stop
+                        ",
+                    ),
+                    &builder,
+                ),
+                Building::from_config(SORTER, PackedPoint2 { x: 2, y: 0 }, &Object::Null, &builder),
+                Building::from_config(
+                    UNLOADER,
+                    PackedPoint2 { x: 3, y: 0 },
+                    &Object::Content(ContentID {
+                        type_: ContentType::Item,
+                        id: content::items::FROM_NAME["lead"].id as i16,
+                    }),
+                    &builder,
+                ),
+                Building::from_config(DOOR, PackedPoint2 { x: 4, y: 0 }, &false.into(), &builder),
+                Building::from_config(
+                    ILLUMINATOR,
+                    PackedPoint2 { x: 5, y: 0 },
+                    &Object::Int(0x00ff00ff),
+                    &builder,
+                ),
+                Building::from_config(
+                    "conveyor",
+                    PackedPoint2 { x: 6, y: 0 },
+                    &Object::Null,
+                    &builder,
+                ),
+                Building::from_config(
+                    "mechanical-drill",
+                    PackedPoint2 { x: 7, y: 0 },
+                    &Object::Null,
+                    &builder,
+                ),
+            ]
+            .map(|v| v.unwrap()),
+        );
+        let mut vm = builder.build().unwrap();
+
+        run(&mut vm, 2, true);
+
+        // conveyors and drills are re-enabled once no processor has controlled them for 6 seconds
+        let enabled = |vm: &LogicVM| {
+            [(6, 0), (7, 0)].map(|position| {
+                match *vm.building(position.into()).unwrap().data.borrow() {
+                    BuildingData::Conveyor { enabled, .. } | BuildingData::Drill { enabled, .. } => {
+                        enabled
+                    }
+                    _ => unreachable!(),
+                }
+            })
+        };
+
+        for _ in 0..300 {
+            vm.do_tick(Duration::ZERO);
+        }
+        assert_eq!(enabled(&vm), [false, false]);
+
+        for _ in 0..60 {
+            vm.do_tick(Duration::ZERO);
+        }
+        assert_eq!(enabled(&vm), [true, true]);
+
+        let processor = take_processor(&mut vm, (0, 0));
+        assert_variables(&processor, map_iter! {
+            u16str!("sorter_initial"): LValue::NULL,
+            u16str!("sorter_copper"): LValue::from(Content::Item(content::items::FROM_NAME["copper"])),
+            u16str!("sorter_water"): LValue::from(Content::Item(content::items::FROM_NAME["copper"])),
+
+            u16str!("unloader_initial"): LValue::from(Content::Item(content::items::FROM_NAME["lead"])),
+            u16str!("unloader_null"): LValue::NULL,
+
+            u16str!("door_initial"): LValue::from(false),
+            u16str!("door_open"): LValue::from(true),
+
+            u16str!("light_initial"): LValue::from(rgba8888_to_double_bits(0x00ff00ff)),
+            u16str!("light_ok"): LValue::from(true),
+            u16str!("light_enabled"): LValue::from(true),
+
+            u16str!("conveyor_initial"): LValue::from(true),
+            u16str!("conveyor_enabled"): LValue::from(false),
+            u16str!("drill_enabled"): LValue::from(false),
+        });
+
+        let light = vm.building(PackedPoint2 { x: 5, y: 0 }).unwrap();
+        assert!(matches!(
+            *light.data.borrow(),
+            BuildingData::Illuminator(0xff007fff)
+        ));
+    }
+
+    thread_local! {
+        static SENSOR_TESTS: Vec<(U16String, &'static str, &'static str, LValue)> = map_iter_from![
+            ("null", "@dead"): true,
+
+            ("@hyper-processor", "@name"): u16str!("hyper-processor"),
+            ("@hyper-processor", "@id"): 141,
+            ("@hyper-processor", "@size"): 3,
+
+            ("@titanium", "@name"): u16str!("titanium"),
+            ("@titanium", "@id"): 6,
+
+            ("@cryofluid", "@name"): u16str!("cryofluid"),
+            ("@cryofluid", "@id"): 3,
+
+            ("@flare", "@name"): u16str!("flare"),
+            ("@flare", "@id"): 15,
+
+            (r#""123456789""#, "@size"): 9,
+
+            ("@sharded", "@id"): 1,
+
+            ("1", "@dead"): LValue::NULL,
+
+            ("@this", "@enabled"): true,
+            ("@this", "@config"): LValue::NULL,
+            ("@this", "@dead"): false,
+            ("@this", "@x"): 0,
+            ("@this", "@y"): 0,
+            ("@this", "@size"): 1,
+            ("@this", "@type"): Content::Block(content::blocks::FROM_NAME[WORLD_PROCESSOR]),
+
+            ("processor1", "@enabled"): false,
+            ("processor1", "@config"): LValue::NULL,
             ("processor1", "@dead"): false,
             ("processor1", "@x"): 1,
             ("processor1", "@y"): 0,
@@ -1453,6 +3371,9 @@ mod tests {
             ("sorter1", "@enabled"): true,
             ("sorter1", "@config"): Content::Item(content::items::FROM_NAME["graphite"]),
             ("sorter1", "@graphite"): LValue::NULL,
+            ("sorter1", "@health"): 40,
+            ("sorter1", "@maxHealth"): 40,
+            ("sorter1", "@team"): 1,
         ]
         .map(|((target, sensor), want)| (u16format!("_{target}_{sensor}"), target, sensor, want))
         .collect();
@@ -1558,6 +3479,67 @@ mod tests {
         });
     }
 
+    #[test]
+    fn test_to_schematic_large_blocks() {
+        let code = "sensor size display1 @size; stop";
+        let tiles = [
+            SchematicTile {
+                block: HYPER_PROCESSOR.into(),
+                position: PackedPoint2 { x: 1, y: 1 },
+                config: ProcessorConfig {
+                    code: code.into(),
+                    links: vec![ProcessorLinkConfig {
+                        name: "display1".into(),
+                        x: 5,
+                        y: 1,
+                    }],
+                }
+                .to_object()
+                .unwrap(),
+                rotation: 0,
+            },
+            SchematicTile {
+                block: "large-logic-display".into(),
+                position: PackedPoint2 { x: 6, y: 2 },
+                config: Object::Null,
+                rotation: 0,
+            },
+        ];
+
+        let mut vm = LogicVM::from_schematic_tiles(&tiles).unwrap();
+        run(&mut vm, 2, true);
+
+        // schematic positions are the centers of the blocks
+        assert_eq!(
+            vm.building(PackedPoint2 { x: 0, y: 0 }).unwrap().block.name.as_str(),
+            HYPER_PROCESSOR
+        );
+        let display = vm.building(PackedPoint2 { x: 9, y: 5 }).unwrap();
+        assert_eq!(display.position, PackedPoint2 { x: 4, y: 0 });
+        assert_eq!(display.center(), PackedPoint2 { x: 6, y: 2 });
+
+        assert_eq!(vm.to_schematic().unwrap().tiles(), &tiles.to_vec());
+
+        // from_schematic_tile keeps treating positions as bottom left corners
+        assert_eq!(
+            Building::from_schematic_tile(&tiles[1], &vm)
+                .unwrap()
+                .position,
+            PackedPoint2 { x: 6, y: 2 }
+        );
+        assert_eq!(
+            Building::from_schematic_tile_center(&tiles[1], &vm)
+                .unwrap()
+                .position,
+            PackedPoint2 { x: 4, y: 0 }
+        );
+
+        let processor = take_processor(&mut vm, (0, 0));
+        assert_variables(&processor, map_iter! {
+            u16str!("size"): LValue::from(6),
+        });
+    }
+
     #[test]
     fn test_sensor_schematic() {
         SENSOR_TESTS.with(|tests| {
@@ -2139,11 +4121,192 @@ mod tests {
     }
 
     #[test]
-    fn test_setrate_unpriv() {
-        let mut vm = single_processor_vm(MICRO_PROCESSOR, "setrate 10; stop");
+    fn test_setrate_unpriv() {
+        let mut vm = single_processor_vm(MICRO_PROCESSOR, "setrate 10; stop");
+        run(&mut vm, 1, true);
+        let processor = take_processor(&mut vm, (0, 0));
+        assert_eq!(processor.state.ipt, 2.0);
+    }
+
+    type Messages = Rc<RefCell<Vec<(MessageType, f64, U16String)>>>;
+
+    fn message_vm(name: &str, code: &str, shown: bool) -> (LogicVM, Messages) {
+        let messages = Rc::new(RefCell::new(Vec::new()));
+
+        let mut builder = LogicVMBuilder::new();
+        builder.add_building(
+            Building::from_processor_config(
+                name,
+                PackedPoint2::new(0, 0),
+                &ProcessorConfig::from_code(code),
+                &builder,
+            )
+            .unwrap(),
+        );
+        builder.set_locales(LocaleBundle::from_properties("greeting = Hello, world!"));
+        builder.set_message_handler(Some(Box::new({
+            let messages = messages.clone();
+            move |message_type, duration, text| {
+                messages
+                    .borrow_mut()
+                    .push((message_type, duration, text.to_owned()));
+                shown
+            }
+        })));
+
+        (builder.build().unwrap(), messages)
+    }
+
+    #[test]
+    fn test_message() {
+        let (mut vm, messages) = message_vm(
+            WORLD_PROCESSOR,
+            r#"
+            print "foo"
+            message notify 0 a
+            print "bar"
+            message announce 3 b
+            print "baz"
+            message toast 1.5 @wait
+            print "@greeting"
+            message mission 0 c
+            print "@missing"
+            message announce 2 d
+            stop
+            "#,
+            true,
+        );
+
+        run(&mut vm, 2, true);
+
+        assert_eq!(*messages.borrow(), vec![
+            (MessageType::Notify, 0., U16String::from_str("foo")),
+            (MessageType::Announce, 3., U16String::from_str("bar")),
+            (MessageType::Toast, 1.5, U16String::from_str("baz")),
+            (MessageType::Mission, 0., U16String::from_str("Hello, world!")),
+            (MessageType::Announce, 2., U16String::from_str("@missing")),
+        ]);
+
+        let processor = take_processor(&mut vm, (0, 0));
+        assert_eq!(processor.state.printbuffer, u16str!(""));
+        assert_variables(&processor, map_iter! {
+            u16str!("a"): LValue::from(1),
+            u16str!("b"): LValue::from(1),
+            u16str!("c"): LValue::from(1),
+            u16str!("d"): LValue::from(1),
+        });
+        assert!(!processor.state.has_variable(u16str!("@wait")));
+    }
+
+    #[test]
+    fn test_message_not_shown() {
+        let (mut vm, messages) = message_vm(
+            WORLD_PROCESSOR,
+            r#"
+            print "foo"
+            message announce 3 success
+            stop
+            "#,
+            false,
+        );
+
+        run(&mut vm, 1, true);
+
+        assert_eq!(messages.borrow().len(), 1);
+
+        let processor = take_processor(&mut vm, (0, 0));
+        assert_eq!(processor.state.printbuffer, u16str!("foo"));
+        assert_variables(&processor, map_iter! {
+            u16str!("success"): LValue::from(0),
+        });
+    }
+
+    #[test]
+    fn test_message_wait() {
+        let (mut vm, messages) = message_vm(
+            WORLD_PROCESSOR,
+            r#"
+            print "foo"
+            message announce 3 @wait
+            stop
+            "#,
+            false,
+        );
+
+        run(&mut vm, 3, false);
+
+        // the processor should yield and retry once per tick
+        assert_eq!(messages.borrow().len(), 3);
+
+        let processor = take_processor(&mut vm, (0, 0));
+        assert_eq!(processor.state.counter, 1);
+        assert_eq!(processor.state.printbuffer, u16str!("foo"));
+    }
+
+    #[test]
+    fn test_message_unpriv() {
+        let (mut vm, messages) = message_vm(
+            HYPER_PROCESSOR,
+            r#"
+            print "foo"
+            message announce 3 success
+            localeprint "greeting"
+            stop
+            "#,
+            true,
+        );
+
+        run(&mut vm, 1, true);
+
+        assert!(messages.borrow().is_empty());
+
+        let processor = take_processor(&mut vm, (0, 0));
+        assert_eq!(processor.state.printbuffer, u16str!("foo"));
+        assert!(!processor.state.has_variable(u16str!("success")));
+    }
+
+    #[test]
+    fn test_message_no_handler() {
+        let mut vm = single_processor_vm(
+            WORLD_PROCESSOR,
+            r#"
+            print "foo"
+            message announce 3 success
+            stop
+            "#,
+        );
+
+        run(&mut vm, 1, true);
+
+        let processor = take_processor(&mut vm, (0, 0));
+        assert_eq!(processor.state.printbuffer, u16str!(""));
+        assert_variables(&processor, map_iter! {
+            u16str!("success"): LValue::from(1),
+        });
+    }
+
+    #[test]
+    fn test_localeprint() {
+        let (mut vm, _) = message_vm(
+            WORLD_PROCESSOR,
+            r#"
+            localeprint "greeting"
+            print " "
+            localeprint "missing"
+            print " "
+            localeprint 1
+            stop
+            "#,
+            true,
+        );
+
         run(&mut vm, 1, true);
+
         let processor = take_processor(&mut vm, (0, 0));
-        assert_eq!(processor.state.ipt, 2.0);
+        assert_eq!(
+            processor.state.printbuffer,
+            u16str!("Hello, world! ???missing??? ???1???")
+        );
     }
 
     const CONDITION_TESTS: &[(&str, &str, &str, bool)] = &[
@@ -2952,6 +5115,579 @@ mod tests {
         assert_eq!(variables[u16str!("team4")], LValue::NULL);
     }
 
+    #[test]
+    fn test_load_error_spans() {
+        fn load_err(code: &str) -> VMLoadError {
+            let mut builder = LogicVMBuilder::new();
+            match Building::from_processor_config(
+                MICRO_PROCESSOR,
+                PackedPoint2::new(0, 0),
+                &ProcessorConfig::from_code(code),
+                &builder,
+            ) {
+                Ok(building) => {
+                    builder.add_building(building);
+                    builder.build().err().unwrap()
+                }
+                Err(err) => err,
+            }
+        }
+
+        fn span(line: usize, start: usize, end: usize) -> Span {
+            Span {
+                start: Location::new(line, start),
+                end: Location::new(line, end),
+            }
+        }
+
+        let err = load_err("noop\n  jump foo always");
+        assert_eq!(
+            err.to_string(),
+            "invalid instruction at line 2, column 3: label not found: foo"
+        );
+        assert_eq!(err.span(), Some(span(2, 3, 18)));
+
+        for (code, want_span, want_error) in [
+            (
+                "noop\n  jump foo always",
+                span(2, 3, 18),
+                InstructionError::LabelNotFound("foo".into()),
+            ),
+            (
+                "jump 5.5 always",
+                span(1, 1, 16),
+                InstructionError::JumpOutOfRange(5.),
+            ),
+            (
+                "noop\nfoo bar",
+                span(2, 1, 8),
+                InstructionError::UnknownInstruction("foo".into()),
+            ),
+        ] {
+            match load_err(code) {
+                VMLoadError::BadInstruction { span, error } => {
+                    assert_eq!((span, error), (want_span, want_error), "{code}");
+                }
+                err => panic!("unexpected error for {code:?}: {err}"),
+            }
+        }
+
+        match load_err("noop\nset x") {
+            VMLoadError::BadProcessorCode(err) => assert_eq!(err.span, span(2, 6, 6)),
+            err => panic!("unexpected error: {err}"),
+        }
+    }
+
+    #[test]
+    fn test_custom_instructions() {
+        let builder = |code: &str| {
+            let mut builder = LogicVMBuilder::new();
+            builder.add_custom_instruction("double", 2, |args, state, _| {
+                let value = args[1].get(state).num();
+                args[0].set(state, (value * 2.).into());
+                InstructionResult::Ok
+            });
+            // keywords can be used as custom instruction names too
+            builder.add_custom_instruction("status", 0, |_, _, _| InstructionResult::Yield);
+            builder.add_building(
+                Building::from_processor_config(
+                    HYPER_PROCESSOR,
+                    PackedPoint2::new(0, 0),
+                    &ProcessorConfig::from_code(code),
+                    &builder,
+                )
+                .unwrap(),
+            );
+            builder
+        };
+
+        let empty = builder("");
+        let mut names = empty.custom_instructions().collect::<Vec<_>>();
+        names.sort();
+        assert_eq!(names, vec![("double", 2), ("status", 0)]);
+
+        let mut vm = builder(
+            "
+            set a 21
+            double b a extra
+            status
+            double c b
+            stop
+            ",
+        )
+        .build()
+        .unwrap();
+
+        // status yields, so this takes 2 ticks even though the hyper processor has a high ipt
+        run(&mut vm, 1, false);
+        with_processor(&mut vm, (0, 0), |p| {
+            assert_variables(p, map_iter! {
+                u16str!("b"): LValue::from(42),
+                u16str!("c"): LValue::NULL,
+            });
+        });
+
+        run(&mut vm, 1, true);
+        with_processor(&mut vm, (0, 0), |p| {
+            assert_variables(p, map_iter! {
+                u16str!("c"): LValue::from(84),
+            });
+        });
+
+        assert!(matches!(
+            builder("double a").build(),
+            Err(VMLoadError::BadInstruction {
+                error: InstructionError::NotEnoughArguments {
+                    want: 2,
+                    got: 1,
+                    ..
+                },
+                ..
+            })
+        ));
+    }
+
+    #[test]
+    fn test_lenient() {
+        let code = "
+            set x 1
+            ucontrol move 1 2
+            status false wet @unit 10
+            op add x x 1
+            stop
+            ";
+
+        let builder = |lenient| {
+            let mut builder = LogicVMBuilder::new();
+            builder.set_lenient(lenient);
+            builder.add_building(
+                Building::from_processor_config(
+                    MICRO_PROCESSOR,
+                    PackedPoint2::new(0, 0),
+                    &ProcessorConfig::from_code(code),
+                    &builder,
+                )
+                .unwrap(),
+            );
+            builder
+        };
+
+        assert!(matches!(
+            builder(false).build(),
+            Err(VMLoadError::BadInstruction {
+                error: InstructionError::UnknownInstruction(_),
+                ..
+            })
+        ));
+
+        let mut vm = builder(true).build().unwrap();
+        assert!(vm.lenient());
+
+        run(&mut vm, 10, true);
+
+        let processor = take_processor(&mut vm, (0, 0));
+        assert_variables(&processor, map_iter! {
+            u16str!("x"): LValue::from(2),
+        });
+        assert_eq!(
+            processor
+                .warnings()
+                .iter()
+                .map(|w| (w.span().unwrap().start.line, w.to_string()))
+                .collect::<Vec<_>>(),
+            vec![
+                (
+                    3,
+                    "invalid instruction at line 3, column 13: unknown instruction: ucontrol"
+                        .to_string()
+                ),
+                (
+                    4,
+                    "invalid instruction at line 4, column 13: unknown instruction: status"
+                        .to_string()
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_processor_limits() {
+        fn load_with(
+            processor: &str,
+            code: &str,
+            links: usize,
+            limits: ProcessorLimits,
+        ) -> VMLoadResult<LogicVM> {
+            // rows of 100 cells, starting next to the processor
+            let position = |i: i16| PackedPoint2::new(i % 100 + 1, i / 100);
+
+            let mut builder = LogicVMBuilder::new();
+            builder.add_buildings(
+                (1..=links as i16)
+                    .map(|i| {
+                        Building::from_config(MEMORY_CELL, position(i), &Object::Null, &builder)
+                            .unwrap()
+                    })
+                    .collect::<Vec<_>>(),
+            );
+            builder.add_building(Building::from_processor_config_with_limits(
+                processor,
+                PackedPoint2::new(0, 0),
+                &ProcessorConfig {
+                    code: code.into(),
+                    links: (1..=links as i16)
+                        .map(|i| {
+                            let position = position(i);
+                            ProcessorLinkConfig::unnamed(position.x, position.y)
+                        })
+                        .collect(),
+                },
+                Some(limits),
+                &builder,
+            )?);
+            builder.build()
+        }
+
+        fn load(code: &str, links: usize, limits: ProcessorLimits) -> VMLoadResult<LogicVM> {
+            load_with(LOGIC_PROCESSOR, code, links, limits)
+        }
+
+        let limits = ProcessorLimits::default();
+
+        // instructions
+
+        let code = "noop\n".repeat(1000);
+        load(&code, 0, limits).unwrap();
+
+        let code = code + "stop\n";
+        assert!(matches!(
+            load(&code, 0, limits),
+            Err(VMLoadError::TooManyInstructions {
+                span: Span {
+                    start: Location { line: 1001, .. },
+                    ..
+                },
+                count: 1001,
+                max: 1000,
+            })
+        ));
+
+        // without limits, the extra instructions are loaded
+        let mut builder = LogicVMBuilder::new();
+        builder.add_building(
+            Building::from_processor_config(
+                LOGIC_PROCESSOR,
+                PackedPoint2::new(0, 0),
+                &ProcessorConfig::from_code(&code),
+                &builder,
+            )
+            .unwrap(),
+        );
+        builder.build().unwrap();
+
+        // code length
+
+        let code = format!("print \"{}\"", "a".repeat(100 * 1024));
+        assert!(matches!(
+            load(&code, 0, limits),
+            Err(VMLoadError::CodeTooLong { max: 102400, .. })
+        ));
+
+        // tokens
+
+        load(r#"print 1 2 3 4 5 6 7 8 9 10 11 12 13 "1 2 3" 15 # comment"#, 0, limits).unwrap();
+        assert!(matches!(
+            load("noop\nprint 1 2 3 4 5 6 7 8 9 10 11 12 13 14 15 16", 0, limits),
+            Err(VMLoadError::TooManyTokens {
+                span: Span {
+                    start: Location { line: 2, .. },
+                    ..
+                },
+                count: 17,
+                max: 16,
+            })
+        ));
+
+        // without source code, the code length and tokens can't be checked
+
+        let load_without_source = |limits: ProcessorLimits| {
+            let mut builder = LogicVMBuilder::new();
+            builder.add_building(Building::from_processor_builder(
+                content::blocks::FROM_NAME[LOGIC_PROCESSOR],
+                PackedPoint2::new(0, 0),
+                ProcessorBuilder {
+                    ipt: 2.,
+                    privileged: false,
+                    code: ProcessorBuilder::parse_code("noop").unwrap(),
+                    source: None,
+                    links: &[],
+                    instruction_hook: None,
+                    limits: Some(limits),
+                },
+                &builder,
+            )?);
+            builder.build()
+        };
+
+        assert!(matches!(
+            load_without_source(limits),
+            Err(VMLoadError::UncheckedLimits)
+        ));
+        load_without_source(ProcessorLimits {
+            max_code_bytes: None,
+            max_tokens: None,
+            ..limits
+        })
+        .unwrap();
+
+        // links
+
+        // only world processors can reach enough buildings to hit the default limit
+        load_with(WORLD_PROCESSOR, "noop", 6000, limits).unwrap();
+        assert!(matches!(
+            load_with(WORLD_PROCESSOR, "noop", 6001, limits),
+            Err(VMLoadError::TooManyLinks {
+                count: 6001,
+                max: 6000,
+            })
+        ));
+
+        let limits = ProcessorLimits {
+            max_links: Some(2),
+            ..limits
+        };
+        load("read x cell2 0", 2, limits).unwrap();
+        assert!(matches!(
+            load("read x cell3 0", 3, limits),
+            Err(VMLoadError::TooManyLinks { count: 3, max: 2 })
+        ));
+
+        // variables
+
+        let mut code = (0..333)
+            .map(|i| format!("op add a{i} b{i} c{i}\n"))
+            .collect::<String>();
+        code += "set d 1\n";
+        load(&code, 0, ProcessorLimits::default()).unwrap();
+
+        code += "set e 1\n";
+        assert!(matches!(
+            load(&code, 0, ProcessorLimits::default()),
+            Err(VMLoadError::TooManyVariables {
+                count: 1001,
+                max: 1000,
+            })
+        ));
+
+        let limits = ProcessorLimits {
+            max_variables: Some(2),
+            ..limits
+        };
+        load("read x cell1 0\nset y @pi\nset y x", 1, limits).unwrap();
+        assert!(matches!(
+            load("set x 1\nset y 2\nset z 3", 0, limits),
+            Err(VMLoadError::TooManyVariables { count: 3, max: 2 })
+        ));
+    }
+
+    #[test]
+    fn test_hot_patch() {
+        let mut vm = single_processor_vm(
+            LOGIC_PROCESSOR,
+            r#"
+            set x 5
+            set y 6
+            print "hi"
+            loop:
+            op add n n 1
+            stop
+            jump loop always
+            "#,
+        );
+        let globals = LVar::create_global_constants();
+
+        let hot_patch = |vm: &LogicVM, code: &str| {
+            let building = vm.building(PackedPoint2::new(0, 0)).unwrap();
+            building.data.borrow_mut().unwrap_processor_mut().hot_patch(
+                parser::parse(code).unwrap(),
+                None,
+                vm,
+                building,
+                &globals,
+            )
+        };
+
+        run(&mut vm, 1, true);
+        with_processor(&mut vm, (0, 0), |p| {
+            assert_eq!(p.state.counter, 4);
+        });
+
+        // the counter should stay at the same offset from the loop label
+        hot_patch(
+            &vm,
+            r#"
+            set x 100
+            print "!"
+            loop:
+            op add n n 2
+            op add n n 5
+            stop
+            set w 1
+            "#,
+        )
+        .unwrap();
+        with_processor(&mut vm, (0, 0), |p| {
+            assert_eq!(p.state.counter, 3);
+            assert_eq!(p.state.printbuffer, u16str!("hi"));
+            assert_variables(p, map_iter! {
+                u16str!("x"): Some(LValue::from(5)),
+                u16str!("y"): None,
+                u16str!("n"): Some(LValue::from(1)),
+                u16str!("w"): Some(LValue::NULL),
+            });
+        });
+
+        run(&mut vm, 1, true);
+        with_processor(&mut vm, (0, 0), |p| {
+            assert_eq!(p.state.counter, 4);
+            assert_variables(p, map_iter! {
+                u16str!("x"): LValue::from(5),
+                u16str!("n"): LValue::from(6),
+            });
+        });
+
+        // errors should roll back all changes
+        assert!(matches!(
+            hot_patch(&vm, "set n 0\njump missing always"),
+            Err(VMLoadError::BadInstruction { .. })
+        ));
+        with_processor(&mut vm, (0, 0), |p| {
+            assert_eq!(p.state.counter, 4);
+            assert_variables(p, map_iter! {
+                u16str!("n"): LValue::from(6),
+                u16str!("w"): LValue::NULL,
+            });
+        });
+
+        // without a matching label, the counter is left unchanged
+        hot_patch(&vm, "op add n n 10\nstop\nnoop\nnoop\nend").unwrap();
+        with_processor(&mut vm, (0, 0), |p| {
+            assert_eq!(p.state.counter, 4);
+            assert!(p.state.enabled());
+        });
+
+        run(&mut vm, 1, true);
+        with_processor(&mut vm, (0, 0), |p| {
+            assert_eq!(p.state.counter, 1);
+            assert_variables(p, map_iter! {
+                u16str!("n"): Some(LValue::from(16)),
+                u16str!("x"): None,
+            });
+        });
+    }
+
+    #[test]
+    fn test_content_version() {
+        let content = ContentVersion::V7;
+        let copper = content.items().from_name("copper").unwrap();
+
+        let mut builder = LogicVMBuilder::new();
+        builder.set_content_version(content);
+        builder.add_buildings([
+            Building::from_processor_config(
+                HYPER_PROCESSOR,
+                PackedPoint2::new(0, 0),
+                &ProcessorConfig::from_code(
+                    "
+                    set blocks @blockCount
+                    set items @itemCount
+                    lookup block block 0
+                    set copper @copper
+                    stop
+                    ",
+                ),
+                &builder,
+            )
+            .unwrap(),
+            Building::from_config(
+                SORTER,
+                PackedPoint2::new(3, 0),
+                &ContentID {
+                    type_: ContentType::Item,
+                    id: copper.id as i16,
+                }
+                .into(),
+                &builder,
+            )
+            .unwrap(),
+        ]);
+
+        // blocks that were added in later versions can't be loaded
+        if let Some(block) = ContentVersion::BE
+            .blocks()
+            .values()
+            .iter()
+            .find(|b| content.blocks().from_name(&b.name).is_none())
+        {
+            assert!(matches!(
+                Building::from_config(
+                    &block.name,
+                    PackedPoint2::new(5, 5),
+                    &Object::Null,
+                    &builder
+                ),
+                Err(VMLoadError::UnknownBlockType(_))
+            ));
+        }
+
+        let mut vm = builder.build().unwrap();
+        assert_eq!(vm.content(), content);
+        assert!(core::ptr::eq(
+            vm.building((0, 0).into()).unwrap().block,
+            content.blocks().from_name(HYPER_PROCESSOR).unwrap()
+        ));
+        match &*vm.building((3, 0).into()).unwrap().data.borrow() {
+            BuildingData::Sorter(Some(item)) => assert!(core::ptr::eq(*item, copper)),
+            other => panic!("expected sorter, got {other:?}"),
+        }
+
+        run(&mut vm, 1, true);
+
+        let variables = take_processor(&mut vm, (0, 0)).state.variables;
+
+        assert_eq!(
+            variables[u16str!("blocks")],
+            LValue::from(content.blocks().logic_count())
+        );
+        assert_eq!(
+            variables[u16str!("items")],
+            LValue::from(content.items().logic_count())
+        );
+        assert!(matches!(
+            variables[u16str!("block")].obj(),
+            Some(LObject::Content(Content::Block(b)))
+                if core::ptr::eq(*b, content.blocks().from_logic_id(0).unwrap())
+        ));
+        assert!(matches!(
+            variables[u16str!("copper")].obj(),
+            Some(LObject::Content(Content::Item(b))) if core::ptr::eq(*b, copper)
+        ));
+
+        assert_eq!(
+            ContentVersion::BE.convert_id(
+                ContentID {
+                    type_: ContentType::Item,
+                    id: content::items::FROM_NAME["copper"].id as i16,
+                },
+                content
+            ),
+            Some(ContentID {
+                type_: ContentType::Item,
+                id: copper.id as i16,
+            })
+        );
+    }
+
     #[test]
     fn test_draw() {
         let tests = [
@@ -0,0 +1,248 @@
+use alloc::vec::Vec;
+
+use super::{Building, PowerModule};
+use crate::{
+    types::{PackedPoint2, content::Block},
+    utils::RapidHashMap,
+};
+
+const POWER_EPSILON: f64 = 0.000001;
+
+/// Returns the default power configuration for a block, or `None` if the block does not use power.
+///
+/// Values are taken from Mindustry's `Blocks.java`. Generators are assumed to always run at full efficiency, and consumers always request their full power usage. Blocks not listed here can be given power by setting [`BuildingProps::power`](super::BuildingProps::power).
+pub fn default_power(block: &Block) -> Option<PowerModule> {
+    Some(match block.name.as_str() {
+        "power-node" | "power-node-large" | "surge-tower" | "beam-node" | "beam-tower" => {
+            PowerModule::node()
+        }
+
+        "battery" => PowerModule::battery(1000.),
+        "battery-large" => PowerModule::battery(50000.),
+
+        "combustion-generator" => PowerModule::producer(1.),
+        "thermal-generator" => PowerModule::producer(18. / 60.),
+        "steam-generator" => PowerModule::producer(5.5),
+        "differential-generator" => PowerModule::producer(18.),
+        "rtg-generator" => PowerModule::producer(4.5),
+        "solar-panel" => PowerModule::producer(0.1),
+        "large-solar-panel" => PowerModule::producer(1.3),
+        "thorium-reactor" => PowerModule::producer(15.),
+        "impact-reactor" => PowerModule::producer(130.),
+
+        "mender" => PowerModule::consumer(0.3),
+        "mend-projector" => PowerModule::consumer(0.4),
+        "illuminator" => PowerModule::consumer(0.05),
+        "laser-drill" => PowerModule::consumer(1.1),
+        "blast-drill" => PowerModule::consumer(3.),
+        "silicon-smelter" => PowerModule::consumer(0.5),
+        "kiln" => PowerModule::consumer(0.6),
+        "pulverizer" => PowerModule::consumer(0.5),
+        "lancer" => PowerModule::consumer(6.),
+
+        _ => return None,
+    })
+}
+
+/// A group of buildings that share power, equivalent to Mindustry's `PowerGraph`.
+///
+/// Buildings are connected by power node links, and by being adjacent to each other (unless both buildings only consume power).
+#[derive(Debug, Default)]
+pub struct PowerGraph {
+    pub buildings: Vec<Building>,
+    /// Total power produced in the last update, including power drawn from batteries.
+    pub last_power_produced: f64,
+    /// Total power requested by consumers in the last update.
+    pub last_power_needed: f64,
+    /// Total power stored in batteries at the start of the last update.
+    pub last_power_stored: f64,
+    /// Total battery capacity at the start of the last update.
+    pub last_capacity: f64,
+    last_delta: f64,
+}
+
+impl PowerGraph {
+    /// Returns the value of `sensor @powerNetIn`, in power units per second.
+    pub fn power_in(&self) -> f64 {
+        self.scaled(self.last_power_produced)
+    }
+
+    /// Returns the value of `sensor @powerNetOut`, in power units per second.
+    pub fn power_out(&self) -> f64 {
+        self.scaled(self.last_power_needed)
+    }
+
+    fn scaled(&self, value: f64) -> f64 {
+        if self.last_delta > 0. {
+            value / self.last_delta * 60.
+        } else {
+            0.
+        }
+    }
+
+    /// Balances the power in this graph for one tick.
+    pub(super) fn update(&mut self, delta: f64) {
+        let mut produced = 0.;
+        let mut needed = 0.;
+        let mut stored = 0.;
+        let mut capacity = 0.;
+
+        for building in &self.buildings {
+            if let Some(power) = &building.props.borrow().power {
+                produced += power.production * delta;
+                needed += power.consumption * delta;
+                stored += power.stored();
+                capacity += power.capacity;
+            }
+        }
+
+        self.last_delta = delta;
+        self.last_power_needed = needed;
+        self.last_power_produced = produced;
+        self.last_power_stored = stored;
+        self.last_capacity = capacity;
+
+        if needed - produced > POWER_EPSILON {
+            // discharge batteries
+            let used = stored.min(needed - produced);
+            if stored > 0. {
+                let consumed = ((needed - produced) / stored).min(1.);
+                self.update_batteries(|status| status * (1. - consumed));
+            }
+            produced += used;
+            self.last_power_produced += used;
+        } else if produced - needed > POWER_EPSILON {
+            // charge batteries
+            let empty = capacity - stored;
+            if empty > 0. {
+                let charged = ((produced - needed) / empty).min(1.);
+                self.update_batteries(|status| status + (1. - status) * charged);
+            }
+            produced -= empty.min(produced - needed);
+        }
+
+        let coverage = if needed <= POWER_EPSILON {
+            if produced <= POWER_EPSILON { 0. } else { 1. }
+        } else {
+            (produced / needed).min(1.)
+        };
+
+        for building in &self.buildings {
+            if let Some(power) = &mut building.props.borrow_mut().power
+                && power.consumption > 0.
+                && power.capacity == 0.
+            {
+                power.status = coverage;
+            }
+        }
+    }
+
+    fn update_batteries(&self, f: impl Fn(f64) -> f64) {
+        for building in &self.buildings {
+            if let Some(power) = &mut building.props.borrow_mut().power
+                && power.capacity > 0.
+            {
+                power.status = f(power.status).clamp(0., 1.);
+            }
+        }
+    }
+}
+
+/// All of the power graphs in a VM.
+#[derive(Debug, Default)]
+pub struct PowerGraphs {
+    pub graphs: Vec<PowerGraph>,
+    /// Maps the position of each building with power to the index of its graph.
+    by_position: RapidHashMap<PackedPoint2, usize>,
+}
+
+impl PowerGraphs {
+    pub(super) fn build_all(
+        buildings: &[Building],
+        buildings_map: &RapidHashMap<PackedPoint2, usize>,
+    ) -> Self {
+        let has_power = |i: usize| buildings[i].props.borrow().power.is_some();
+
+        // adjacency list of building indices
+        let mut edges = RapidHashMap::<usize, Vec<usize>>::default();
+        let mut connect = |a: usize, b: usize| {
+            if a != b && buildings[a].team() == buildings[b].team() {
+                edges.entry(a).or_default().push(b);
+                edges.entry(b).or_default().push(a);
+            }
+        };
+
+        for (i, building) in buildings.iter().enumerate() {
+            let props = building.props.borrow();
+            let Some(power) = &props.power else {
+                continue;
+            };
+
+            // laser links
+            for link in &power.links {
+                if let Some(&j) = buildings_map.get(link)
+                    && has_power(j)
+                {
+                    connect(i, j);
+                }
+            }
+
+            // adjacent buildings
+            for position in building.iter_positions() {
+                for (dx, dy) in [(1, 0), (-1, 0), (0, 1), (0, -1)] {
+                    let neighbour = PackedPoint2 {
+                        x: position.x.wrapping_add(dx),
+                        y: position.y.wrapping_add(dy),
+                    };
+                    if let Some(&j) = buildings_map.get(&neighbour)
+                        && j != i
+                        && let Some(other) = &buildings[j].props.borrow().power
+                        && !(power.is_consumer_only() && other.is_consumer_only())
+                    {
+                        connect(i, j);
+                    }
+                }
+            }
+        }
+
+        // flood fill, visiting buildings in update order so that graphs are deterministic
+        let mut graphs = Self::default();
+        for start in 0..buildings.len() {
+            if !has_power(start) || graphs.by_position.contains_key(&buildings[start].position) {
+                continue;
+            }
+
+            let index = graphs.graphs.len();
+            let mut graph = PowerGraph::default();
+            let mut stack = Vec::from([start]);
+            graphs.by_position.insert(buildings[start].position, index);
+
+            while let Some(i) = stack.pop() {
+                graph.buildings.push(buildings[i].clone());
+                for &j in edges.get(&i).into_iter().flatten() {
+                    if !graphs.by_position.contains_key(&buildings[j].position) {
+                        graphs.by_position.insert(buildings[j].position, index);
+                        stack.push(j);
+                    }
+                }
+            }
+
+            graphs.graphs.push(graph);
+        }
+
+        graphs
+    }
+
+    /// Returns the power graph containing a building, or `None` if the building does not use power.
+    pub fn graph_of(&self, building: &Building) -> Option<&PowerGraph> {
+        self.by_position
+            .get(&building.position)
+            .map(|&i| &self.graphs[i])
+    }
+
+    pub(super) fn update(&mut self, delta: f64) {
+        for graph in &mut self.graphs {
+            graph.update(delta);
+        }
+    }
+}
@@ -15,16 +15,21 @@ use replace_with::replace_with_or_default_and_return;
 use widestring::{U16Str, U16String};
 
 use super::{
-    Building, BuildingData, DrawCommand, InstructionResult, LValue, LVar, LogicVM, VMLoadError,
-    VMLoadResult,
+    Building, BuildingData, BuildingProps, DrawCommand, InstructionError, InstructionResult,
+    LValue, LVar, LogicVM, VMLoadError, VMLoadResult,
     instructions::{Instruction, InstructionBuilder, InstructionTrait, Noop},
     variables::{Constants, Variables},
 };
 #[cfg(feature = "std")]
-use crate::parser::LogicParser;
+use super::{Content, instructions::MAX_IPT};
+#[cfg(feature = "std")]
+use crate::{parser, types::MapProcessorState};
 use crate::{
     parser::ast,
-    types::{PackedPoint2, ProcessorLinkConfig, content},
+    types::{
+        Object, PackedPoint2, ProcessorLinkConfig,
+        content::{self, ContentVersion},
+    },
     utils::{RapidHashMap, RapidHashSet},
 };
 
@@ -39,12 +44,77 @@ pub type InstructionHook =
 #[derivative(Debug)]
 pub struct Processor {
     instructions: Vec<Instruction>,
+    labels: Rc<RapidHashMap<String, usize>>,
     #[derivative(Debug = "ignore")]
     instruction_hook: Option<Box<InstructionHook>>,
+    source: Option<String>,
+    warnings: Vec<VMLoadError>,
+    limits: Option<ProcessorLimits>,
     pub state: ProcessorState,
 }
 
 impl Processor {
+    /// Returns the problems that were ignored when this processor's code was loaded, eg. unknown instructions in a [lenient](super::LogicVMBuilder::set_lenient) VM.
+    pub fn warnings(&self) -> &[VMLoadError] {
+        &self.warnings
+    }
+
+    /// Returns the source code of this processor, if it is known.
+    ///
+    /// This is set from [`ProcessorBuilder::source`], which processors created from a [`ProcessorConfig`](crate::types::ProcessorConfig) fill in automatically, and is cleared when the processor's code is replaced.
+    pub fn source(&self) -> Option<&str> {
+        self.source.as_deref()
+    }
+
+    /// Sets the source code that this processor's instructions were parsed from.
+    ///
+    /// This does not change the processor's instructions; it's only used when exporting the processor, eg. with [`LogicVM::to_schematic`].
+    pub fn set_source(&mut self, source: Option<String>) {
+        self.source = source;
+    }
+
+    /// Restores the variables and instructions per tick that were saved in a map.
+    ///
+    /// Like in Mindustry, saved variables that don't exist in the current code are ignored. Units can't be restored, so they are replaced with null.
+    #[cfg(feature = "std")]
+    pub fn restore_map_state(&mut self, saved: &MapProcessorState, vm: &LogicVM) {
+        if let Some(ipt) = saved.ipt {
+            self.state.ipt = (ipt as i32).clamp(1, MAX_IPT) as f64;
+        }
+
+        for (name, value) in &saved.variables {
+            let name = U16String::from_str(name);
+            if !self.state.variables.contains_key(name.as_ustr()) {
+                continue;
+            }
+
+            let value = match value {
+                &Object::Int(v) => v.into(),
+                &Object::Long(v) => (v as f64).into(),
+                &Object::Float(v) => v.into(),
+                &Object::Double(v) => v.into(),
+                &Object::Bool(v) => v.into(),
+                Object::String(Some(v)) => v.to_string().into(),
+                // map variables use the IDs of the default content version
+                &Object::Content(id) => ContentVersion::default()
+                    .convert_id(id, vm.content())
+                    .and_then(|id| Content::from_id(id, vm.content()).ok())
+                    .into(),
+                &Object::Building(position) => vm
+                    .building(PackedPoint2 {
+                        x: (position >> 16) as i16,
+                        y: position as i16,
+                    })
+                    .cloned()
+                    .into(),
+                &Object::Team(team) => team.into(),
+                &Object::LAccess(sensor) => sensor.into(),
+                _ => LValue::NULL,
+            };
+            self.state.set_variable(&name, value);
+        }
+    }
+
     pub(super) fn late_init(
         &mut self,
         vm: &LogicVM,
@@ -57,6 +127,20 @@ impl Processor {
         // ie. if a custom link name is specified for a building that would be built after this processor
         let mut taken_names = RapidHashMap::default();
 
+        if let Some(limits) = &self.limits
+            && self.instructions.len() > limits.max_instructions
+        {
+            let span = match &self.instructions[limits.max_instructions] {
+                Instruction::InstructionBuilder(builder) => builder.span,
+                _ => ast::Span::default(),
+            };
+            return Err(VMLoadError::TooManyInstructions {
+                span,
+                count: self.instructions.len(),
+                max: limits.max_instructions,
+            });
+        }
+
         self.state.links.retain_mut(|link| {
             // resolve the actual building at the link position
             // before this, link.building is just air
@@ -91,16 +175,7 @@ impl Processor {
 
             // finally, get the link name
 
-            // link name prefix, eg "processor"
-            let mut parts = other.block.name.rsplit('-');
-            let last_part = parts.next().unwrap_or("");
-            let name_prefix = if let Some(second_last_part) = parts.next()
-                && (last_part == "large" || last_part.parse::<f64>().is_ok())
-            {
-                second_last_part
-            } else {
-                last_part
-            };
+            let name_prefix = link_name_prefix(&other.block.name);
 
             // link indices that are already in use for this prefix
             if !taken_names.contains_key(name_prefix) {
@@ -128,6 +203,15 @@ impl Processor {
             false // should never happen
         });
 
+        if let Some(max) = self.limits.and_then(|limits| limits.max_links)
+            && self.state.links.len() > max
+        {
+            return Err(VMLoadError::TooManyLinks {
+                count: self.state.links.len(),
+                max,
+            });
+        }
+
         self.state
             .linked_positions
             .extend(self.state.links.iter().map(|l| l.building.position));
@@ -145,18 +229,37 @@ impl Processor {
                 |instruction| -> (VMLoadResult<()>, _) {
                     let result = match instruction {
                         Instruction::InstructionBuilder(builder) => {
-                            builder.late_init(globals, &mut self.state)
+                            builder.late_init(vm, building, globals, &mut self.state)
                         }
                         _ => Err(VMLoadError::AlreadyInitialized),
                     };
                     match result {
                         Ok(instruction) => (Ok(()), instruction),
+                        // like Mindustry, lenient VMs treat unknown instructions as noops
+                        Err(
+                            err @ VMLoadError::BadInstruction {
+                                error: InstructionError::UnknownInstruction(_),
+                                ..
+                            },
+                        ) if vm.lenient => {
+                            self.warnings.push(err);
+                            (Ok(()), Noop.into())
+                        }
                         Err(err) => (Err(err), Noop.into()),
                     }
                 },
             )?;
         }
 
+        if let Some(max) = self.limits.and_then(|limits| limits.max_variables)
+            && self.state.variables.len() > max
+        {
+            return Err(VMLoadError::TooManyVariables {
+                count: self.state.variables.len(),
+                max,
+            });
+        }
+
         // finally, now that we know everything has succeeded, tell the VM if this processor is running
         if self.state.enabled {
             vm.running_processors.update(|n| n + 1);
@@ -180,6 +283,8 @@ impl Processor {
         for<'a> &'a T: IntoIterator<Item = &'a ast::Statement>,
     {
         self.instructions.clear();
+        self.source = None;
+        self.warnings.clear();
         self.state = ProcessorState::new(self.state.privileged, self.state.ipt, vm.as_ref());
         self.set_initial_config(code, links, position);
         self
@@ -187,6 +292,10 @@ impl Processor {
 
     /// Overwrites the code (and optionally the links) of this processor in-place, resetting most internal state.
     ///
+    /// To keep the processor's variables while it's running, eg. when live-editing code, use [`Self::hot_patch`] instead.
+    ///
+    /// This clears the processor's [source code](Self::source), so callers that know the new source should call [`Self::set_source`] afterwards.
+    ///
     /// If an error occurs, all changes will be rolled back.
     pub fn update_config<T>(
         &mut self,
@@ -201,6 +310,9 @@ impl Processor {
         for<'a> &'a T: IntoIterator<Item = &'a ast::Statement>,
     {
         let prev_instructions = core::mem::take(&mut self.instructions);
+        let prev_labels = self.labels.clone();
+        let prev_source = self.source.take();
+        let prev_warnings = core::mem::take(&mut self.warnings);
 
         // late_init assumes the processor is disabled and increments running_processors if it becomes enabled
         // so decrement running_processors if the processor is currently enabled to avoid double-counting
@@ -220,12 +332,89 @@ impl Processor {
         let result = self.late_init(vm, building, globals);
         if result.is_err() {
             let _ = core::mem::replace(&mut self.instructions, prev_instructions);
+            self.labels = prev_labels;
+            self.source = prev_source;
+            self.warnings = prev_warnings;
             vm.running_processors.set(prev_running_processors);
             let _ = core::mem::replace(&mut self.state, prev_state);
         }
         result
     }
 
+    /// Overwrites the code (and optionally the links) of this processor in-place, keeping as much of its state as possible.
+    ///
+    /// Unlike [`Self::update_config`], which matches Mindustry's behaviour, this is meant for development tools that reload code while it's running. Variables with the same name in the old and new code keep their values, and the print buffer, draw buffer and any pending `wait` are kept. The counter is moved to the same offset from the closest preceding label that exists in both versions of the code, or is left unchanged if there is no such label. Everything else is reset like in [`Self::update_config`].
+    ///
+    /// If `links` is `None`, the processor's current links are kept.
+    ///
+    /// If an error occurs, all changes will be rolled back.
+    pub fn hot_patch<T>(
+        &mut self,
+        code: T,
+        links: Option<&[ProcessorLinkConfig]>,
+        vm: &LogicVM,
+        building: &Building,
+        globals: &Constants,
+    ) -> VMLoadResult<()>
+    where
+        T: IntoIterator<Item = ast::Statement>,
+        for<'a> &'a T: IntoIterator<Item = &'a ast::Statement>,
+    {
+        let current_links;
+        let links = match links {
+            Some(links) => links,
+            None => {
+                current_links = self
+                    .state
+                    .links
+                    .iter()
+                    .map(|link| ProcessorLinkConfig {
+                        name: link.name.as_str().into(),
+                        x: link.building.position.x - building.position.x,
+                        y: link.building.position.y - building.position.y,
+                    })
+                    .collect::<Vec<_>>();
+                &current_links
+            }
+        };
+
+        let prev_labels = self.labels.clone();
+        let prev_state = self.state.clone();
+
+        self.update_config(code, Some(links), vm, building, globals)?;
+
+        // the next instruction wraps around to the start if the counter is out of range
+        let counter = if prev_state.counter < prev_state.num_instructions {
+            prev_state.counter
+        } else {
+            0
+        };
+        let anchor = prev_labels
+            .iter()
+            .filter(|&(name, &index)| index <= counter && self.labels.contains_key(name))
+            // break ties by name so the result doesn't depend on the map's iteration order
+            .max_by(|(a_name, a_index), (b_name, b_index)| {
+                a_index.cmp(b_index).then_with(|| b_name.cmp(a_name))
+            });
+        self.state.counter = match anchor {
+            Some((name, &index)) => self.labels[name] + (counter - index),
+            None => counter,
+        };
+
+        for (name, value) in prev_state.variables {
+            if let Some(variable) = self.state.variables.get_mut(&name) {
+                *variable = value;
+            }
+        }
+
+        self.state.wait_end_time = prev_state.wait_end_time;
+        self.state.printbuffer = prev_state.printbuffer;
+        self.state.drawbuffer = prev_state.drawbuffer;
+        self.state.drawbuffer_len = prev_state.drawbuffer_len;
+
+        Ok(())
+    }
+
     /// Overwrites the code/links of this processor **without** fully initializing them. Assumes the processor is currently in its default state.
     fn set_initial_config<T>(
         &mut self,
@@ -240,23 +429,26 @@ impl Processor {
             let mut labels = RapidHashMap::default();
             for statement in (&code).into_iter() {
                 match statement {
-                    ast::Statement::Label(label) => {
+                    ast::Statement::Label(label, _) => {
                         labels.insert(label.clone(), self.state.num_instructions);
                     }
-                    ast::Statement::Instruction(_, _) => {
+                    ast::Statement::Instruction(_, _, _) => {
                         self.state.num_instructions += 1;
                     }
                 }
             }
             Rc::new(labels)
         };
+        self.labels = labels.clone();
 
         self.instructions.reserve_exact(self.state.num_instructions);
         for statement in code.into_iter() {
-            if let ast::Statement::Instruction(instruction, _) = statement {
+            if let ast::Statement::Instruction(instruction, args, span) = statement {
                 self.instructions.push(
                     InstructionBuilder {
                         instruction,
+                        span,
+                        args,
                         labels: labels.clone(),
                     }
                     .into(),
@@ -268,7 +460,12 @@ impl Processor {
 
         let fake_data = Rc::new(RefCell::new(BuildingData::Unknown {
             senseable_config: None,
+            config: Object::Null,
         }));
+        let fake_props = Rc::new(RefCell::new(BuildingProps::new(
+            &content::blocks::AIR,
+            ContentVersion::default(),
+        )));
 
         if let Some(links) = links {
             self.state
@@ -282,6 +479,7 @@ impl Processor {
                             y: position.y + link.y,
                         },
                         data: fake_data.clone(),
+                        props: fake_props.clone(),
                     },
                 }));
         }
@@ -498,6 +696,19 @@ impl ProcessorState {
     }
 }
 
+/// Returns the prefix of the automatically generated link names for a block, eg. `processor` for `micro-processor`.
+pub(crate) fn link_name_prefix(block_name: &str) -> &str {
+    let mut parts = block_name.rsplit('-');
+    let last_part = parts.next().unwrap_or("");
+    if let Some(second_last_part) = parts.next()
+        && (last_part == "large" || last_part.parse::<f64>().is_ok())
+    {
+        second_last_part
+    } else {
+        last_part
+    }
+}
+
 /// A representation of a link from this processor to a building.
 #[derive(Debug, Clone)]
 #[non_exhaustive]
@@ -506,46 +717,145 @@ pub struct ProcessorLink {
     pub building: Building,
 }
 
+/// Load-time limits for processor code.
+///
+/// The default limits match Mindustry's. Mindustry uses the same limits for every type of processor, apart from the link range, which is checked separately (see the `enforce_processor_range` feature).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ProcessorLimits {
+    /// Maximum number of instructions. Mindustry ignores any instructions after the first 1000.
+    pub max_instructions: usize,
+    /// Maximum length of the code in bytes. Mindustry refuses to load processors with more than 100 KiB of code.
+    ///
+    /// This is checked against [`ProcessorBuilder::source`], so processors without source code fail to load with [`VMLoadError::UncheckedLimits`] if this is set.
+    pub max_code_bytes: Option<usize>,
+    /// Maximum number of tokens in each instruction, including the instruction name. Mindustry fails to parse longer lines.
+    ///
+    /// Like `max_code_bytes`, this requires [`ProcessorBuilder::source`].
+    pub max_tokens: Option<usize>,
+    /// Maximum number of valid links. Mindustry drops any links after the first 6000.
+    pub max_links: Option<usize>,
+    /// Maximum number of variables, not including constants and links. Mindustry refuses to load processors with more than 1000 variables.
+    pub max_variables: Option<usize>,
+}
+
+impl Default for ProcessorLimits {
+    fn default() -> Self {
+        Self {
+            max_instructions: 1000,
+            max_code_bytes: Some(100 * 1024),
+            max_tokens: Some(16),
+            max_links: Some(6000),
+            max_variables: Some(1000),
+        }
+    }
+}
+
+impl ProcessorLimits {
+    /// Checks the limits that apply to the source code that a processor's instructions were parsed from.
+    fn check_source(&self, source: Option<&str>, code: &[ast::Statement]) -> VMLoadResult<()> {
+        if self.max_code_bytes.is_none() && self.max_tokens.is_none() {
+            return Ok(());
+        }
+        let Some(source) = source else {
+            return Err(VMLoadError::UncheckedLimits);
+        };
+
+        if let Some(max) = self.max_code_bytes
+            && source.len() > max
+        {
+            return Err(VMLoadError::CodeTooLong {
+                bytes: source.len(),
+                max,
+            });
+        }
+
+        if let Some(max) = self.max_tokens {
+            #[cfg(feature = "std")]
+            {
+                let lines = parser::LineIndex::new(source);
+                for statement in code {
+                    if let ast::Statement::Instruction(_, _, span) = statement {
+                        let count = parser::count_tokens(lines.text(*span));
+                        if count > max {
+                            return Err(VMLoadError::TooManyTokens {
+                                span: *span,
+                                count,
+                                max,
+                            });
+                        }
+                    }
+                }
+            }
+
+            // the tokenizer is only available with std
+            #[cfg(not(feature = "std"))]
+            {
+                let _ = (max, code);
+                return Err(VMLoadError::UncheckedLimits);
+            }
+        }
+
+        Ok(())
+    }
+}
+
 #[derive(Derivative)]
 #[derivative(Debug)]
 pub struct ProcessorBuilder<'a> {
     pub ipt: f64,
     pub privileged: bool,
     pub code: Box<[ast::Statement]>,
+    /// The source code that `code` was parsed from, if known. This is returned by [`Processor::source`], and is required to check some [limits](Self::limits).
+    pub source: Option<&'a str>,
     pub links: &'a [ProcessorLinkConfig],
     /// If provided, this is called just before this processor executes each instruction.
     /// The intercepted instruction is skipped if this hook returns `Some`.
     #[derivative(Debug = "ignore")]
     pub instruction_hook: Option<Box<InstructionHook>>,
+    /// If provided, the processor fails to load if its code or links exceed these limits.
+    pub limits: Option<ProcessorLimits>,
 }
 
 impl ProcessorBuilder<'_> {
     #[cfg(feature = "std")]
     pub fn parse_code(code: &str) -> VMLoadResult<Box<[ast::Statement]>> {
-        match LogicParser::new().parse(code) {
-            Ok(value) => Ok(value.into_boxed_slice()),
-            // FIXME: hack
-            Err(e) => Err(VMLoadError::BadProcessorCode(e.to_string())),
-        }
+        Ok(parser::parse(code)?.into_boxed_slice())
     }
 
-    pub fn build(self, position: PackedPoint2, vm: impl AsRef<LogicVM>) -> Box<Processor> {
+    /// Creates the processor.
+    ///
+    /// Fails if the source code exceeds the [limits](Self::limits). The remaining limits are checked when the processor is added to a VM.
+    pub fn build(
+        self,
+        position: PackedPoint2,
+        vm: impl AsRef<LogicVM>,
+    ) -> VMLoadResult<Box<Processor>> {
         let ProcessorBuilder {
             ipt,
             privileged,
             code,
+            source,
             links,
             instruction_hook,
+            limits,
         } = self;
 
+        if let Some(limits) = &limits {
+            limits.check_source(source, &code)?;
+        }
+
         let mut processor = Processor {
             instructions: Vec::new(),
+            labels: Rc::default(),
             instruction_hook,
+            source: source.map(String::from),
+            warnings: Vec::new(),
+            limits,
             state: ProcessorState::new(privileged, ipt, vm.as_ref()),
         };
 
         processor.set_initial_config(code, Some(links), position);
 
-        Box::new(processor)
+        Ok(Box::new(processor))
     }
 }
@@ -0,0 +1,55 @@
+use alloc::vec::Vec;
+
+use itertools::Itertools;
+
+use super::Building;
+use crate::{
+    types::{Team, content::Block},
+    utils::RapidHashMap,
+};
+
+/// The buildings owned by a single team, equivalent to the building lists in Mindustry's `TeamData`.
+///
+/// All lists are ordered by the position of each building's center tile (`y`, then `x`), which matches the order that Mindustry adds buildings to these lists when loading a map.
+#[derive(Debug, Default)]
+pub struct TeamIndex {
+    pub buildings: Vec<Building>,
+    pub cores: Vec<Building>,
+    buildings_by_block: RapidHashMap<i32, Vec<Building>>,
+}
+
+impl TeamIndex {
+    pub(super) fn build_all<'a>(
+        buildings: impl IntoIterator<Item = &'a Building>,
+    ) -> RapidHashMap<Team, Self> {
+        let mut indexes = RapidHashMap::<Team, Self>::default();
+
+        for building in buildings.into_iter().sorted_unstable_by_key(|b| {
+            let center = b.center();
+            (center.y, center.x)
+        }) {
+            let index = indexes.entry(building.team()).or_default();
+
+            index.buildings.push(building.clone());
+
+            if building.block.name.starts_with("core-") {
+                index.cores.push(building.clone());
+            }
+
+            index
+                .buildings_by_block
+                .entry(building.block.id)
+                .or_default()
+                .push(building.clone());
+        }
+
+        indexes
+    }
+
+    /// Returns all buildings of a specific block type owned by this team.
+    pub fn buildings_of(&self, block: &Block) -> &[Building] {
+        self.buildings_by_block
+            .get(&block.id)
+            .map_or(&[], |v| v.as_slice())
+    }
+}
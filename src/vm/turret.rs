@@ -0,0 +1,159 @@
+use super::{Building, LObject};
+use crate::types::content::Block;
+
+/// The default value of Mindustry's `Turret.rotateSpeed`.
+const DEFAULT_ROTATE_SPEED: f64 = 5.;
+
+/// The number of ticks that a turret stays under logic control after the last `control shoot` or `control shootp`, equivalent to Mindustry's `logicControlCooldown`.
+const LOGIC_CONTROL_COOLDOWN: f64 = 60. * 2.;
+
+/// The state of a turret, equivalent to the logic-controlled parts of Mindustry's `TurretBuild`.
+///
+/// Turrets only aim while controlled by a processor. They don't search for targets, use ammo or fire bullets.
+#[derive(Debug, Clone)]
+pub struct TurretData {
+    /// The turret's rotation in degrees, returned by `sensor @rotation`.
+    pub rotation: f64,
+    /// The maximum rotation per tick, in degrees.
+    ///
+    /// The content data doesn't include `rotateSpeed`, so this is only a fallback: scatter and foreshadow use their values from Mindustry's `Blocks.java`, and every other turret uses the default of 5. Hosts that need exact values for other turrets should overwrite this field.
+    pub rotate_speed: f64,
+    /// The position that the turret is aiming at, in tiles.
+    pub shoot_x: f64,
+    pub shoot_y: f64,
+    /// True if the last `control shoot` or `control shootp` told the turret to shoot.
+    pub shooting: bool,
+    /// The unit or building targeted by the last `control shootp`, or `None` if the turret was aimed with `control shoot`.
+    pub target: Option<LObject>,
+    /// The number of ticks until the turret stops being controlled by a processor, equivalent to `TurretBuild.logicControlTime`.
+    pub logic_control_time: f64,
+}
+
+impl TurretData {
+    pub fn new(block: &Block) -> Self {
+        Self {
+            rotation: 90.,
+            rotate_speed: match block.name.as_str() {
+                "scatter" => 15.,
+                "foreshadow" => 1.,
+                _ => DEFAULT_ROTATE_SPEED,
+            },
+            shoot_x: 0.,
+            shoot_y: 0.,
+            shooting: false,
+            target: None,
+            logic_control_time: 0.,
+        }
+    }
+
+    /// Aims the turret at a position, like `control shoot`.
+    pub fn shoot(&mut self, x: f64, y: f64, shooting: bool) {
+        self.shoot_x = x;
+        self.shoot_y = y;
+        self.shooting = shooting;
+        self.target = None;
+        self.logic_control_time = LOGIC_CONTROL_COOLDOWN;
+    }
+
+    /// Aims the turret at a unit or building, like `control shootp`.
+    ///
+    /// Returns false if the target is not a unit or building.
+    pub fn shoot_at(&mut self, target: &LObject, shooting: bool) -> bool {
+        let (x, y) = match target {
+            LObject::Unit(unit) => {
+                let data = unit.data.borrow();
+                (data.x, data.y)
+            }
+            LObject::Building(building) => building.world_center(),
+            _ => return false,
+        };
+        self.shoot(x, y, shooting);
+        self.target = Some(target.clone());
+        true
+    }
+
+    /// Returns true if a processor has aimed the turret within the last 2 seconds.
+    pub fn controlled(&self) -> bool {
+        self.logic_control_time > 0.
+    }
+
+    /// Returns true if the turret is shooting, as returned by `sensor @shooting`.
+    pub fn is_shooting(&self) -> bool {
+        self.controlled() && self.shooting
+    }
+
+    /// Turns the turret towards its aim position, and releases it from logic control once the cooldown runs out.
+    pub(super) fn update(&mut self, building: &Building, delta: f64) {
+        if !self.controlled() {
+            return;
+        }
+        self.logic_control_time -= delta;
+
+        let (x, y) = building.world_center();
+        let dx = self.shoot_x - x;
+        let dy = self.shoot_y - y;
+        if dx == 0. && dy == 0. {
+            return;
+        }
+
+        let target = wrap_angle(atan2(dy, dx).to_degrees());
+        self.rotation = move_toward(self.rotation, target, self.rotate_speed * delta);
+    }
+}
+
+#[cfg(feature = "std")]
+fn atan2(y: f64, x: f64) -> f64 {
+    y.atan2(x)
+}
+
+#[cfg(all(not(feature = "std"), feature = "no_std"))]
+fn atan2(y: f64, x: f64) -> f64 {
+    libm::atan2(y, x)
+}
+
+/// Equivalent to `Mathf.mod(angle, 360)`.
+fn wrap_angle(angle: f64) -> f64 {
+    ((angle % 360.) + 360.) % 360.
+}
+
+/// Equivalent to Mindustry's `Angles.angleDist`.
+fn angle_dist(a: f64, b: f64) -> f64 {
+    let a = wrap_angle(a);
+    let b = wrap_angle(b);
+    let forward = (a - b).abs();
+    forward.min(360. - forward)
+}
+
+/// Equivalent to Mindustry's `Angles.moveToward`.
+fn move_toward(angle: f64, to: f64, speed: f64) -> f64 {
+    if angle_dist(angle, to) < speed {
+        return to;
+    }
+
+    let angle = wrap_angle(angle);
+    let to = wrap_angle(to);
+    let forward = (angle - to).abs();
+    let backward = 360. - forward;
+    if (angle > to) == (backward > forward) {
+        angle - speed
+    } else {
+        angle + speed
+    }
+}
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    #[test]
+    fn test_move_toward() {
+        assert_eq!(move_toward(90., 0., 5.), 85.);
+        assert_eq!(move_toward(90., 180., 5.), 95.);
+        assert_eq!(move_toward(10., 350., 5.), 5.);
+        assert_eq!(move_toward(350., 10., 5.), 355.);
+        assert_eq!(move_toward(358., 1., 5.), 1.);
+        assert_eq!(move_toward(90., 92., 5.), 92.);
+    }
+}
@@ -0,0 +1,69 @@
+use alloc::rc::Rc;
+use core::cell::RefCell;
+
+use crate::types::{Team, content::Unit};
+
+/// A unit in the VM's world.
+///
+/// Units are not simulated. They are added, moved and removed by the host, and are only visible to processors via instructions like `radar`, `fetch` and `sensor`.
+#[derive(Debug, Clone)]
+pub struct UnitEntity {
+    pub unit_type: &'static Unit,
+    pub id: u32,
+    pub data: Rc<RefCell<UnitData>>,
+}
+
+impl PartialEq for UnitEntity {
+    fn eq(&self, other: &Self) -> bool {
+        Rc::ptr_eq(&self.data, &other.data)
+    }
+}
+
+/// The mutable state of a [`UnitEntity`].
+///
+/// Positions are in tiles, like the values returned by `sensor @x` and `sensor @y`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct UnitData {
+    pub team: Team,
+    pub x: f64,
+    pub y: f64,
+    pub rotation: f64,
+    pub health: f64,
+    pub max_health: f64,
+    pub shield: f64,
+    pub armor: f64,
+    pub flying: bool,
+    pub boss: bool,
+    /// True if this unit is controlled by a player.
+    pub player: bool,
+    /// True if this unit has any weapons.
+    pub can_shoot: bool,
+    pub dead: bool,
+}
+
+impl UnitData {
+    pub fn new(team: Team, x: f64, y: f64) -> Self {
+        Self {
+            team,
+            x,
+            y,
+            rotation: 0.,
+            health: 100.,
+            max_health: 100.,
+            shield: 0.,
+            armor: 0.,
+            flying: false,
+            boss: false,
+            player: false,
+            can_shoot: true,
+            dead: false,
+        }
+    }
+
+    /// Returns the squared distance in tiles from this unit to a point.
+    pub fn dst2(&self, x: f64, y: f64) -> f64 {
+        let dx = self.x - x;
+        let dy = self.y - y;
+        dx * dx + dy * dy
+    }
+}
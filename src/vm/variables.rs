@@ -12,13 +12,13 @@ use thiserror::Error;
 use widestring::{U16Str, U16String};
 
 use super::{
-    Building, TextAlignment,
+    Building, TextAlignment, UnitEntity, World,
     processor::{ProcessorLink, ProcessorState},
 };
 use crate::{
     types::{
         ContentID, ContentType, LAccess, Team, colors,
-        content::{self, Block, Item, Liquid, Unit},
+        content::{Block, ContentVersion, Item, Liquid, Unit},
     },
     utils::{RapidIndexMap, u16format},
 };
@@ -54,6 +54,11 @@ pub enum LVar {
 impl LVar {
     // https://github.com/Anuken/Mindustry/blob/e95c543fb224b8d8cb21f834e0d02cbdb9f34d48/core/src/mindustry/logic/GlobalVars.java#L41
     pub fn create_global_constants() -> Constants {
+        Self::create_global_constants_for(ContentVersion::default())
+    }
+
+    /// Creates the global constants for a specific version of Mindustry's content, eg. `@blockCount` and `@copper`.
+    pub fn create_global_constants_for(content: ContentVersion) -> Constants {
         let mut globals: Constants = [
             ("@counter", Self::Counter),
             ("@ipt", Self::Ipt),
@@ -73,16 +78,10 @@ impl LVar {
             ("@waveTime", constant(0)),
             ("@server", constant(1)),
             ("@client", constant(0)),
-            (
-                "@blockCount",
-                constant(content::blocks::FROM_LOGIC_ID.len()),
-            ),
-            ("@itemCount", constant(content::items::FROM_LOGIC_ID.len())),
-            (
-                "@liquidCount",
-                constant(content::liquids::FROM_LOGIC_ID.len()),
-            ),
-            ("@unitCount", constant(content::units::FROM_LOGIC_ID.len())),
+            ("@blockCount", constant(content.blocks().logic_count())),
+            ("@itemCount", constant(content.items().logic_count())),
+            ("@liquidCount", constant(content.liquids().logic_count())),
+            ("@unitCount", constant(content.units().logic_count())),
             ("@center", constant(TextAlignment::CENTER)),
             ("@top", constant(TextAlignment::TOP)),
             ("@bottom", constant(TextAlignment::BOTTOM)),
@@ -104,21 +103,27 @@ impl LVar {
         );
 
         globals.extend(
-            content::items::VALUES
+            content
+                .items()
+                .values()
                 .iter()
                 .map(|v| named_constant(&v.name, Content::Item(v))),
         );
 
         globals.extend(
-            content::liquids::VALUES
+            content
+                .liquids()
+                .values()
                 .iter()
                 .map(|v| named_constant(&v.name, Content::Liquid(v))),
         );
 
         globals.extend(
-            content::blocks::VALUES
+            content
+                .blocks()
+                .values()
                 .iter()
-                .filter(|v| !content::items::FROM_NAME.contains_key(v.name.as_str()) && !v.legacy)
+                .filter(|v| content.items().from_name(&v.name).is_none() && !v.legacy)
                 .map(|v| named_constant(&v.name, Content::Block(v))),
         );
 
@@ -137,7 +142,9 @@ impl LVar {
         globals.extend(LAccess::VARIANTS.iter().map(|&v| named_constant(v, v)));
 
         globals.extend(
-            content::units::VALUES
+            content
+                .units()
+                .values()
                 .iter()
                 .map(|v| named_constant(&v.name, Content::Unit(v))),
         );
@@ -145,6 +152,20 @@ impl LVar {
         globals
     }
 
+    /// Adds or replaces the global constants that depend on the world, eg. `@waveNumber` and `@mapw`.
+    pub fn add_world_constants(globals: &mut Constants, world: &World) {
+        globals.extend(
+            [
+                ("@waveNumber", constant(world.wave)),
+                ("@waveTime", constant(world.wave_time / 60.)),
+                ("@mapw", constant(world.width)),
+                ("@maph", constant(world.height)),
+            ]
+            .into_iter()
+            .map(|(k, v)| (k.into(), v)),
+        );
+    }
+
     pub(super) fn create_local_constants(
         locals: &mut Constants,
         building: &Building,
@@ -368,6 +389,17 @@ impl LValue {
         }
     }
 
+    /// Converts this value to a team, either from a team object or from a team id.
+    #[inline(always)]
+    pub fn team(&self) -> Option<Team> {
+        match self.objval {
+            Some(LObject::Team(team)) => Some(team),
+            Some(_) => None,
+            // like Team.get, only the lowest 8 bits of the id are used
+            None => Some(Team((self.numi() & 0xff) as u8)),
+        }
+    }
+
     #[inline(always)]
     pub fn obj(&self) -> &Option<LObject> {
         &self.objval
@@ -489,6 +521,13 @@ impl From<Building> for LValue {
     }
 }
 
+impl From<UnitEntity> for LValue {
+    #[inline]
+    fn from(value: UnitEntity) -> Self {
+        unsafe { Self::non_null(LObject::Unit(value)) }
+    }
+}
+
 impl From<LAccess> for LValue {
     #[inline]
     fn from(value: LAccess) -> Self {
@@ -519,6 +558,7 @@ pub enum LObject {
     Content(Content),
     Team(Team),
     Building(Building),
+    Unit(UnitEntity),
     Sensor(LAccess),
 }
 
@@ -556,6 +596,13 @@ impl From<Building> for LObject {
     }
 }
 
+impl From<UnitEntity> for LObject {
+    #[inline]
+    fn from(value: UnitEntity) -> Self {
+        Self::Unit(value)
+    }
+}
+
 impl From<LAccess> for LObject {
     #[inline]
     fn from(value: LAccess) -> Self {
@@ -661,34 +708,64 @@ impl Content {
     }
 }
 
-impl TryFrom<ContentID> for Content {
-    type Error = ContentIDLookupError;
-
-    fn try_from(ContentID { type_, id }: ContentID) -> Result<Self, Self::Error> {
+impl Content {
+    /// Looks up a content ID in a specific version of Mindustry's content.
+    pub fn from_id(
+        ContentID { type_, id }: ContentID,
+        content: ContentVersion,
+    ) -> Result<Self, ContentIDLookupError> {
         let id = id as i32;
         let unknown_id_err = ContentIDLookupError::UnknownID(id);
         match type_ {
-            ContentType::Block => content::blocks::FROM_ID
-                .get(&id)
-                .map(|&v| Self::Block(v))
+            ContentType::Block => content
+                .blocks()
+                .from_id(id)
+                .map(Self::Block)
                 .ok_or(unknown_id_err),
-            ContentType::Item => content::items::FROM_ID
-                .get(&id)
-                .map(|&v| Self::Item(v))
+            ContentType::Item => content
+                .items()
+                .from_id(id)
+                .map(Self::Item)
                 .ok_or(unknown_id_err),
-            ContentType::Liquid => content::liquids::FROM_ID
-                .get(&id)
-                .map(|&v| Self::Liquid(v))
+            ContentType::Liquid => content
+                .liquids()
+                .from_id(id)
+                .map(Self::Liquid)
                 .ok_or(unknown_id_err),
-            ContentType::Unit => content::units::FROM_ID
-                .get(&id)
-                .map(|&v| Self::Unit(v))
+            ContentType::Unit => content
+                .units()
+                .from_id(id)
+                .map(Self::Unit)
                 .ok_or(unknown_id_err),
             _ => Err(ContentIDLookupError::UnsupportedType(type_)),
         }
     }
 }
 
+/// Looks up a content ID in the default [`ContentVersion`]. Use [`Content::from_id`] for VMs that use another version.
+impl TryFrom<ContentID> for Content {
+    type Error = ContentIDLookupError;
+
+    fn try_from(value: ContentID) -> Result<Self, Self::Error> {
+        Self::from_id(value, ContentVersion::default())
+    }
+}
+
+impl From<Content> for ContentID {
+    fn from(value: Content) -> Self {
+        let (type_, id) = match value {
+            Content::Block(block) => (ContentType::Block, block.id),
+            Content::Item(item) => (ContentType::Item, item.id),
+            Content::Liquid(liquid) => (ContentType::Liquid, liquid.id),
+            Content::Unit(unit) => (ContentType::Unit, unit.id),
+        };
+        Self {
+            type_,
+            id: id as i16,
+        }
+    }
+}
+
 #[derive(Debug, Clone, Copy, Error)]
 pub enum ContentIDLookupError {
     #[error("unsupported content type: {0:?}")]
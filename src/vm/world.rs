@@ -0,0 +1,118 @@
+use alloc::{collections::BTreeMap, string::String, vec, vec::Vec};
+
+#[cfg(feature = "std")]
+use crate::types::Map;
+use crate::types::{
+    PackedPoint2, Rules,
+    content::{Block, ContentVersion},
+};
+
+/// The environment that a VM's buildings are placed in, usually loaded from a map.
+#[derive(Debug, Clone)]
+pub struct World {
+    pub width: u16,
+    pub height: u16,
+    tiles: Vec<WorldTile>,
+    pub rules: Rules,
+    /// The map's metadata, eg. `name`, `author`, `description` and the unparsed `rules`.
+    pub tags: BTreeMap<String, String>,
+    /// The current wave number, returned by `@waveNumber`.
+    pub wave: i32,
+    /// The time until the next wave in ticks, used for `@waveTime`.
+    pub wave_time: f64,
+}
+
+/// The blocks on a single tile of the world, ignoring buildings.
+#[derive(Debug, Clone, Copy)]
+pub struct WorldTile {
+    pub floor: &'static Block,
+    pub overlay: &'static Block,
+    /// The block on this tile, eg. a wall. Tiles covered by buildings contain the building's block.
+    pub block: &'static Block,
+}
+
+impl World {
+    /// Creates a world of the given size, where every tile has a stone floor and nothing else.
+    pub fn new(width: u16, height: u16, content: ContentVersion) -> Self {
+        let air = air(content);
+        Self {
+            width,
+            height,
+            tiles: vec![
+                WorldTile {
+                    floor: stone(content),
+                    overlay: air,
+                    block: air,
+                };
+                width as usize * height as usize
+            ],
+            rules: Rules::default(),
+            tags: BTreeMap::new(),
+            wave: 1,
+            wave_time: 0.,
+        }
+    }
+
+    /// Creates a world from the tiles, rules and tags of a map.
+    ///
+    /// Blocks that don't exist in the given content version are replaced with stone (for floors) or air.
+    #[cfg(feature = "std")]
+    pub fn from_map(map: &Map, content: ContentVersion) -> Self {
+        let block = |id| {
+            map.block_name(id)
+                .and_then(|name| content.blocks().from_name(name))
+        };
+
+        let stone = stone(content);
+        let air = air(content);
+
+        let mut world = Self::new(map.width, map.height, content);
+        for (tile, map_tile) in world.tiles.iter_mut().zip(&map.tiles) {
+            *tile = WorldTile {
+                // like Mindustry, replace air floors with stone
+                floor: block(map_tile.floor)
+                    .filter(|&floor| floor != air)
+                    .unwrap_or(stone),
+                overlay: block(map_tile.overlay).unwrap_or(air),
+                block: block(map_tile.block).unwrap_or(air),
+            };
+        }
+
+        world.rules = map.rules();
+        world.tags = map
+            .tags
+            .iter()
+            .map(|(k, v)| (k.clone(), v.clone()))
+            .collect();
+        world.wave = map.wave();
+        world.wave_time = map.wave_time();
+        world
+    }
+
+    /// Returns the tile at a position, or `None` if the position is out of bounds.
+    pub fn tile(&self, position: PackedPoint2) -> Option<&WorldTile> {
+        self.index(position).map(|i| &self.tiles[i])
+    }
+
+    /// Returns a mutable reference to the tile at a position, or `None` if the position is out of bounds.
+    pub fn tile_mut(&mut self, position: PackedPoint2) -> Option<&mut WorldTile> {
+        self.index(position).map(|i| &mut self.tiles[i])
+    }
+
+    fn index(&self, PackedPoint2 { x, y }: PackedPoint2) -> Option<usize> {
+        if x < 0 || y < 0 || x as u16 >= self.width || y as u16 >= self.height {
+            return None;
+        }
+        Some(y as usize * self.width as usize + x as usize)
+    }
+}
+
+/// Returns the stone floor block from a content version, which is used for tiles without a floor.
+pub(super) fn stone(content: ContentVersion) -> &'static Block {
+    content.blocks().from_name("stone").unwrap()
+}
+
+/// Returns the air block from a content version, which is used for empty tiles.
+pub(super) fn air(content: ContentVersion) -> &'static Block {
+    content.blocks().from_name("air").unwrap()
+}